@@ -0,0 +1,13 @@
+-- Baseline schema. Uses IF NOT EXISTS so databases created by the old
+-- `CreateTable` bootstrap are adopted without changes.
+CREATE TABLE IF NOT EXISTS autoroom (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    category_id BIGINT NOT NULL,
+    suffix VARCHAR(16) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS monitored_autoroom (
+    channel_id BIGINT PRIMARY KEY,
+    owner_id BIGINT NOT NULL
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS permament_autoroom (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    owner_id BIGINT NOT NULL,
+    placement_category_id BIGINT NOT NULL,
+    storage_category_id BIGINT NOT NULL,
+    UNIQUE (guild_id, owner_id)
+);
@@ -0,0 +1,7 @@
+ALTER TABLE autoroom
+    ADD COLUMN IF NOT EXISTS grace_period_secs INTEGER NOT NULL DEFAULT 0;
+
+-- Trigger the room was created from, NULL for rooms adopted by cleanup
+ALTER TABLE monitored_autoroom
+    ADD COLUMN IF NOT EXISTS autoroom_channel_id BIGINT NULL,
+    ADD COLUMN IF NOT EXISTS delete_at TIMESTAMPTZ NULL;
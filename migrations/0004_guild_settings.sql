@@ -0,0 +1,5 @@
+-- 0 = automatic transfer to the longest present member, 1 = manual "claim" button
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id BIGINT PRIMARY KEY,
+    ownership_transfer SMALLINT NOT NULL DEFAULT 0
+);
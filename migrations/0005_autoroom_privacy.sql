@@ -0,0 +1,3 @@
+-- 0 = public, 1 = locked (visible, invited members connect), 2 = hidden (invited members only)
+ALTER TABLE autoroom
+    ADD COLUMN IF NOT EXISTS privacy SMALLINT NOT NULL DEFAULT 0;
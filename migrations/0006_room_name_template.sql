@@ -0,0 +1,7 @@
+ALTER TABLE autoroom
+    ALTER COLUMN suffix TYPE VARCHAR(100),
+    ADD COLUMN IF NOT EXISTS name_template VARCHAR(100) NOT NULL DEFAULT '{nick}`s {suffix}';
+
+-- Value of the {n} placeholder the room was named with
+ALTER TABLE monitored_autoroom
+    ADD COLUMN IF NOT EXISTS room_number INTEGER NULL;
@@ -0,0 +1,7 @@
+-- Optional settings applied to every room created from the trigger, NULL keeps the Discord default
+ALTER TABLE autoroom
+    ADD COLUMN IF NOT EXISTS user_limit INTEGER NULL,
+    ADD COLUMN IF NOT EXISTS bitrate_cap INTEGER NULL,
+    ADD COLUMN IF NOT EXISTS rtc_region VARCHAR(32) NULL,
+    ADD COLUMN IF NOT EXISTS video_quality SMALLINT NULL,
+    ADD COLUMN IF NOT EXISTS nsfw BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Copy user limit, bitrate, region, video quality, NSFW and overwrites from the trigger channel
+ALTER TABLE autoroom
+    ADD COLUMN IF NOT EXISTS clone_trigger BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,12 @@
+-- Extra placement categories of an autoroom, used in order once the main category is full
+CREATE TABLE IF NOT EXISTS autoroom_overflow_category (
+    autoroom_channel_id BIGINT NOT NULL REFERENCES autoroom (channel_id) ON DELETE CASCADE,
+    category_id BIGINT NOT NULL,
+    position INT NOT NULL,
+    created_by_bot BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (autoroom_channel_id, category_id)
+);
+
+-- Channel for admin alerts, the guild system channel is used when empty
+ALTER TABLE guild_settings
+    ADD COLUMN IF NOT EXISTS alert_channel_id BIGINT NULL;
@@ -0,0 +1,4 @@
+-- Rooms are inserted uncommitted and committed once every creation step succeeded.
+-- Rows left uncommitted belong to a creation interrupted by a restart and are rolled back at startup.
+ALTER TABLE monitored_autoroom
+    ADD COLUMN IF NOT EXISTS committed BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,30 @@
+-- Channels created by the bot. Cleanups never delete or adopt a channel missing here
+CREATE TABLE IF NOT EXISTS bot_created_channel (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS bot_created_channel_guild_id_idx ON bot_created_channel (guild_id);
+
+-- Rooms created from a trigger and bot created overflow categories existed before the ledger
+INSERT INTO bot_created_channel (channel_id, guild_id)
+SELECT monitored_autoroom.channel_id, autoroom.guild_id
+FROM monitored_autoroom
+JOIN autoroom ON autoroom.channel_id = monitored_autoroom.autoroom_channel_id
+ON CONFLICT (channel_id) DO NOTHING;
+
+INSERT INTO bot_created_channel (channel_id, guild_id)
+SELECT autoroom_overflow_category.category_id, autoroom.guild_id
+FROM autoroom_overflow_category
+JOIN autoroom ON autoroom.channel_id = autoroom_overflow_category.autoroom_channel_id
+WHERE autoroom_overflow_category.created_by_bot
+ON CONFLICT (channel_id) DO NOTHING;
+
+-- Channels admins never want a cleanup to touch, even when the bot created them
+CREATE TABLE IF NOT EXISTS protected_channel (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS protected_channel_guild_id_idx ON protected_channel (guild_id);
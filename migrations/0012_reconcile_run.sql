@@ -0,0 +1,12 @@
+-- History of background reconciliations. Only the instance holding the advisory lock runs one
+CREATE TABLE IF NOT EXISTS reconcile_run (
+    id BIGSERIAL PRIMARY KEY,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ NULL,
+    succeeded BOOLEAN NULL,
+    stale_rows INT NOT NULL DEFAULT 0,
+    deleted INT NOT NULL DEFAULT 0,
+    adopted INT NOT NULL DEFAULT 0,
+    skipped INT NOT NULL DEFAULT 0,
+    error TEXT NULL
+);
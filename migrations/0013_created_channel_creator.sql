@@ -0,0 +1,10 @@
+-- Member a room was created for, used to recover the owner when a cleanup adopts the room
+ALTER TABLE bot_created_channel
+    ADD COLUMN IF NOT EXISTS creator_id BIGINT NULL;
+
+-- Existing rooms only know their current owner, the best guess there is
+UPDATE bot_created_channel
+SET creator_id = monitored_autoroom.owner_id
+FROM monitored_autoroom
+WHERE monitored_autoroom.channel_id = bot_created_channel.channel_id
+    AND bot_created_channel.creator_id IS NULL;
@@ -0,0 +1,3 @@
+-- Control panel message of the room, edited in place whenever the room changes.
+ALTER TABLE monitored_autoroom
+    ADD COLUMN IF NOT EXISTS panel_message_id BIGINT NULL;
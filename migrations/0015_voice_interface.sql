@@ -0,0 +1,8 @@
+-- Text channel holding the guild's voice interface panel, its buttons act on the room of whoever clicks
+ALTER TABLE guild_settings
+    ADD COLUMN IF NOT EXISTS interface_channel_id BIGINT NULL,
+    ADD COLUMN IF NOT EXISTS interface_message_id BIGINT NULL;
+
+-- Guilds using the voice interface may turn off the panel sent into every room
+ALTER TABLE autoroom
+    ADD COLUMN IF NOT EXISTS room_panel BOOLEAN NOT NULL DEFAULT TRUE;
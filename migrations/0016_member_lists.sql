@@ -0,0 +1,15 @@
+-- Members a host trusts, invited to every room the host creates
+CREATE TABLE IF NOT EXISTS trusted_member (
+    owner_id BIGINT NOT NULL,
+    member_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (owner_id, member_id)
+);
+
+-- Members a host blocked, denied and disconnected from every room the host creates
+CREATE TABLE IF NOT EXISTS blocked_member (
+    owner_id BIGINT NOT NULL,
+    member_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (owner_id, member_id)
+);
@@ -1,17 +1,36 @@
+use std::time::Duration;
+
 use poise::{CreateReply, serenity_prelude as serenity};
 use ::serenity::all::Mentionable;
 
 use crate::{
-    services::autoroom::{self, cleanup_categories_monitored_rooms, cleanup_db_monitored_rooms}, sql::autoroom::{AutoRoom, AutoRoomDeleteStrategy}
+    services::{
+        autoroom::{self, cleanup_categories_monitored_rooms, cleanup_db_monitored_rooms},
+        channel_cache,
+        cleanup::{CleanupOptions, CleanupReport, CleanupScope},
+        custom_id::{CustomId, PanelAction},
+        gateway::DiscordGateway,
+        room_creation,
+        permanent_room::reconcile_permanent_rooms,
+        reconciler,
+        voice_interface,
+        room_name::{lowest_free_number, render, uses_number, RoomNameValues, DEFAULT_TEMPLATE}
+    },
+    sql::{
+        autoroom::{AutoRoom, AutoRoomDeleteStrategy, RoomPrivacy, RoomVideoQuality},
+        guild_settings::OwnershipTransferMode,
+        reconcile_run::ReconcileRun,
+        repository::context_pool
+    }
 };
 
 use super::{ CommandContext, CommandError };
 use super::checks::{ is_bot_or_guild_owner, parse_ctx_guild_id, have_ctx_guild_id};
 
 
-#[poise::command(slash_command, subcommands("invite", "cleanup", "add", "list", "remove"), check = "have_ctx_guild_id")]
+#[poise::command(slash_command, subcommands("invite", "kick", "cleanup", "add", "edit", "list", "remove", "settings", "interface", "overflow", "protect", "stats"), check = "have_ctx_guild_id")]
 pub async fn autoroom(ctx: CommandContext<'_>) -> Result<(), CommandError> {
-    ctx.say(format!("Available commands: ({}, {})", "invite", "-")).await?;
+    ctx.say(format!("Available commands: ({}, {})", "invite", "kick")).await?;
     Ok(())
 }
 
@@ -20,10 +39,10 @@ pub async fn invite(
     ctx: CommandContext<'_>,
     #[description = "Invite a user to the connected voice channel"] user: serenity::User,
 ) -> Result<(), CommandError> {
-    let pool = &ctx.data().pool;
     let author = ctx.author();
+    let guild_id = parse_ctx_guild_id(&ctx)?;
     
-    autoroom::voice_channel::invite_user(ctx.http(), pool, author.id.get() as i64, &user).await?;
+    autoroom::voice_channel::invite_user(ctx.serenity_context(), &ctx.data().repositories, guild_id, author.id.get() as i64, &user).await?;
 
     ctx.send(
         CreateReply::default()
@@ -39,15 +58,14 @@ pub async fn kick(
     ctx: CommandContext<'_>,
     #[description = "Kick a user from the connected voice channel"] user: serenity::User,
 ) -> Result<(), CommandError> {
-    let pool = &ctx.data().pool;
     let author = ctx.author();
     let guild_id = ctx.guild_id().unwrap();
     
-    autoroom::voice_channel::kick_user(ctx.http(), pool, guild_id, author.id.get() as i64, &user).await?;
+    autoroom::voice_channel::kick_user(ctx.serenity_context(), &ctx.data().repositories, guild_id, author.id.get() as i64, &user).await?;
 
     ctx.send(
         CreateReply::default()
-            .content(format!("Member has been kicked"))
+            .content("Member has been kicked")
             .ephemeral(false)
     ).await?;
 
@@ -59,10 +77,10 @@ pub async fn context_invite(
     ctx: CommandContext<'_>,
     user: serenity::User
 ) -> Result<(), CommandError> {
-    let pool = &ctx.data().pool;
     let author = ctx.author();
+    let guild_id = parse_ctx_guild_id(&ctx)?;
     
-    if let Err(err) = autoroom::voice_channel::invite_user(ctx.http(), pool, author.id.get() as i64, &user).await {
+    if let Err(err) = autoroom::voice_channel::invite_user(ctx.serenity_context(), &ctx.data().repositories, guild_id, author.id.get() as i64, &user).await {
         ctx.send(
             CreateReply::default()
                 .content(format!("{}", err))
@@ -73,21 +91,87 @@ pub async fn context_invite(
     Ok(())
 }
 
+/// How long the confirm button of a dry run stays active
+const CLEANUP_CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+
+async fn run_cleanup(ctx: &CommandContext<'_>, options: &CleanupOptions) -> Result<CleanupReport, CommandError> {
+    match reconciler::exclusive(ctx.serenity_context(), cleanup_locked(ctx, options)).await? {
+        Some(report) => report,
+        None => Err("Another cleanup is running, try again later".into()),
+    }
+}
+
+async fn cleanup_locked(ctx: &CommandContext<'_>, options: &CleanupOptions) -> Result<CleanupReport, CommandError> {
+    let repositories = &ctx.data().repositories;
+    let mut report = cleanup_db_monitored_rooms(ctx.serenity_context(), repositories, options).await?;
+    report.merge(cleanup_categories_monitored_rooms(ctx.serenity_context(), repositories, options).await?);
+    // Permanent rooms are reconciled for every guild at once, so only a full run does it
+    if !options.dry_run && options.guild_id.is_none() {
+        reconcile_permanent_rooms(ctx.serenity_context(), repositories).await?;
+    }
+    Ok(report)
+}
+
 // #[poise::command(slash_command, owners_only, global_cooldown = 3600)]
 #[poise::command(slash_command, owners_only)]
-pub async fn cleanup(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+pub async fn cleanup(
+    ctx: CommandContext<'_>,
+    #[description = "Only show what would be cleaned up, with a button to run it"] dry_run: Option<bool>,
+    #[description = "Guilds to clean up, all by default"] scope: Option<CleanupScope>,
+) -> Result<(), CommandError> {
+    let guild_id = match scope.unwrap_or_default() {
+        CleanupScope::All => None,
+        CleanupScope::Guild => Some(parse_ctx_guild_id(&ctx)?),
+    };
+    let options = CleanupOptions { dry_run: dry_run.unwrap_or(false), guild_id };
     let handle = ctx.say("Starting cleanup").await?;
 
-    cleanup_db_monitored_rooms(ctx.serenity_context()).await?;
-    cleanup_categories_monitored_rooms(ctx.serenity_context()).await?;
-    
-    handle.edit(ctx, poise::CreateReply::default()
-        .content("Cleanup completed. Check logs for more information")
+    let report = run_cleanup(&ctx, &options).await?;
+    if !options.dry_run || report.is_empty() {
+        handle.edit(ctx, CreateReply::default().content("").embed(report.to_embed())).await?;
+        return Ok(());
+    }
+
+    let confirm = CustomId::new(PanelAction::CleanupConfirm, ctx.author().id, ctx.channel_id());
+    let confirm_id = confirm.encode();
+    handle.edit(ctx, CreateReply::default()
+        .content("")
+        .embed(report.to_embed())
+        .components(vec![serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(confirm_id.clone())
+                .label("Run cleanup")
+                .style(serenity::ButtonStyle::Danger),
+        ])])
     ).await?;
 
+    let message = handle.message().await?;
+    let interaction = serenity::ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .message_id(message.id)
+        .timeout(CLEANUP_CONFIRM_TIMEOUT)
+        .filter(move |interaction| CustomId::decode(&interaction.data.custom_id) == Ok(confirm))
+        .await;
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => {
+            handle.edit(ctx, CreateReply::default().embed(report.to_embed()).components(vec![])).await?;
+            return Ok(());
+        }
+    };
+    interaction.create_response(ctx, serenity::CreateInteractionResponse::UpdateMessage(
+        serenity::CreateInteractionResponseMessage::new()
+            .content("Running cleanup")
+            .components(vec![])
+    )).await?;
+
+    // The plan is computed again, the guild could have changed while the button waited
+    let report = run_cleanup(&ctx, &CleanupOptions { dry_run: false, ..options }).await?;
+    handle.edit(ctx, CreateReply::default().content("").embed(report.to_embed()).components(vec![])).await?;
+
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 #[poise::command(slash_command, check = "is_bot_or_guild_owner")]
 pub async fn add(
     ctx: CommandContext<'_>,
@@ -97,36 +181,185 @@ pub async fn add(
     #[description = "Category to move to"]
     #[channel_types("Category")]
         placement_category: serenity::GuildChannel,
-    #[description = "Channel Suffix"] #[max_length = 10] suffix: Option<String>,
+    #[description = "Channel Suffix"] #[max_length = 100] suffix: Option<String>,
+    #[description = "Seconds an empty room is kept before deletion"]
+    #[max = 3600]
+        grace_period: Option<u32>,
+    #[description = "Who can see and join created rooms"] privacy: Option<RoomPrivacy>,
+    #[description = "Room name, placeholders: {nick} {username} {n} {game} {suffix}"]
+    #[max_length = 100]
+        name_template: Option<String>,
+    #[description = "Maximum number of members in a room, 0 for no limit"] #[min = 0] #[max = 99] user_limit: Option<u32>,
+    #[description = "Highest bitrate in kbps, 0 for the server maximum"] #[min = 0] #[max = 384] bitrate: Option<u32>,
+    #[description = "Voice region like `rotterdam` or `us-east`, `auto` lets Discord choose"] #[max_length = 32] rtc_region: Option<String>,
+    #[description = "Video quality of rooms"] video_quality: Option<RoomVideoQuality>,
+    #[description = "Mark rooms as age-restricted"] nsfw: Option<bool>,
+    #[description = "Copy limit, bitrate, region and permissions of the trigger channel"] clone_trigger: Option<bool>,
+    #[description = "Send the control panel into every created room"] room_panel: Option<bool>,
+    #[description = "Only show the name a room would get, without saving"] preview: Option<bool>,
 ) -> Result<(), CommandError> {
     let guild_id = parse_ctx_guild_id(&ctx)?;
-    let pool = &ctx.data().pool;
+    let repositories = &ctx.data().repositories;
     let channel_id = from_channel.id;
     let category_id = placement_category.id;
     let suffix = match suffix {
         Some(suffix) => suffix,
         None => "room".to_string(),
     };
+    let name_template = name_template.unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+
+    if preview.unwrap_or(false) {
+        let member = ctx.author_member().await.ok_or("Member not found")?;
+        let mut name_values = RoomNameValues::from_member(ctx.serenity_context(), &member, &suffix);
+        if uses_number(&name_template) {
+            let taken = repositories.rooms.get_category_room_numbers(&[category_id.get() as i64]).await?;
+            name_values.number = Some(lowest_free_number(&taken));
+        }
+
+        ctx.send(
+            CreateReply::default()
+                .content(format!("Your room would be named `{}`", render(&name_template, &name_values)))
+                .ephemeral(true)
+        ).await?;
+        return Ok(());
+    }
 
     let autoroom = AutoRoom {
         channel_id: channel_id.get() as i64,
         guild_id: guild_id.get() as i64,
         category_id: category_id.get() as i64,
-        suffix: suffix.to_string() };
-    if let Err(err) = autoroom.create(pool).await {
+        suffix: suffix.to_string(),
+        grace_period_secs: grace_period.unwrap_or(0) as i32,
+        privacy: privacy.unwrap_or_default(),
+        name_template,
+        user_limit: user_limit.and_then(parse_user_limit),
+        bitrate_cap: bitrate.and_then(parse_bitrate_cap),
+        rtc_region: rtc_region.map(parse_rtc_region).transpose()?.flatten(),
+        video_quality,
+        nsfw: nsfw.unwrap_or(false),
+        clone_trigger: clone_trigger.unwrap_or(false),
+        room_panel: room_panel.unwrap_or(true) };
+    if let Err(err) = repositories.autorooms.create(&autoroom).await {
         return Err(err.into())
     };
+    channel_cache::set_trigger(channel_id, true);
 
     ctx.say(format!("Record was created! channel id: {}, category id: {}", channel_id, category_id)).await?;
     Ok(())
 }
 
+fn parse_user_limit(user_limit: u32) -> Option<i32> {
+    (user_limit > 0).then_some(user_limit as i32)
+}
+
+fn parse_bitrate_cap(kbps: u32) -> Option<i32> {
+    (kbps > 0).then_some(kbps.max(8) as i32 * 1000)
+}
+
+/// Voice regions Discord accepts for a channel
+const RTC_REGIONS: &[&str] = &[
+    "brazil", "hongkong", "india", "japan", "rotterdam", "russia", "singapore", "south-korea", "southafrica",
+    "sydney", "us-central", "us-east", "us-south", "us-west",
+];
+
+/// `None` for `auto`, the region chosen by Discord.
+fn parse_rtc_region(rtc_region: String) -> Result<Option<String>, CommandError> {
+    let rtc_region = rtc_region.trim().to_lowercase();
+    if rtc_region == "auto" {
+        return Ok(None);
+    }
+    match RTC_REGIONS.contains(&rtc_region.as_str()) {
+        true => Ok(Some(rtc_region)),
+        false => Err(format!("Unknown voice region, use `auto` or one of: {}", RTC_REGIONS.join(", ")).into()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn edit(
+    ctx: CommandContext<'_>,
+    #[description = "VoiceChannel to edit"]
+    #[channel_types("Voice")]
+        from_channel: serenity::GuildChannel,
+    #[description = "Category to move to"]
+    #[channel_types("Category")]
+        placement_category: Option<serenity::GuildChannel>,
+    #[description = "Channel Suffix"] #[max_length = 100] suffix: Option<String>,
+    #[description = "Seconds an empty room is kept before deletion"]
+    #[max = 3600]
+        grace_period: Option<u32>,
+    #[description = "Who can see and join created rooms"] privacy: Option<RoomPrivacy>,
+    #[description = "Room name, placeholders: {nick} {username} {n} {game} {suffix}"]
+    #[max_length = 100]
+        name_template: Option<String>,
+    #[description = "Maximum number of members in a room, 0 for no limit"] #[min = 0] #[max = 99] user_limit: Option<u32>,
+    #[description = "Highest bitrate in kbps, 0 for the server maximum"] #[min = 0] #[max = 384] bitrate: Option<u32>,
+    #[description = "Voice region like `rotterdam` or `us-east`, `auto` lets Discord choose"] #[max_length = 32] rtc_region: Option<String>,
+    #[description = "Video quality of rooms"] video_quality: Option<RoomVideoQuality>,
+    #[description = "Let Discord or the trigger channel decide the video quality again"] reset_video_quality: Option<bool>,
+    #[description = "Mark rooms as age-restricted"] nsfw: Option<bool>,
+    #[description = "Copy limit, bitrate, region and permissions of the trigger channel"] clone_trigger: Option<bool>,
+    #[description = "Send the control panel into every created room"] room_panel: Option<bool>,
+) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?;
+    let repositories = &ctx.data().repositories;
+
+    let mut autoroom = match repositories.autorooms.get_by_channel_id(from_channel.id.get() as i64).await? {
+        Some(autoroom) if autoroom.guild_id == guild_id.get() as i64 => autoroom,
+        _ => return Err("Autoroom with given channel was not found".into()),
+    };
+
+    if let Some(placement_category) = placement_category {
+        autoroom.category_id = placement_category.id.get() as i64;
+    }
+    if let Some(suffix) = suffix {
+        autoroom.suffix = suffix;
+    }
+    if let Some(grace_period) = grace_period {
+        autoroom.grace_period_secs = grace_period as i32;
+    }
+    if let Some(privacy) = privacy {
+        autoroom.privacy = privacy;
+    }
+    if let Some(name_template) = name_template {
+        autoroom.name_template = name_template;
+    }
+    if let Some(user_limit) = user_limit {
+        autoroom.user_limit = parse_user_limit(user_limit);
+    }
+    if let Some(bitrate) = bitrate {
+        autoroom.bitrate_cap = parse_bitrate_cap(bitrate);
+    }
+    if let Some(rtc_region) = rtc_region {
+        autoroom.rtc_region = parse_rtc_region(rtc_region)?;
+    }
+    if video_quality.is_some() {
+        autoroom.video_quality = video_quality;
+    } else if reset_video_quality.unwrap_or(false) {
+        autoroom.video_quality = None;
+    }
+    if let Some(nsfw) = nsfw {
+        autoroom.nsfw = nsfw;
+    }
+    if let Some(clone_trigger) = clone_trigger {
+        autoroom.clone_trigger = clone_trigger;
+    }
+    if let Some(room_panel) = room_panel {
+        autoroom.room_panel = room_panel;
+    }
+
+    repositories.autorooms.update(&autoroom).await?;
+
+    ctx.say(format!("Record was updated! {}", autoroom.to_display_string())).await?;
+    Ok(())
+}
+
 #[poise::command(slash_command, check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
 pub async fn list(ctx: CommandContext<'_>) -> Result<(), CommandError> {
     let guild_id = parse_ctx_guild_id(&ctx)?;
     
-    let pool = &ctx.data().pool;
-    let autorooms = AutoRoom::get_guild_autorooms(pool, guild_id.get() as i64).await?;
+    let repositories = &ctx.data().repositories;
+    let autorooms = repositories.autorooms.get_guild_autorooms(guild_id.get() as i64).await?;
     let result = match autorooms.is_empty() {
         true => "Records not found".to_string(),
         false => {
@@ -149,10 +382,233 @@ pub async fn remove(
     #[channel_types("Voice")]
         from_channel: serenity::GuildChannel
 ) -> Result<(), CommandError> {
-    let pool = &ctx.data().pool;
-    AutoRoom::delete(
-        pool,
+    ctx.data().repositories.autorooms.delete(
         AutoRoomDeleteStrategy::SingleByChannelId(from_channel.id.get() as i64)
     ).await?;
+    channel_cache::set_trigger(from_channel.id, false);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[poise::command(slash_command, check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn settings(
+    ctx: CommandContext<'_>,
+    #[description = "What happens to a room when its host leaves"] ownership_transfer: Option<OwnershipTransferMode>,
+    #[description = "Channel for admin alerts"]
+    #[channel_types("Text")]
+        alert_channel: Option<serenity::GuildChannel>,
+    #[description = "Send admin alerts to the system channel again"] reset_alert_channel: Option<bool>,
+) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?.get() as i64;
+    let settings = ctx.data().repositories.settings.as_ref();
+
+    if let Some(mode) = ownership_transfer {
+        settings.set_ownership_transfer(guild_id, mode).await?;
+    }
+    if let Some(alert_channel) = alert_channel {
+        settings.set_alert_channel(guild_id, Some(alert_channel.id.get() as i64)).await?;
+    } else if reset_alert_channel.unwrap_or(false) {
+        settings.set_alert_channel(guild_id, None).await?;
+    }
+
+    let settings = settings.get(guild_id).await?;
+    ctx.say(settings.to_display_string()).await?;
+    Ok(())
+}
+
+/// Sends the voice interface panel, acting on the room of whoever clicks it
+#[poise::command(slash_command, check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn interface(
+    ctx: CommandContext<'_>,
+    #[description = "Channel for the panel, the current one is redeployed when empty"]
+    #[channel_types("Text")]
+        channel: Option<serenity::GuildChannel>,
+    #[description = "Delete the panel and turn the voice interface off"] remove: Option<bool>,
+) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?.get() as i64;
+    let repositories = &ctx.data().repositories;
+    let settings = repositories.settings.get(guild_id).await?;
+    let previous = settings.interface_channel_id
+        .zip(settings.interface_message_id)
+        .map(|(channel_id, message_id)| (serenity::ChannelId::new(channel_id as u64), serenity::MessageId::new(message_id as u64)));
+
+    if remove.unwrap_or(false) {
+        if let Some((channel_id, message_id)) = previous {
+            if let Err(err) = ctx.serenity_context().delete_message(channel_id, message_id).await {
+                tracing::warn!("Failed to delete voice interface CHANNEL({}) MESSAGE({}).\n{}", channel_id, message_id, err);
+            }
+        }
+        repositories.settings.set_interface(guild_id, None, None).await?;
+        ctx.say("The voice interface is turned off").await?;
+        return Ok(());
+    }
+
+    let channel_id = match (channel, settings.interface_channel_id) {
+        (Some(channel), _) => channel.id,
+        (None, Some(channel_id)) => serenity::ChannelId::new(channel_id as u64),
+        (None, None) => return Err("Pick a channel for the voice interface".into()),
+    };
+    let message_id = voice_interface::deploy(ctx.serenity_context(), channel_id, previous).await?;
+    repositories.settings.set_interface(guild_id, Some(channel_id.get() as i64), Some(message_id.get() as i64)).await?;
+
+    ctx.say(format!("The voice interface is in {}", channel_id.mention())).await?;
+    Ok(())
+}
+
+/// Channel cache counters, failed room creations since startup and the last reconciliation
+#[poise::command(slash_command, check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn stats(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    let mut failures: Vec<String> = room_creation::failure_counts()
+        .iter()
+        .map(|(label, count)| format!("{}: {}", label, count))
+        .collect();
+    failures.sort();
+    let last_reconcile = ReconcileRun::get_last(&context_pool(ctx.serenity_context()).await).await?;
+
+    ctx.say(format!(
+        "Trigger cache: {}\nRoom cache: {}\nPermanent room cache: {}\nFailed room creations: {}\nLast reconciliation: {}",
+        channel_cache::trigger_stats().to_display_string(),
+        channel_cache::room_stats().to_display_string(),
+        channel_cache::permanent_stats().to_display_string(),
+        match failures.is_empty() {
+            true => "none".to_string(),
+            false => failures.join(", "),
+        },
+        last_reconcile.map_or("never".to_string(), |run| run.to_display_string())
+    )).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    subcommands("overflow_add", "overflow_remove", "overflow_list"),
+    check = "is_bot_or_guild_owner",
+    check = "have_ctx_guild_id"
+)]
+pub async fn overflow(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    ctx.say(format!("Available commands: ({})", ["add", "remove", "list"].join(", "))).await?;
+    Ok(())
+}
+
+async fn get_guild_autoroom(ctx: &CommandContext<'_>, channel: &serenity::GuildChannel) -> Result<AutoRoom, CommandError> {
+    let guild_id = parse_ctx_guild_id(ctx)?;
+    match ctx.data().repositories.autorooms.get_by_channel_id(channel.id.get() as i64).await? {
+        Some(autoroom) if autoroom.guild_id == guild_id.get() as i64 => Ok(autoroom),
+        _ => Err("Autoroom with given channel was not found".into()),
+    }
+}
+
+/// Adds a category to the end of the autoroom overflow list
+#[poise::command(slash_command, rename = "add", check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn overflow_add(
+    ctx: CommandContext<'_>,
+    #[description = "Autoroom trigger channel"]
+    #[channel_types("Voice")]
+        from_channel: serenity::GuildChannel,
+    #[description = "Category used when the previous ones are full"]
+    #[channel_types("Category")]
+        category: serenity::GuildChannel,
+) -> Result<(), CommandError> {
+    let autoroom = get_guild_autoroom(&ctx, &from_channel).await?;
+    if autoroom.category_id == category.id.get() as i64 {
+        return Err("Category is already the main category of this autoroom".into());
+    }
+
+    let overflow = ctx.data().repositories.autorooms.push_overflow_category(autoroom.channel_id, category.id.get() as i64, false).await?;
+    ctx.say(format!("Overflow category added: {}", overflow.to_display_string())).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, rename = "remove", check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn overflow_remove(
+    ctx: CommandContext<'_>,
+    #[description = "Autoroom trigger channel"]
+    #[channel_types("Voice")]
+        from_channel: serenity::GuildChannel,
+    #[description = "Overflow category to remove"]
+    #[channel_types("Category")]
+        category: serenity::GuildChannel,
+) -> Result<(), CommandError> {
+    let autoroom = get_guild_autoroom(&ctx, &from_channel).await?;
+    if !ctx.data().repositories.autorooms.remove_overflow_category(autoroom.channel_id, category.id.get() as i64).await? {
+        return Err("Category is not an overflow category of this autoroom".into());
+    }
+
+    ctx.say(format!("Overflow category removed: <#{}>", category.id)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, rename = "list", check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn overflow_list(
+    ctx: CommandContext<'_>,
+    #[description = "Autoroom trigger channel"]
+    #[channel_types("Voice")]
+        from_channel: serenity::GuildChannel,
+) -> Result<(), CommandError> {
+    let autoroom = get_guild_autoroom(&ctx, &from_channel).await?;
+    let categories = ctx.data().repositories.autorooms.get_overflow_categories(autoroom.channel_id).await?;
+    let result = match categories.is_empty() {
+        true => "Records not found".to_string(),
+        false => categories
+            .iter()
+            .map(|category| category.to_display_string())
+            .collect::<Vec<String>>()
+            .join("\n"),
+    };
+
+    ctx.say(format!("Main category: <#{}>\n{}", autoroom.category_id, result)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    subcommands("protect_add", "protect_remove", "protect_list"),
+    check = "is_bot_or_guild_owner",
+    check = "have_ctx_guild_id"
+)]
+pub async fn protect(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    ctx.say(format!("Available commands: ({})", ["add", "remove", "list"].join(", "))).await?;
+    Ok(())
+}
+
+/// Keeps the channel out of every cleanup, even when the bot created it
+#[poise::command(slash_command, rename = "add", check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn protect_add(
+    ctx: CommandContext<'_>,
+    #[description = "Channel cleanups must never delete"] channel: serenity::GuildChannel,
+) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?;
+    ctx.data().repositories.channels.protect(guild_id.get() as i64, channel.id.get() as i64).await?;
+    ctx.say(format!("Channel protected: <#{}>", channel.id)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, rename = "remove", check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn protect_remove(
+    ctx: CommandContext<'_>,
+    #[description = "Protected channel"] channel: serenity::GuildChannel,
+) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?;
+    if !ctx.data().repositories.channels.unprotect(guild_id.get() as i64, channel.id.get() as i64).await? {
+        return Err("Channel is not protected".into());
+    }
+
+    ctx.say(format!("Channel is no longer protected: <#{}>", channel.id)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, rename = "list", check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn protect_list(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?;
+    let channels = ctx.data().repositories.channels.get_protected(guild_id.get() as i64).await?;
+    let result = match channels.is_empty() {
+        true => "Records not found".to_string(),
+        false => channels
+            .iter()
+            .map(|channel| channel.to_display_string())
+            .collect::<Vec<String>>()
+            .join("\n"),
+    };
+
+    ctx.say(result).await?;
+    Ok(())
+}
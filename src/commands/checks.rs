@@ -4,11 +4,11 @@ use crate::commands::{CommandContext, CommandError};
 
 
 pub async fn is_bot_or_guild_owner(ctx: CommandContext<'_>) -> Result<bool, CommandError> {
-    if is_bot_owner(&ctx) == true {
+    if is_bot_owner(&ctx) {
         return Ok(true)
     }
 
-    if is_guild_owner(&ctx) == true {
+    if is_guild_owner(&ctx) {
         return Ok(true)
     }
 
@@ -29,8 +29,9 @@ fn is_guild_owner(ctx: &CommandContext<'_>) -> bool {
     false
 }
 
+#[allow(dead_code)]
 pub async fn is_admin(ctx: CommandContext<'_>) -> Result<bool, CommandError> {
-    if let Some(permissions) = ctx.author_member().await.map(|m| m.permissions).flatten() {
+    if let Some(permissions) = ctx.author_member().await.and_then(|m| m.permissions) {
         if permissions.administrator() {
             return Ok(true);
         }
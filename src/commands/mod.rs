@@ -1,17 +1,19 @@
-use sqlx::PgPool;
+use crate::sql::prelude::Repositories;
 
 pub mod autoroom;
 pub mod checks;
+pub mod permanent;
+pub mod room;
 
 
 pub type CommandError = Box<dyn std::error::Error + Send + Sync>;
 pub type CommandContext<'a> = poise::Context<'a, CommandData, CommandError>;
 
 pub struct CommandData {
-    pub pool: PgPool
+    pub repositories: Repositories
 }
 
-pub async fn generate_commands_framework(pool: PgPool) -> poise::Framework<CommandData, CommandError> {
+pub async fn generate_commands_framework(repositories: Repositories) -> poise::Framework<CommandData, CommandError> {
     let framework: poise::Framework<CommandData, CommandError> = poise::Framework::builder()
         .options(poise::FrameworkOptions {
             // prefix_options: PrefixFrameworkOptions {
@@ -21,15 +23,17 @@ pub async fn generate_commands_framework(pool: PgPool) -> poise::Framework<Comma
             commands: vec![
                 autoroom::autoroom(),
                 autoroom::context_invite(),
+                permanent::permanent(),
+                room::room(),
             ],
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(CommandData { pool: pool })
+                Ok(CommandData { repositories })
             })
         })
         .build();
-    return framework;
+    framework
 }
\ No newline at end of file
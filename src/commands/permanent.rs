@@ -0,0 +1,111 @@
+use poise::serenity_prelude as serenity;
+use ::serenity::all::Mentionable;
+
+use crate::{
+    services::permanent_room::{self, PermanentRoomError},
+    sql::autoroom::PermamentAutoRoom
+};
+
+use super::{ CommandContext, CommandError };
+use super::checks::{ is_bot_or_guild_owner, parse_ctx_guild_id, have_ctx_guild_id};
+
+
+#[poise::command(slash_command, subcommands("create", "delete", "transfer", "list"), check = "have_ctx_guild_id")]
+pub async fn permanent(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    ctx.say(format!("Available commands: ({}, {}, {}, {})", "create", "delete", "transfer", "list")).await?;
+    Ok(())
+}
+
+async fn get_managed_room(
+    ctx: &CommandContext<'_>,
+    channel: &serenity::GuildChannel
+) -> Result<PermamentAutoRoom, CommandError> {
+    let room = ctx.data().repositories.permanent
+        .get_by_channel_id(channel.id.get() as i64)
+        .await?
+        .ok_or(PermanentRoomError::NotFound)?;
+
+    if room.owner_id as u64 != ctx.author().id.get() && !is_bot_or_guild_owner(*ctx).await? {
+        return Err(PermanentRoomError::NotOwner.into());
+    }
+
+    Ok(room)
+}
+
+#[poise::command(slash_command, check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn create(
+    ctx: CommandContext<'_>,
+    #[description = "Owner of the room"] owner: serenity::User,
+    #[description = "Category the room is moved to while someone is inside"]
+    #[channel_types("Category")]
+        placement_category: serenity::GuildChannel,
+    #[description = "Hidden category the room is kept in while empty"]
+    #[channel_types("Category")]
+        storage_category: serenity::GuildChannel,
+    #[description = "Room name"] #[max_length = 100] name: Option<String>,
+) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?;
+    let name = name.unwrap_or_else(|| format!("{}`s room", owner.name));
+
+    let channel = permanent_room::create_room(
+        ctx.serenity_context(),
+        &ctx.data().repositories,
+        guild_id,
+        &owner,
+        name,
+        placement_category.id,
+        storage_category.id,
+    ).await?;
+
+    ctx.say(format!("Permanent room {} was created for {}", channel.mention(), owner.mention())).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn delete(
+    ctx: CommandContext<'_>,
+    #[description = "Permanent room to delete"]
+    #[channel_types("Voice")]
+        channel: serenity::GuildChannel,
+) -> Result<(), CommandError> {
+    let room = get_managed_room(&ctx, &channel).await?;
+    permanent_room::delete_room(ctx.serenity_context(), &ctx.data().repositories, &room).await?;
+
+    ctx.say(format!("Permanent room `{}` was deleted", channel.name)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn transfer(
+    ctx: CommandContext<'_>,
+    #[description = "Permanent room to transfer"]
+    #[channel_types("Voice")]
+        channel: serenity::GuildChannel,
+    #[description = "New owner of the room"] new_owner: serenity::User,
+) -> Result<(), CommandError> {
+    let room = get_managed_room(&ctx, &channel).await?;
+    permanent_room::transfer_room(ctx.serenity_context(), &ctx.data().repositories, &room, new_owner.id).await?;
+
+    ctx.say(format!("{} now owns {}", new_owner.mention(), channel.mention())).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, check = "is_bot_or_guild_owner", check = "have_ctx_guild_id")]
+pub async fn list(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    let guild_id = parse_ctx_guild_id(&ctx)?;
+
+    let rooms = ctx.data().repositories.permanent.get_guild_rooms(guild_id.get() as i64).await?;
+    let result = match rooms.is_empty() {
+        true => "Records not found".to_string(),
+        false => {
+            rooms
+                .iter()
+                .map(|room| room.to_display_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+        },
+    };
+
+    ctx.say(result).await?;
+    Ok(())
+}
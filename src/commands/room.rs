@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use poise::{CreateReply, serenity_prelude as serenity};
+use ::serenity::all::Mentionable;
+
+use crate::services::autoroom::voice_channel::{self, MembersReport};
+use crate::services::member_list;
+use crate::services::rename_queue::RenameSlot;
+use crate::sql::member_list::MemberList;
+
+use super::{ CommandContext, CommandError };
+use super::checks::have_ctx_guild_id;
+
+
+/// Confirmation sent after a successful room action, in the caller's language.
+pub enum RoomReply<'a> {
+    Invited(&'a serenity::User),
+    Kicked(&'a serenity::User),
+    Locked,
+    Unlocked,
+    Hidden,
+    Unhidden,
+    Renamed(&'a str),
+    /// The rename rate limit is reached, the name is applied after the delay
+    RenameQueued(&'a str, Duration),
+    Limited(u32),
+    Bitrate(u32),
+    Transferred(&'a serenity::User),
+    Trusted(&'a serenity::User),
+    Untrusted(&'a serenity::User),
+    Blocked(&'a serenity::User),
+    Unblocked(&'a serenity::User),
+}
+
+impl<'a> RoomReply<'a> {
+    pub fn renamed(name: &'a str, slot: RenameSlot) -> Self {
+        match slot {
+            RenameSlot::Now => Self::Renamed(name),
+            RenameSlot::Queued(delay) => Self::RenameQueued(name, delay),
+        }
+    }
+
+    pub fn localize(&self, locale: Option<&str>) -> String {
+        let russian = locale.is_some_and(|locale| locale.starts_with("ru"));
+        match (self, russian) {
+            (Self::Invited(user), false) => format!("{} has been invited to the room", user.mention()),
+            (Self::Invited(user), true) => format!("{} приглашён в комнату", user.mention()),
+            (Self::Kicked(user), false) => format!("{} has been kicked from the room", user.mention()),
+            (Self::Kicked(user), true) => format!("{} выгнан из комнаты", user.mention()),
+            (Self::Locked, false) => "The room is locked, only invited members can connect".to_string(),
+            (Self::Locked, true) => "Комната закрыта, подключиться могут только приглашённые".to_string(),
+            (Self::Unlocked, false) => "The room is unlocked".to_string(),
+            (Self::Unlocked, true) => "Комната открыта".to_string(),
+            (Self::Hidden, false) => "The room is hidden, only invited members can see it".to_string(),
+            (Self::Hidden, true) => "Комната скрыта, её видят только приглашённые".to_string(),
+            (Self::Unhidden, false) => "The room is visible again".to_string(),
+            (Self::Unhidden, true) => "Комната снова видна всем".to_string(),
+            (Self::Renamed(name), false) => format!("The room has been renamed to `{}`", name),
+            (Self::Renamed(name), true) => format!("Комната переименована в `{}`", name),
+            (Self::RenameQueued(name, delay), false) => format!(
+                "Discord allows two renames per ten minutes, the room will be renamed to `{}` in {} min",
+                name, delay.as_secs().div_ceil(60)
+            ),
+            (Self::RenameQueued(name, delay), true) => format!(
+                "Discord разрешает два переименования за десять минут, комната станет `{}` через {} мин",
+                name, delay.as_secs().div_ceil(60)
+            ),
+            (Self::Limited(0), false) => "The user limit has been removed".to_string(),
+            (Self::Limited(0), true) => "Лимит участников снят".to_string(),
+            (Self::Limited(limit), false) => format!("The user limit is set to {}", limit),
+            (Self::Limited(limit), true) => format!("Лимит участников: {}", limit),
+            (Self::Bitrate(bitrate), false) => format!("The bitrate is set to {} kbps", bitrate / 1000),
+            (Self::Bitrate(bitrate), true) => format!("Битрейт: {} кбит/с", bitrate / 1000),
+            (Self::Transferred(user), false) => format!("{} is now the host of the room", user.mention()),
+            (Self::Transferred(user), true) => format!("{} теперь хозяин комнаты", user.mention()),
+            (Self::Trusted(user), false) => format!("{} is trusted and will be invited to your rooms", user.mention()),
+            (Self::Trusted(user), true) => format!("{} в списке доверенных и будет приглашён в ваши комнаты", user.mention()),
+            (Self::Untrusted(user), false) => format!("{} is no longer trusted", user.mention()),
+            (Self::Untrusted(user), true) => format!("{} больше не в списке доверенных", user.mention()),
+            (Self::Blocked(user), false) => format!("{} is blocked from your rooms", user.mention()),
+            (Self::Blocked(user), true) => format!("{} заблокирован в ваших комнатах", user.mention()),
+            (Self::Unblocked(user), false) => format!("{} is no longer blocked", user.mention()),
+            (Self::Unblocked(user), true) => format!("{} больше не заблокирован", user.mention()),
+        }
+    }
+}
+
+async fn reply(ctx: CommandContext<'_>, room_reply: RoomReply<'_>) -> Result<(), CommandError> {
+    ctx.send(
+        CreateReply::default()
+            .content(format!("✅ {}", room_reply.localize(ctx.locale())))
+            .ephemeral(false)
+    ).await?;
+    Ok(())
+}
+
+/// Room the author manages in the guild of the command.
+async fn owned_room(ctx: CommandContext<'_>) -> Result<serenity::ChannelId, CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    Ok(voice_channel::owned_room_id(ctx.serenity_context(), &ctx.data().repositories, guild_id, ctx.author().id.get() as i64).await?)
+}
+
+#[poise::command(
+    slash_command,
+    subcommands(
+        "invite", "kick", "lock", "unlock", "hide", "unhide", "rename", "limit", "bitrate", "transfer",
+        "trust", "untrust", "block", "unblock"
+    ),
+    check = "have_ctx_guild_id"
+)]
+pub async fn room(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    ctx.say(format!(
+        "Available commands: ({})",
+        [
+            "invite", "kick", "lock", "unlock", "hide", "unhide", "rename", "limit", "bitrate", "transfer",
+            "trust", "untrust", "block", "unblock"
+        ].join(", ")
+    )).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn invite(
+    ctx: CommandContext<'_>,
+    #[description = "Member to invite to your room"] user: serenity::User,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    voice_channel::invite_user(ctx.serenity_context(), &ctx.data().repositories, guild_id, ctx.author().id.get() as i64, &user).await?;
+    reply(ctx, RoomReply::Invited(&user)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn kick(
+    ctx: CommandContext<'_>,
+    #[description = "Member to kick from your room"] user: serenity::User,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    voice_channel::kick_user(ctx.serenity_context(), &ctx.data().repositories, guild_id, ctx.author().id.get() as i64, &user).await?;
+    reply(ctx, RoomReply::Kicked(&user)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn lock(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    let room_id = owned_room(ctx).await?;
+    voice_channel::set_room_locked(ctx.serenity_context(), room_id, ctx.author().id.get() as i64, true).await?;
+    reply(ctx, RoomReply::Locked).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn unlock(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    let room_id = owned_room(ctx).await?;
+    voice_channel::set_room_locked(ctx.serenity_context(), room_id, ctx.author().id.get() as i64, false).await?;
+    reply(ctx, RoomReply::Unlocked).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn hide(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    let room_id = owned_room(ctx).await?;
+    voice_channel::set_room_hidden(ctx.serenity_context(), room_id, ctx.author().id.get() as i64, true).await?;
+    reply(ctx, RoomReply::Hidden).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn unhide(ctx: CommandContext<'_>) -> Result<(), CommandError> {
+    let room_id = owned_room(ctx).await?;
+    voice_channel::set_room_hidden(ctx.serenity_context(), room_id, ctx.author().id.get() as i64, false).await?;
+    reply(ctx, RoomReply::Unhidden).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn rename(
+    ctx: CommandContext<'_>,
+    #[description = "New room name"] #[min_length = 1] #[max_length = 100] name: String,
+) -> Result<(), CommandError> {
+    let room_id = owned_room(ctx).await?;
+    let slot = voice_channel::rename_room(ctx.serenity_context(), room_id, ctx.author().id.get() as i64, &name).await?;
+    reply(ctx, RoomReply::renamed(name.trim(), slot)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn limit(
+    ctx: CommandContext<'_>,
+    #[description = "Maximum number of members, 0 removes the limit"] #[min = 0] #[max = 99] user_limit: u32,
+) -> Result<(), CommandError> {
+    let room_id = owned_room(ctx).await?;
+    voice_channel::set_room_user_limit(ctx.serenity_context(), room_id, ctx.author().id.get() as i64, user_limit).await?;
+    reply(ctx, RoomReply::Limited(user_limit)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn bitrate(
+    ctx: CommandContext<'_>,
+    #[description = "Bitrate in kbps, capped by the server boost level"] #[min = 8] #[max = 384] kbps: u32,
+) -> Result<(), CommandError> {
+    let room_id = owned_room(ctx).await?;
+    let applied = voice_channel::set_room_bitrate(ctx.serenity_context(), room_id, ctx.author().id.get() as i64, kbps).await?;
+    reply(ctx, RoomReply::Bitrate(applied)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn transfer(
+    ctx: CommandContext<'_>,
+    #[description = "New host of your room"] user: serenity::User,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    voice_channel::transfer_user_room(ctx.serenity_context(), &ctx.data().repositories, guild_id, ctx.author().id.get() as i64, &user).await?;
+    reply(ctx, RoomReply::Transferred(&user)).await
+}
+
+/// A failed member is left off the list, the report says why.
+fn single_member(report: MembersReport, user: &serenity::User, done_label: &str) -> Result<(), CommandError> {
+    match report.done.contains(&user.id) {
+        true => Ok(()),
+        false => Err(report.to_message(done_label).into()),
+    }
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn trust(
+    ctx: CommandContext<'_>,
+    #[description = "Member invited to every room you create"] user: serenity::User,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let report = member_list::add_members(
+        ctx.serenity_context(),
+        &ctx.data().repositories,
+        guild_id,
+        MemberList::Trusted,
+        ctx.author().id.get() as i64,
+        &[user.id]
+    ).await?;
+    single_member(report, &user, "Trusted")?;
+    reply(ctx, RoomReply::Trusted(&user)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn untrust(
+    ctx: CommandContext<'_>,
+    #[description = "Trusted member to remove"] user: serenity::User,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let report = member_list::remove_members(
+        ctx.serenity_context(),
+        &ctx.data().repositories,
+        guild_id,
+        MemberList::Trusted,
+        ctx.author().id.get() as i64,
+        &[user.id]
+    ).await?;
+    single_member(report, &user, "No longer trusted")?;
+    reply(ctx, RoomReply::Untrusted(&user)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn block(
+    ctx: CommandContext<'_>,
+    #[description = "Member kept out of every room you create"] user: serenity::User,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let report = member_list::add_members(
+        ctx.serenity_context(),
+        &ctx.data().repositories,
+        guild_id,
+        MemberList::Blocked,
+        ctx.author().id.get() as i64,
+        &[user.id]
+    ).await?;
+    single_member(report, &user, "Blocked")?;
+    reply(ctx, RoomReply::Blocked(&user)).await
+}
+
+#[poise::command(slash_command, check = "have_ctx_guild_id")]
+pub async fn unblock(
+    ctx: CommandContext<'_>,
+    #[description = "Blocked member to remove"] user: serenity::User,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let report = member_list::remove_members(
+        ctx.serenity_context(),
+        &ctx.data().repositories,
+        guild_id,
+        MemberList::Blocked,
+        ctx.author().id.get() as i64,
+        &[user.id]
+    ).await?;
+    single_member(report, &user, "Unblocked")?;
+    reply(ctx, RoomReply::Unblocked(&user)).await
+}
@@ -1,8 +1,6 @@
-use std::collections::HashMap;
+use std::sync::Arc;
 
-use once_cell::sync::OnceCell;
-use parking_lot::Mutex;
-use serenity::all::{ChannelId, ComponentInteractionDataKind, CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage, EditInteractionResponse, Interaction, InviteCreateEvent, UserId};
+use serenity::all::{ChannelId, ComponentInteractionDataKind, GuildId, CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse, EditMessage, ComponentInteraction, GuildChannel, Interaction, InviteCreateEvent, UserId};
 use serenity::{all::VoiceState, async_trait};
 use serenity::model::gateway::Ready;
 use serenity::prelude::*;
@@ -19,50 +17,79 @@ mod sql;
 mod commands;
 pub mod services;
 
-use voice::{create_proccessing, remove_channel_by_voicestate};
+use voice::voice_state_update;
 use sql::{prelude::*, SerenityPool};
 
 use crate::services::autoroom::cleanup_categories_monitored_rooms;
-use crate::services::autoroom::voice_channel::{invite_user, kick_user};
-use crate::sql::pool::SqlPool;
-use crate::{services::autoroom::cleanup_db_monitored_rooms, sql::pool::GLOBAL_SQL_POOL};
+use crate::services::autoroom::invite_modal::claim_components;
+use crate::services::autoroom::voice_channel::{
+    get_channel_owner_id, BotError, invite_user, invite_users, kick_users, rename_room, set_room_bitrate, set_room_hidden,
+    set_room_locked, set_room_user_limit
+};
+use crate::commands::room::RoomReply;
+use crate::services::{
+    channel_cache, member_list, ownership, permanent_room, rename_queue, room_panel, voice_interface, voice_presence
+};
+use crate::services::autoroom::cleanup_db_monitored_rooms;
+use crate::services::cleanup::CleanupOptions;
+use crate::services::custom_id::{self, CustomId, PanelAction};
+use crate::services::reconciler::{self, ReconcilerConfig};
+use crate::sql::member_list::MemberList;
 
 struct Handler;
 
 
-static SELECTED_USER_STORE: OnceCell<Mutex<HashMap<u64, u64>>> = OnceCell::new();
-
-
 #[async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, ready: Ready) {
         tracing::info!("`{}` is now online", ready.user.name);
-        let mut err = cleanup_db_monitored_rooms(&ctx).await.err();
-        if err.is_some() {
-            tracing::error!(err);
+        let repositories = Repositories::from_context(&ctx).await;
+        if let Err(err) = channel_cache::load(&repositories).await {
+            tracing::error!("Failed to load channel cache: {}", err);
+        };
+        // Сверку при запуске пропускаем, если её уже ведёт другой экземпляр или команда
+        match reconciler::exclusive(&ctx, startup_reconcile(&ctx, &repositories)).await {
+            Ok(Some(())) => (),
+            Ok(None) => tracing::info!("Startup reconciliation skipped, another one is running"),
+            Err(err) => tracing::error!("Failed to take the reconcile lock: {}", err),
         };
-        err = cleanup_categories_monitored_rooms(&ctx).await.err();
-        if err.is_some() {
-            tracing::error!(err);
+        // Пока бота не было, в комнатах могли смениться участники
+        match repositories.rooms.get_all().await {
+            Ok(rooms) => rooms
+                .iter()
+                .for_each(|room| room_panel::schedule_refresh(&ctx, ChannelId::new(room.channel_id as u64))),
+            Err(err) => tracing::error!("Failed to load rooms for panel refresh: {}", err),
         };
+        // Дальше сверка идёт в фоне, чтобы не копить расхождения до перезапуска
+        if let Some(config) = ReconcilerConfig::from_env() {
+            reconciler::start(&ctx, config);
+        }
+        // Переименования сверх лимита Discord применяются в фоне
+        rename_queue::start(&ctx);
+    }
+
+    async fn channel_update(&self, ctx: Context, _old: Option<GuildChannel>, new: GuildChannel) {
+        room_panel::schedule_refresh(&ctx, new.id);
     }
 
     async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
-        create_proccessing(&ctx, &new).await;
-        if let Some(voice_state) = old {
-            let err = match remove_channel_by_voicestate(&ctx, &voice_state).await {
-                Ok(_) => return,
-                Err(_err) => _err,
-            };
-            tracing::error!(err);
-        };
+        voice_presence::track(old.as_ref(), &new);
+        let old_channel_id = old.as_ref().and_then(|o| o.channel_id);
+        if old_channel_id != new.channel_id {
+            old_channel_id
+                .into_iter()
+                .chain(new.channel_id)
+                .for_each(|channel_id| room_panel::schedule_refresh(&ctx, channel_id));
+        }
+        let repositories = Repositories::from_context(&ctx).await;
+        voice_state_update(Arc::new(ctx), &repositories, old.as_ref(), &new).await;
     }
 
     async fn invite_create(&self, ctx: Context, data: InviteCreateEvent) {
         if let Some(guild_id) = data.guild_id {
             if let (Some(author), Some(user)) = (data.inviter, data.target_user) {
-                let pool = &GLOBAL_SQL_POOL.get().unwrap().get_pool();
-                match invite_user(ctx.http(), pool, author.id.get() as i64, &user).await {
+                let repositories = Repositories::from_context(&ctx).await;
+                match invite_user(&ctx, &repositories, guild_id, author.id.get() as i64, &user).await {
                     Ok(_) => tracing::info!(
                         "Invite event, permissions gived.\nGUILD({}) INVITER({}) TARGET({})",
                         guild_id,
@@ -87,192 +114,310 @@ impl EventHandler for Handler {
                 let custom_id = &mci.data.custom_id;
                 tracing::info!("interaction_create: {}", custom_id);
 
-                // 1. Проверяем префикс через starts_with
-                if custom_id.starts_with("inv_") {
-                    
-                    // Разделяем ID на части. 
-                    // Ожидаемый формат: "inv_тип_ктоСоздал_какойКанал"
-                    let parts: Vec<&str> = custom_id.split('_').collect();
-                    
-                    // Извлекаем тип действия (второй элемент после "inv")
-                    let action_type = parts.get(1).copied().unwrap_or("");
-                    
-                    // Безопасно парсим ID пользователя и канала
-                    let owner_id = parts.get(2).and_then(|s| s.parse::<u64>().ok()).map(UserId::new);
-                    let channel_id = parts.get(3).and_then(|s| s.parse::<u64>().ok()).map(ChannelId::new);
-
-                    if let (Some(owner), Some(channel)) = (owner_id, channel_id) {
-
-                        if mci.user.id != owner {
-                            let _ = mci.create_response(&ctx.http, CreateInteractionResponse::Message(
-                                CreateInteractionResponseMessage::new()
-                                    .content("You aren't host of the room")
-                                    .ephemeral(true)
-                            )).await;
-                            return;
-                        }
-
-                        match action_type {
-                            "sel" => {
-                                if let ComponentInteractionDataKind::UserSelect { values } = &mci.data.kind {
-                                    if let Some(target_id) = values.first() {
-                                        {
-                                            let mut storage = SELECTED_USER_STORE.get().unwrap().lock();
-                                            storage.insert(owner.get(), target_id.get());
-                                        }
-
-                                        if let Err(err) = mci.defer(&ctx.http).await {
-                                            tracing::error!("{:?}", err);
-                                            return;
-                                        };
-                                    }
-                                    
-                                    // if let Err(err) = mci.create_response(&ctx.http, CreateInteractionResponse::Message(
-                                    //     CreateInteractionResponseMessage::new().content("Member not selected").ephemeral(true)
-                                    // )).await {
-                                    //     tracing::error!("{:?}", err);
-                                    //     return;
-                                    // };
-                                }
-                            },
-                            "inv" => {
-                                let storage_target: Option<u64>;
-                                {
-                                    let storage = SELECTED_USER_STORE.get().unwrap().lock();
-                                    storage_target = storage.get(&owner.get()).copied();
-                                }
-                                if let Some(target_id) = storage_target {
-                                    if let Err(err) = mci.defer(&ctx.http).await {
-                                        tracing::error!("{:?}", err);
-                                        return;
-                                    };
-
-                                    let target_id = UserId::new(target_id);
-                                    let pool = GLOBAL_SQL_POOL.get().unwrap().get_pool();
-
-                                    let invited_user = match target_id.to_user(&ctx).await {
-                                        Ok(_user) => _user,
-                                        Err(err) => {
-                                            tracing::error!("{:?}", err);
-                                            return;
-                                        },
-                                    };
-                                    
-                                    if let Err(err) = invite_user(&ctx.http, &pool, owner.get() as i64, &invited_user).await {
-                                        tracing::error!("{:?}", err);
-                                        return;    
-                                    };
-
-                                    if let Err(err) = mci
-                                        // .edit_response(
-                                        //     &ctx.http,
-                                        //     EditInteractionResponse::new()
-                                        //         .content(format!(
-                                        //             "{} has been successfully invited",
-                                        //             &invited_user.mention().to_string())
-                                        //         )
-                                        // )
-                                        // .await
-                                        .create_followup(
-                                            &ctx.http,
-                                            CreateInteractionResponseFollowup::new()
-                                                .content(format!(
-                                                        "{} has been successfully invited",
-                                                        &invited_user.mention().to_string())
-                                                )
-                                        ).await
-                                    {
-                                        tracing::error!("{:?}", err);
-                                        return;
-                                    }
-                                } else {
-                                    if let Err(err) = mci.edit_response(&ctx.http, EditInteractionResponse::new()
-                                        .content("⚠️ Member not selected!")
-                                    ).await {
-                                        tracing::error!("{:?}", err);
-                                        return;    
-                                    };
+                let id = match CustomId::decode(custom_id) {
+                    Ok(id) => id,
+                    Err(err) => {
+                        // Меню, отправленные до подписи ID, принимаем только с сообщений самого бота
+                        let legacy = CustomId::parse_legacy(custom_id)
+                            .filter(|_| mci.message.author.id == ctx.cache.current_user().id);
+                        match legacy {
+                            Some(id) => {
+                                tracing::info!("Legacy component interaction.\nUSER({}) CHANNEL({}) MESSAGE({})", mci.user.id, mci.channel_id, mci.message.id);
+                                if id.action == PanelAction::Claim {
+                                    migrate_panel(&ctx, &mci, id, id.user_id).await;
                                 }
+                                id
                             },
-                            "kick" => {
-                                let storage_target: Option<u64>;
-                                {
-                                    let storage = SELECTED_USER_STORE.get().unwrap().lock();
-                                    storage_target = storage.get(&owner.get()).copied();
-                                }
-                                if let Some(target_id) = storage_target {
-                                    if let Err(err) = mci.defer(&ctx.http).await {
-                                        tracing::error!("{:?}", err);
-                                        return;
-                                    };
-
-                                    let target_id = UserId::new(target_id);
-                                    let pool = GLOBAL_SQL_POOL.get().unwrap().get_pool();
-
-                                    let user_to_kick = match target_id.to_user(&ctx).await {
-                                        Ok(_user) => _user,
-                                        Err(err) => {
-                                            tracing::error!("{:?}", err);
-                                            return;
-                                        },
-                                    };
-                                    
-                                    if let Err(err) = kick_user(&ctx.http, &pool, guild_id, owner.get() as i64, &user_to_kick).await {
-                                        tracing::error!("{:?}", err);
-                                        return;    
-                                    };
-
-                                    if let Err(err) = mci
-                                        // .edit_response(
-                                        //     &ctx.http,
-                                        //     EditInteractionResponse::new()
-                                        //         .content(format!(
-                                        //             "{} has been successfully invited",
-                                        //             &invited_user.mention().to_string())
-                                        //         )
-                                        // )
-                                        // .await
-                                        .create_followup(
-                                            &ctx.http,
-                                            CreateInteractionResponseFollowup::new()
-                                                .content(format!(
-                                                        "Member has been successfully invited"
-                                                    )
-                                                )
-                                        ).await
-                                    {
-                                        tracing::error!("{:?}", err);
-                                        return;
-                                    }
-                                    
-                                } else {
-                                    if let Err(err) = mci.edit_response(&ctx.http, EditInteractionResponse::new()
-                                        .content("⚠️ Member not selected!")
-                                    ).await {
-                                        tracing::error!("{:?}", err);
-                                        return;    
-                                    };
-                                }
+                            None => {
+                                tracing::warn!(
+                                    "Rejected component interaction, {}.\nUSER({}) CHANNEL({}) MESSAGE({}) ID({})",
+                                    err,
+                                    mci.user.id,
+                                    mci.channel_id,
+                                    mci.message.id,
+                                    custom_id
+                                );
+                                let _ = mci.create_response(&ctx.http, CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::new()
+                                        .content("This menu is no longer valid")
+                                        .ephemeral(true)
+                                )).await;
+                                return;
                             },
-                            _ => {
-                                tracing::warn!("Unkown interaction id: {}", action_type);
-                            }
                         }
-                    }
+                    },
+                };
+                // Подтверждение очистки обрабатывает сама команда
+                if id.action == PanelAction::CleanupConfirm {
+                    return;
+                }
+
+                // Кнопку "Claim" может нажать любой участник комнаты
+                if id.action == PanelAction::Claim {
+                    let content = match ownership::claim(&ctx, &Repositories::from_context(&ctx).await, guild_id, id.channel_id, mci.user.id).await {
+                        Ok(_) => "You are the host of the room now".to_string(),
+                        Err(err) => err.to_string(),
+                    };
+                    if let Err(err) = mci.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(content)
+                            .ephemeral(true)
+                    )).await {
+                        tracing::error!("{:?}", err);
+                    };
+                    return;
+                }
+
+                let repositories = Repositories::from_context(&ctx).await;
+                let (channel, owner) = match panel_room(&ctx, &repositories, guild_id, id, mci.user.id).await {
+                    Ok(room) => room,
+                    Err(err) => {
+                        let _ = mci.create_response(&ctx.http, CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("⚠️ {}", err))
+                                .ephemeral(true)
+                        )).await;
+                        return;
+                    },
+                };
+
+                if mci.user.id != owner {
+                    let _ = mci.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("You aren't host of the room")
+                            .ephemeral(true)
+                    )).await;
+                    return;
                 }
+
+                // Кнопки и одиночный выбор старого меню: обновляем меню и просим повторить
+                let outdated = match id.action {
+                    PanelAction::Invite | PanelAction::Kick => !matches!(mci.data.kind, ComponentInteractionDataKind::UserSelect { .. }),
+                    PanelAction::Select => true,
+                    _ => false,
+                };
+                if outdated {
+                    migrate_panel(&ctx, &mci, id, owner).await;
+                    let _ = mci.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("The menu has been updated, please try again")
+                            .ephemeral(true)
+                    )).await;
+                    return;
+                }
+
+                // Настройки вводятся в форме, её отправка придёт отдельным взаимодействием
+                let cached_channel = ctx.cache.guild(guild_id).and_then(|guild| guild.channels.get(&channel).cloned());
+                if let Some(modal) = room_panel::settings_modal(id, cached_channel.as_ref()) {
+                    if let Err(err) = mci.create_response(&ctx.http, CreateInteractionResponse::Modal(modal)).await {
+                        tracing::error!("{:?}", err);
+                    };
+                    return;
+                }
+
+                if let Err(err) = mci.defer_ephemeral(&ctx.http).await {
+                    tracing::error!("{:?}", err);
+                    return;
+                };
+
+                // Списки открываются отдельным сообщением, видным только хозяину
+                if let Some(list) = member_list::shown_list(id.action) {
+                    let response = match member_list::menu(&repositories, list, owner, channel).await {
+                        Ok((content, components)) => EditInteractionResponse::new().content(content).components(components),
+                        Err(err) => EditInteractionResponse::new().content(format!("⚠️ {}", err)),
+                    };
+                    if let Err(err) = mci.edit_response(&ctx.http, response).await {
+                        tracing::error!("{:?}", err);
+                    };
+                    return;
+                }
+
+                // Само меню обновится по событию изменения канала
+                let author_id = owner.get() as i64;
+                let locale = Some(mci.locale.as_str());
+                let report = match (id.action, &mci.data.kind) {
+                    (PanelAction::Invite, ComponentInteractionDataKind::UserSelect { values }) => invite_users(&ctx, channel, author_id, values)
+                        .await
+                        .map(|report| report.to_message("Invited")),
+                    (PanelAction::Kick, ComponentInteractionDataKind::UserSelect { values }) => kick_users(&ctx, channel, author_id, values)
+                        .await
+                        .map(|report| report.to_message("Kicked")),
+                    (PanelAction::Trust, ComponentInteractionDataKind::UserSelect { values }) => member_list::add_members(&ctx, &repositories, guild_id, MemberList::Trusted, author_id, values)
+                        .await
+                        .map(|report| report.to_message("Trusted")),
+                    (PanelAction::Untrust, ComponentInteractionDataKind::UserSelect { values }) => member_list::remove_members(&ctx, &repositories, guild_id, MemberList::Trusted, author_id, values)
+                        .await
+                        .map(|report| report.to_message("No longer trusted")),
+                    (PanelAction::Block, ComponentInteractionDataKind::UserSelect { values }) => member_list::add_members(&ctx, &repositories, guild_id, MemberList::Blocked, author_id, values)
+                        .await
+                        .map(|report| report.to_message("Blocked")),
+                    (PanelAction::Unblock, ComponentInteractionDataKind::UserSelect { values }) => member_list::remove_members(&ctx, &repositories, guild_id, MemberList::Blocked, author_id, values)
+                        .await
+                        .map(|report| report.to_message("Unblocked")),
+                    (PanelAction::Lock, _) => set_room_locked(&ctx, channel, author_id, true)
+                        .await
+                        .map(|_| RoomReply::Locked.localize(locale)),
+                    (PanelAction::Unlock, _) => set_room_locked(&ctx, channel, author_id, false)
+                        .await
+                        .map(|_| RoomReply::Unlocked.localize(locale)),
+                    (PanelAction::Hide, _) => set_room_hidden(&ctx, channel, author_id, true)
+                        .await
+                        .map(|_| RoomReply::Hidden.localize(locale)),
+                    (PanelAction::Unhide, _) => set_room_hidden(&ctx, channel, author_id, false)
+                        .await
+                        .map(|_| RoomReply::Unhidden.localize(locale)),
+                    _ => return,
+                };
+                let content = report.unwrap_or_else(|err| format!("⚠️ {}", err));
+                if let Err(err) = mci.edit_response(&ctx.http, EditInteractionResponse::new().content(content)).await {
+                    tracing::error!("{:?}", err);
+                };
+            } else if let Interaction::Modal(modal) = interaction {
+                let id = match CustomId::decode(&modal.data.custom_id) {
+                    Ok(id) => id,
+                    Err(err) => {
+                        tracing::warn!(
+                            "Rejected modal submit, {}.\nUSER({}) CHANNEL({}) ID({})",
+                            err,
+                            modal.user.id,
+                            modal.channel_id,
+                            modal.data.custom_id
+                        );
+                        let _ = modal.create_response(&ctx.http, CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content("This form is no longer valid")
+                                .ephemeral(true)
+                        )).await;
+                        return;
+                    },
+                };
+
+                let repositories = Repositories::from_context(&ctx).await;
+                let (channel, owner) = match panel_room(&ctx, &repositories, guild_id, id, modal.user.id).await {
+                    Ok(room) => room,
+                    Err(err) => {
+                        let _ = modal.create_response(&ctx.http, CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("⚠️ {}", err))
+                                .ephemeral(true)
+                        )).await;
+                        return;
+                    },
+                };
+                if modal.user.id != owner {
+                    let _ = modal.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("You aren't host of the room")
+                            .ephemeral(true)
+                    )).await;
+                    return;
+                }
+
+                if let Err(err) = modal.defer_ephemeral(&ctx.http).await {
+                    tracing::error!("{:?}", err);
+                    return;
+                };
+
+                let author_id = owner.get() as i64;
+                let locale = Some(modal.locale.as_str());
+                let value = room_panel::submitted_value(&modal.data.components);
+                let report = match id.action {
+                    PanelAction::Rename => rename_room(&ctx, channel, author_id, value)
+                        .await
+                        .map(|slot| RoomReply::renamed(value.trim(), slot).localize(locale)),
+                    PanelAction::Limit => match room_panel::parse_number(value) {
+                        Ok(user_limit) => set_room_user_limit(&ctx, channel, author_id, user_limit)
+                            .await
+                            .map(|_| RoomReply::Limited(user_limit).localize(locale)),
+                        Err(err) => Err(err),
+                    },
+                    PanelAction::Bitrate => match room_panel::parse_number(value) {
+                        Ok(kbps) => set_room_bitrate(&ctx, channel, author_id, kbps)
+                            .await
+                            .map(|applied| RoomReply::Bitrate(applied).localize(locale)),
+                        Err(err) => Err(err),
+                    },
+                    _ => return,
+                };
+                let content = report.unwrap_or_else(|err| format!("⚠️ {}", err));
+                if let Err(err) = modal.edit_response(&ctx.http, EditInteractionResponse::new().content(content)).await {
+                    tracing::error!("{:?}", err);
+                };
             }
         }
     }
 }
 
+async fn startup_reconcile(ctx: &Context, repositories: &Repositories) {
+    let mut err = cleanup_db_monitored_rooms(ctx, repositories, &CleanupOptions::default()).await.err();
+    if err.is_some() {
+        tracing::error!(err);
+    };
+    err = cleanup_categories_monitored_rooms(ctx, repositories, &CleanupOptions::default()).await.err();
+    if err.is_some() {
+        tracing::error!(err);
+    };
+    err = permanent_room::reconcile_permanent_rooms(ctx, repositories).await.err();
+    if err.is_some() {
+        tracing::error!(err);
+    };
+}
+
+/// Room a panel acts on and its current owner. The voice interface has no room of its own,
+/// it acts on the room of whoever uses it.
+async fn panel_room(
+    ctx: &Context,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    id: CustomId,
+    user_id: UserId
+) -> Result<(ChannelId, UserId), BotError> {
+    // Владелец мог смениться после отправки меню, поэтому берём его из БД
+    match get_channel_owner_id(repositories, id.channel_id).await {
+        Ok(Some(owner)) => return Ok((id.channel_id, owner)),
+        Ok(None) => (),
+        Err(err) => {
+            tracing::error!("{:?}", err);
+            return Err(BotError::DatabaseError);
+        },
+    };
+
+    let settings = repositories.settings.get(guild_id.get() as i64).await.map_err(|err| {
+        tracing::error!("{:?}", err);
+        BotError::DatabaseError
+    })?;
+    if settings.interface_channel_id == Some(id.channel_id.get() as i64) {
+        let room = voice_interface::owned_room(ctx, repositories, guild_id, user_id).await?;
+        return Ok((room, user_id));
+    }
+    Ok((id.channel_id, id.user_id))
+}
+
+/// Replaces an outdated menu with the current claim button or the live room panel.
+async fn migrate_panel(ctx: &Context, mci: &ComponentInteraction, id: CustomId, owner_id: UserId) {
+    let result = match id.action {
+        PanelAction::Claim => mci.channel_id
+            .edit_message(&ctx.http, mci.message.id, EditMessage::new().components(claim_components(id.user_id, id.channel_id)))
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string()),
+        _ => {
+            let repositories = Repositories::from_context(ctx).await;
+            room_panel::adopt(ctx, repositories.rooms.as_ref(), id.channel_id, mci.message.id, owner_id).await
+        },
+    };
+    match result {
+        Ok(_) => tracing::info!("Migrated outdated panel.\nCHANNEL({}) MESSAGE({})", mci.channel_id, mci.message.id),
+        Err(err) => tracing::error!("Failed to migrate outdated panel.\nCHANNEL({}) MESSAGE({})\n{}", mci.channel_id, mci.message.id, err),
+    };
+}
+
 fn configurate_logger() {
     let ingesting_host = std::env::var("BETTER_STACK_INGESTING_HOST")
         .expect("BETTER_STACK_INGESTING_HOST must be set");
     let source_token = std::env::var("BETTER_STACK_SOURCE_TOKEN")
         .expect("BETTER_STACK_SOURCE_TOKEN must be set");
 
-    SELECTED_USER_STORE.set(Mutex::new(HashMap::new())).unwrap();
-
     tracing_subscriber::registry()
         .with(BetterStackLayer::new(
             BetterStackConfig::builder(ingesting_host, source_token).build()
@@ -296,6 +441,11 @@ async fn main() {
 
     configurate_logger();
 
+    if let Err(err) = custom_id::init_secret() {
+        tracing::error!("Error while reading the custom id secret. Finishing...\n Error: `{}`", err);
+        return;
+    }
+
     let token = std::env::var("DISCORD_TOKEN").unwrap();
 
     let db_url = std::env::var("POSTGRES_URI").unwrap();
@@ -303,42 +453,32 @@ async fn main() {
         .max_connections(5)
         .connect(&db_url).await.unwrap();
 
-    // tracing::info!("Drop table has begun");
-    // sqlx::query("DROP table monitored_autoroom").execute(&db).await.expect("Drop table 'monitored_autoroom' unsuccessful");
-    // tracing::info!("Drop table has been completed");
-
-    tracing::info!("Table creation has begun");
-    if let Err(err) = create_tables(&db).await {
-        tracing::error!("Error while creationg sql tables. Finishing...\n Error: `{}`", err);
+    if let Err(err) = run_migrations(&db).await {
+        tracing::error!("Error while running migrations. Finishing...\n Error: `{}`", err);
         return;
     };
-    tracing::info!("Table creation has been completed");
-
-    match GLOBAL_SQL_POOL.set(SqlPool::new(db.clone())) {
-        Ok(_) => (),
-        Err(_) => {
-            tracing::error!("GLOBAL_SQL_POOL isn't empty");
-            GLOBAL_SQL_POOL.get().expect("Unable to get GLOBAL_SQL_POOL");
-        },
-    };
 
+    let repositories = Repositories::postgres(db.clone());
 
     // Set gateway intents, which decides what events the bot will be notified about
     let intents = GatewayIntents::non_privileged()
         | GatewayIntents::GUILD_VOICE_STATES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT
-        | GatewayIntents::GUILD_INVITES;
+        | GatewayIntents::GUILD_INVITES
+        // Привилегированный intent: без присутствий в кэше `{game}` в шаблоне имени всегда пуст
+        | GatewayIntents::GUILD_PRESENCES;
 
     let mut client = Client::builder(&token, intents)
         .event_handler(Handler)
-        .framework(commands::generate_commands_framework(db.clone()).await)
+        .framework(commands::generate_commands_framework(repositories.clone()).await)
         .await
         .expect("Error creating client");
 
     {
         let mut data = client.data.write().await;
         data.insert::<SerenityPool>(db.clone());
+        data.insert::<Repositories>(repositories);
     };
 
     tracing::info!("Starting discord bot");
@@ -1,14 +1,20 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
 
 use futures::{StreamExt, stream::FuturesUnordered};
-use serenity::all::{ ChannelId, Context, Guild, GuildChannel, Http, PermissionOverwrite, PermissionOverwriteType, Permissions, UserId};
+use serenity::all::{ChannelId, Context, GuildChannel, GuildId, PermissionOverwrite, PermissionOverwriteType, Permissions, RoleId, UserId};
 
-use crate::sql::{autoroom::AutoRoomDeleteStrategy, pool::GLOBAL_SQL_POOL, prelude::{AutoRoom, MonitoredAutoRoom}};
+use crate::services::channel_guard::{ChannelGuard, ChannelGuards, SkipReason};
+use crate::services::cleanup::{CleanupOptions, CleanupReport};
+use crate::services::{channel_cache, gateway::DiscordGateway, room_creation, room_deletion, voice_presence};
+use crate::sql::{autoroom::{AutoRoomDeleteStrategy, RoomPrivacy}, prelude::{MonitoredAutoRoom, Repositories}};
 
 
-pub async fn grant_owner_privileges(http: &Http, channel: &ChannelId, user_id: &UserId) -> Result<(), serenity::Error> {
+pub async fn grant_owner_privileges(discord: &dyn DiscordGateway, channel: &ChannelId, user_id: &UserId) -> Result<(), serenity::Error> {
     let permissions = PermissionOverwrite {
         allow: Permissions::VIEW_CHANNEL
+            | Permissions::CONNECT
             | Permissions::SEND_MESSAGES
             | Permissions::MANAGE_CHANNELS
             | Permissions::MUTE_MEMBERS
@@ -16,7 +22,7 @@ pub async fn grant_owner_privileges(http: &Http, channel: &ChannelId, user_id: &
         deny: Permissions::empty(),
         kind: PermissionOverwriteType::Member(*user_id),
     };
-    if let Err(err) = channel.create_permission(http, permissions).await {
+    if let Err(err) = discord.create_permission(*channel, permissions).await {
         tracing::error!(
             "Failed to grant channel({:?}) permissions to the user({:?}). Error: \"{:?}\"",
             &channel.get(),
@@ -28,13 +34,75 @@ pub async fn grant_owner_privileges(http: &Http, channel: &ChannelId, user_id: &
     Ok(())
 }
 
-pub async fn grant_guest_privileges(http: &Http, channel: &ChannelId, user_id: &UserId) -> Result<(), serenity::Error> {
+/// Member holding the overwrite [`grant_owner_privileges`] creates, guests never get `MANAGE_CHANNELS`.
+pub fn owner_from_overwrites(channel: &GuildChannel, bot_id: UserId) -> Option<UserId> {
+    channel.permission_overwrites
+        .iter()
+        .filter(|overwrite| overwrite.allow.contains(Permissions::MANAGE_CHANNELS))
+        .find_map(|overwrite| match overwrite.kind {
+            PermissionOverwriteType::Member(user_id) if user_id != bot_id => Some(user_id),
+            _ => None,
+        })
+}
+
+/// Privacy of a room as currently set by the `@everyone` overwrite of the channel.
+pub fn room_privacy(channel: &GuildChannel) -> RoomPrivacy {
+    let everyone = PermissionOverwriteType::Role(RoleId::new(channel.guild_id.get()));
+    let deny = channel.permission_overwrites
+        .iter()
+        .find(|overwrite| overwrite.kind == everyone)
+        .map(|overwrite| overwrite.deny)
+        .unwrap_or_default();
+
+    if deny.contains(Permissions::VIEW_CHANNEL) {
+        RoomPrivacy::Hidden
+    } else if deny.contains(Permissions::CONNECT) {
+        RoomPrivacy::Locked
+    } else {
+        RoomPrivacy::Public
+    }
+}
+
+/// Keeps the room visible to the bot once `@everyone` can't see it, a bot without Administrator
+/// could neither post the panel nor edit or delete the room otherwise.
+pub async fn grant_bot_access(discord: &dyn DiscordGateway, channel_id: ChannelId) -> Result<(), serenity::Error> {
+    discord.create_permission(channel_id, PermissionOverwrite {
+        allow: Permissions::VIEW_CHANNEL | Permissions::CONNECT | Permissions::MANAGE_CHANNELS | Permissions::MOVE_MEMBERS,
+        deny: Permissions::empty(),
+        kind: PermissionOverwriteType::Member(discord.current_user_id()),
+    }).await
+}
+
+/// Applies the `@everyone` overwrite of the privacy mode to a freshly created room.
+pub async fn apply_room_privacy(discord: &dyn DiscordGateway, channel: &GuildChannel, privacy: RoomPrivacy) -> Result<(), serenity::Error> {
+    match privacy {
+        RoomPrivacy::Public => Ok(()),
+        RoomPrivacy::Locked => set_everyone_deny(discord, channel, Permissions::CONNECT, true).await,
+        RoomPrivacy::Hidden => {
+            grant_bot_access(discord, channel.id).await?;
+            set_everyone_deny(discord, channel, Permissions::VIEW_CHANNEL | Permissions::CONNECT, true).await
+        },
+    }
+}
+
+pub async fn grant_guest_privileges(
+    discord: &dyn DiscordGateway,
+    channel: &ChannelId,
+    user_id: &UserId,
+    privacy: RoomPrivacy
+) -> Result<(), serenity::Error> {
+    let allow = match privacy {
+        RoomPrivacy::Public => Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+        RoomPrivacy::Locked | RoomPrivacy::Hidden => Permissions::VIEW_CHANNEL
+            | Permissions::CONNECT
+            | Permissions::SEND_MESSAGES,
+    };
     let permissions = PermissionOverwrite {
-        allow: Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+        allow,
         deny: Permissions::empty(),
         kind: PermissionOverwriteType::Member(*user_id),
     };
-    if let Err(err) = channel.create_permission(http, permissions).await {
+    if let Err(err) = discord.create_permission(*channel, permissions).await {
         tracing::error!(
             "Failed to grant channel({:?}) permissions to the user({:?}). Error: \"{:?}\"",
             &channel.get(),
@@ -46,14 +114,25 @@ pub async fn grant_guest_privileges(http: &Http, channel: &ChannelId, user_id: &
     Ok(())
 }
 
+/// Public rooms get an explicit `CONNECT` deny, otherwise the member could simply rejoin.
+/// Locked and hidden rooms only lose the member overwrite and fall back to the `@everyone` deny.
 pub async fn revoke_guest_privileges(
-    http: &Http, 
-    channel: &ChannelId, 
-    user_id: &UserId
+    discord: &dyn DiscordGateway,
+    channel: &ChannelId,
+    user_id: &UserId,
+    privacy: RoomPrivacy
 ) -> Result<(), serenity::Error> {
     let target = PermissionOverwriteType::Member(*user_id);
 
-    if let Err(err) = channel.delete_permission(http, target).await {
+    let result = match privacy {
+        RoomPrivacy::Public => discord.create_permission(*channel, PermissionOverwrite {
+            allow: Permissions::empty(),
+            deny: Permissions::CONNECT,
+            kind: target,
+        }).await,
+        RoomPrivacy::Locked | RoomPrivacy::Hidden => discord.delete_permission(*channel, target).await,
+    };
+    if let Err(err) = result {
         tracing::error!(
             "Failed to revoke channel({:?}) permissions from user({:?}). Error: \"{:?}\"",
             channel.get(),
@@ -66,12 +145,47 @@ pub async fn revoke_guest_privileges(
     Ok(())
 }
 
+/// Adds `permissions` to the `@everyone` deny list of the channel or lifts them, keeping the rest of the overwrite.
+pub async fn set_everyone_deny(
+    discord: &dyn DiscordGateway,
+    channel: &GuildChannel,
+    permissions: Permissions,
+    deny: bool
+) -> Result<(), serenity::Error> {
+    let everyone = PermissionOverwriteType::Role(RoleId::new(channel.guild_id.get()));
+    let existing = channel.permission_overwrites.iter().find(|overwrite| overwrite.kind == everyone);
+    let mut overwrite = existing.cloned().unwrap_or(PermissionOverwrite {
+        allow: Permissions::empty(),
+        deny: Permissions::empty(),
+        kind: everyone,
+    });
+    if deny {
+        overwrite.allow.remove(permissions);
+        overwrite.deny.insert(permissions);
+    } else {
+        overwrite.deny.remove(permissions);
+    }
+
+    if overwrite.allow.is_empty() && overwrite.deny.is_empty() {
+        if existing.is_some() {
+            discord.delete_permission(channel.id, everyone).await?;
+        }
+        return Ok(());
+    }
+    discord.create_permission(channel.id, overwrite).await
+}
+
 
 pub mod voice_channel {
-    use serenity::all::{ChannelId, EditMember, GuildId, Http, User};
+    use std::collections::HashSet;
+
+    use serenity::all::{ChannelId, EditChannel, GuildChannel, GuildId, Mentionable, Permissions, PremiumTier, User, UserId};
 
-    use crate::{services::autoroom::revoke_guest_privileges, sql::{pool::PoolType, prelude::MonitoredAutoRoom}};
-    use super::grant_guest_privileges;
+    use crate::bitrate::get_bitrate;
+    use crate::services::{gateway::DiscordGateway, ownership::transfer_ownership, permanent_room::{self, PermanentRoomError}};
+    use crate::services::rename_queue::{self, RenameSlot};
+    use crate::{services::autoroom::revoke_guest_privileges, sql::prelude::Repositories};
+    use super::{grant_bot_access, grant_guest_privileges, room_privacy, set_everyone_deny};
 
     #[derive(thiserror::Error, Debug)]
     pub enum BotError {
@@ -83,25 +197,78 @@ pub mod voice_channel {
 
         #[error("Could not reach Discord. Please try again later")]
         SerenityError,
+
+        #[error("You must be in the room to do this")]
+        NotInRoom,
+
+        #[error("The host is still in the room")]
+        OwnerPresent,
+
+        #[error("Bots can't own a room")]
+        BotOwner,
+
+        #[error("{0}")]
+        Rejected(&'static str),
     }
 
-    pub async fn invite_user(http: &Http, pool: &PoolType, author_id: i64, invited_user: &User) -> Result<(), BotError> {
-        let monitored_autoroom = match MonitoredAutoRoom::get_by_owner_id(pool, author_id).await {
-            Ok(option) => match option {
-                Some(monitored_autoroom_result) => monitored_autoroom_result,
-                None => return Err(BotError::MonitoredAutoRoomNotFound)
-            },
-            Err(err) => {
-                tracing::error!("invite_user database error AUTHOR({}) INVITED({}).\n{}", author_id, invited_user, err);
-                return Err(BotError::DatabaseError)
-            },
-        };
+    /// Current owner of a temporary or permanent room.
+    pub async fn get_channel_owner_id(repositories: &Repositories, channel_id: ChannelId) -> Result<Option<UserId>, sqlx::Error> {
+        if let Some(monitored_autoroom) = repositories.rooms.get_by_channel_id(channel_id.get() as i64).await? {
+            return Ok(Some(UserId::new(monitored_autoroom.owner_id as u64)));
+        }
 
-        let channel_id = ChannelId::new(monitored_autoroom.channel_id as u64);
+        Ok(
+            repositories.permanent.get_by_channel_id(channel_id.get() as i64)
+                .await?
+                .map(|room| UserId::new(room.owner_id as u64))
+        )
+    }
+
+    /// Resolves the room `owner_id` manages in the guild: the room they are connected to when they own it,
+    /// otherwise their newest temporary room of the guild, then their permanent room there.
+    pub async fn get_owned_channel_id(
+        discord: &dyn DiscordGateway,
+        repositories: &Repositories,
+        guild_id: GuildId,
+        owner_id: i64
+    ) -> Result<Option<ChannelId>, sqlx::Error> {
+        let owner = UserId::new(owner_id as u64);
+        if let Some(channel_id) = discord.voice_channel_id(guild_id, owner) {
+            if get_channel_owner_id(repositories, channel_id).await? == Some(owner) {
+                return Ok(Some(channel_id));
+            }
+        }
+
+        // Rows of temporary rooms don't store the guild, its channels tell the rooms apart
+        let guild_channel_ids: HashSet<ChannelId> = discord
+            .guild_channels(guild_id)
+            .unwrap_or_default()
+            .iter()
+            .map(|channel| channel.id)
+            .collect();
+        let temporary = repositories.rooms.get_all_by_owner_id(owner_id)
+            .await?
+            .into_iter()
+            .map(|room| ChannelId::new(room.channel_id as u64))
+            .find(|channel_id| guild_channel_ids.contains(channel_id));
+        if temporary.is_some() {
+            return Ok(temporary);
+        }
+
+        Ok(
+            repositories.permanent.get_by_owner_id(guild_id.get() as i64, owner_id)
+                .await?
+                .map(|room| ChannelId::new(room.channel_id as u64))
+        )
+    }
+
+    pub async fn invite_user(discord: &dyn DiscordGateway, repositories: &Repositories, guild_id: GuildId, author_id: i64, invited_user: &User) -> Result<(), BotError> {
+        let channel = get_owned_guild_channel(discord, repositories, guild_id, author_id).await?;
+        let privacy = room_privacy(&channel);
         
-        tracing::info!("Invite User. Inviter({}) Invited({}) to Channel({})", author_id, invited_user.id.get(), channel_id.get());
+        tracing::info!("Invite User. Inviter({}) Invited({}) to Channel({}) Privacy({:?})", author_id, invited_user.id.get(), channel.id.get(), privacy);
 
-        grant_guest_privileges(http, &channel_id, &invited_user.id)
+        grant_guest_privileges(discord, &channel.id, &invited_user.id, privacy)
             .await
             .map_err(|err| {
                 tracing::error!("invite_user serenity error AUTHOR({}) INVITED({}).\n{}", author_id, invited_user, err);
@@ -111,31 +278,21 @@ pub mod voice_channel {
         Ok(())
     }
 
-    pub async fn kick_user(http: &Http, pool: &PoolType, guild_id: GuildId, author_id: i64, user_to_kick: &User) -> Result<(), BotError> {
-        let monitored_autoroom = match MonitoredAutoRoom::get_by_owner_id(pool, author_id).await {
-            Ok(option) => match option {
-                Some(monitored_autoroom_result) => monitored_autoroom_result,
-                None => return Err(BotError::MonitoredAutoRoomNotFound)
-            },
-            Err(err) => {
-                tracing::error!("kick_user database error KICKER({}) KICKED({}).\n{}", author_id, user_to_kick, err);
-                return Err(BotError::DatabaseError)
-            },
-        };
-
-        let channel_id = ChannelId::new(monitored_autoroom.channel_id as u64);
+    pub async fn kick_user(discord: &dyn DiscordGateway, repositories: &Repositories, guild_id: GuildId, author_id: i64, user_to_kick: &User) -> Result<(), BotError> {
+        let channel = get_owned_guild_channel(discord, repositories, guild_id, author_id).await?;
+        let privacy = room_privacy(&channel);
         
-        tracing::info!("Kick User. KICKER({}) KICKED({}) to CHANNEL({})", author_id, user_to_kick.id.get(), channel_id.get());
+        tracing::info!("Kick User. KICKER({}) KICKED({}) to CHANNEL({}) PRIVACY({:?})", author_id, user_to_kick.id.get(), channel.id.get(), privacy);
 
-        revoke_guest_privileges(http, &channel_id, &user_to_kick.id)
+        revoke_guest_privileges(discord, &channel.id, &user_to_kick.id, privacy)
             .await
             .map_err(|err| {
                 tracing::error!("kick_user serenity error KICKER({}) KICKED({}).\n{}", author_id, user_to_kick, err);
                 BotError::SerenityError
             })?;
 
-        guild_id
-            .edit_member(http, user_to_kick, EditMember::new().disconnect_member())
+        discord
+            .disconnect_member(guild_id, user_to_kick.id)
             .await
             .map_err(|err| {
                 tracing::error!("kick_user serenity error KICKER({}) KICKED({}).\n{}", author_id, user_to_kick, err);
@@ -144,18 +301,271 @@ pub mod voice_channel {
 
         Ok(())
     }
+
+    /// Outcome of a panel action over the members picked in a select.
+    #[derive(Debug, Default)]
+    pub struct MembersReport {
+        pub done: Vec<UserId>,
+        /// The host picked themselves
+        pub skipped: Vec<UserId>,
+        pub failed: Vec<UserId>,
+    }
+
+    impl MembersReport {
+        pub fn to_message(&self, done_label: &str) -> String {
+            let mentions = |ids: &[UserId]| ids.iter().map(|id| id.mention().to_string()).collect::<Vec<_>>().join(", ");
+            let mut lines = Vec::new();
+            if !self.done.is_empty() {
+                lines.push(format!("{}: {}", done_label, mentions(&self.done)));
+            }
+            if !self.skipped.is_empty() {
+                lines.push(format!("Skipped, you are the host: {}", mentions(&self.skipped)));
+            }
+            if !self.failed.is_empty() {
+                lines.push(format!("⚠️ Failed: {}", mentions(&self.failed)));
+            }
+            lines.join("\n")
+        }
+    }
+
+    pub async fn invite_users(discord: &dyn DiscordGateway, channel_id: ChannelId, author_id: i64, user_ids: &[UserId]) -> Result<MembersReport, BotError> {
+        let channel = room_channel(discord, channel_id).await?;
+        let privacy = room_privacy(&channel);
+        let mut report = MembersReport::default();
+
+        for user_id in user_ids {
+            if user_id.get() as i64 == author_id {
+                report.skipped.push(*user_id);
+                continue;
+            }
+            tracing::info!("Invite User. Inviter({}) Invited({}) to Channel({}) Privacy({:?})", author_id, user_id, channel.id, privacy);
+            match grant_guest_privileges(discord, &channel.id, user_id, privacy).await {
+                Ok(_) => report.done.push(*user_id),
+                Err(err) => {
+                    tracing::error!("invite_users serenity error AUTHOR({}) INVITED({}).\n{}", author_id, user_id, err);
+                    report.failed.push(*user_id);
+                },
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Revokes access of every picked member and disconnects the ones inside the room.
+    pub async fn kick_users(discord: &dyn DiscordGateway, channel_id: ChannelId, author_id: i64, user_ids: &[UserId]) -> Result<MembersReport, BotError> {
+        let channel = room_channel(discord, channel_id).await?;
+        let guild_id = channel.guild_id;
+        let privacy = room_privacy(&channel);
+        let mut report = MembersReport::default();
+
+        for user_id in user_ids {
+            if user_id.get() as i64 == author_id {
+                report.skipped.push(*user_id);
+                continue;
+            }
+            tracing::info!("Kick User. KICKER({}) KICKED({}) to CHANNEL({}) PRIVACY({:?})", author_id, user_id, channel.id, privacy);
+            let mut result = revoke_guest_privileges(discord, &channel.id, user_id, privacy).await;
+            if result.is_ok() && discord.voice_channel_id(guild_id, *user_id) == Some(channel.id) {
+                result = discord.disconnect_member(guild_id, *user_id).await;
+            }
+            match result {
+                Ok(_) => report.done.push(*user_id),
+                Err(err) => {
+                    tracing::error!("kick_users serenity error KICKER({}) KICKED({}).\n{}", author_id, user_id, err);
+                    report.failed.push(*user_id);
+                },
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Room `author_id` manages in the guild, for the slash commands. Panels know their room.
+    pub async fn owned_room_id(discord: &dyn DiscordGateway, repositories: &Repositories, guild_id: GuildId, author_id: i64) -> Result<ChannelId, BotError> {
+        match get_owned_channel_id(discord, repositories, guild_id, author_id).await {
+            Ok(Some(channel_id)) => Ok(channel_id),
+            Ok(None) => Err(BotError::MonitoredAutoRoomNotFound),
+            Err(err) => {
+                tracing::error!("owned_room_id database error AUTHOR({}).\n{}", author_id, err);
+                Err(BotError::DatabaseError)
+            },
+        }
+    }
+
+    async fn room_channel(discord: &dyn DiscordGateway, channel_id: ChannelId) -> Result<GuildChannel, BotError> {
+        match discord.channel(channel_id).await {
+            Ok(channel) => channel.ok_or(BotError::MonitoredAutoRoomNotFound),
+            Err(err) => {
+                tracing::error!("room_channel serenity error CHANNEL({}).\n{}", channel_id, err);
+                Err(BotError::SerenityError)
+            }
+        }
+    }
+
+    async fn get_owned_guild_channel(discord: &dyn DiscordGateway, repositories: &Repositories, guild_id: GuildId, author_id: i64) -> Result<GuildChannel, BotError> {
+        let channel_id = owned_room_id(discord, repositories, guild_id, author_id).await?;
+        room_channel(discord, channel_id).await
+    }
+
+    async fn set_room_everyone_deny(
+        discord: &dyn DiscordGateway,
+        channel_id: ChannelId,
+        author_id: i64,
+        permissions: Permissions,
+        deny: bool
+    ) -> Result<(), BotError> {
+        let channel = room_channel(discord, channel_id).await?;
+
+        tracing::info!("Room permissions. AUTHOR({}) CHANNEL({}) DENY({}) {:?}", author_id, channel.id.get(), deny, permissions);
+
+        set_everyone_deny(discord, &channel, permissions, deny)
+            .await
+            .map_err(|err| {
+                tracing::error!("set_room_everyone_deny serenity error AUTHOR({}) CHANNEL({}).\n{}", author_id, channel.id, err);
+                BotError::SerenityError
+            })
+    }
+
+    /// Denies or restores `CONNECT` for `@everyone`. The owner and guests keep their member overwrites.
+    pub async fn set_room_locked(discord: &dyn DiscordGateway, channel_id: ChannelId, author_id: i64, locked: bool) -> Result<(), BotError> {
+        set_room_everyone_deny(discord, channel_id, author_id, Permissions::CONNECT, locked).await
+    }
+
+    /// Denies or restores `VIEW_CHANNEL` for `@everyone`. The owner, guests and the bot keep their member overwrites.
+    pub async fn set_room_hidden(discord: &dyn DiscordGateway, channel_id: ChannelId, author_id: i64, hidden: bool) -> Result<(), BotError> {
+        if hidden {
+            grant_bot_access(discord, channel_id).await.map_err(|err| {
+                tracing::error!("set_room_hidden serenity error AUTHOR({}) CHANNEL({}).\n{}", author_id, channel_id, err);
+                BotError::SerenityError
+            })?;
+        }
+        set_room_everyone_deny(discord, channel_id, author_id, Permissions::VIEW_CHANNEL, hidden).await
+    }
+
+    async fn edit_room(
+        discord: &dyn DiscordGateway,
+        channel_id: ChannelId,
+        author_id: i64,
+        builder: EditChannel<'_>
+    ) -> Result<(), BotError> {
+        let channel = room_channel(discord, channel_id).await?;
+
+        discord
+            .edit_channel(channel.id, builder)
+            .await
+            .map(|_| ())
+            .map_err(|err| {
+                tracing::error!("edit_room serenity error AUTHOR({}) CHANNEL({}).\n{}", author_id, channel.id, err);
+                BotError::SerenityError
+            })
+    }
+
+    /// Renames the room right away when the rate limit allows it, otherwise queues the name.
+    pub async fn rename_room(discord: &dyn DiscordGateway, channel_id: ChannelId, author_id: i64, name: &str) -> Result<RenameSlot, BotError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(BotError::Rejected("Room name must be 1 to 100 characters long"));
+        }
+        let channel = room_channel(discord, channel_id).await?;
+
+        let slot = rename_queue::rename(discord, channel.id, name).await.map_err(|err| {
+            tracing::error!("rename_room serenity error AUTHOR({}) CHANNEL({}).\n{}", author_id, channel.id, err);
+            BotError::SerenityError
+        })?;
+        tracing::info!("Rename Room. AUTHOR({}) CHANNEL({}) NAME({}) SLOT({:?})", author_id, channel.id, name, slot);
+        Ok(slot)
+    }
+
+    pub async fn set_room_user_limit(discord: &dyn DiscordGateway, channel_id: ChannelId, author_id: i64, user_limit: u32) -> Result<(), BotError> {
+        if user_limit > 99 {
+            return Err(BotError::Rejected("User limit must be between 0 and 99"));
+        }
+
+        tracing::info!("Room user limit. AUTHOR({}) CHANNEL({}) LIMIT({})", author_id, channel_id, user_limit);
+        edit_room(discord, channel_id, author_id, EditChannel::new().user_limit(user_limit)).await
+    }
+
+    /// Sets the room bitrate, capped by the guild boost tier. Returns the applied bitrate in bps.
+    pub async fn set_room_bitrate(discord: &dyn DiscordGateway, channel_id: ChannelId, author_id: i64, kbps: u32) -> Result<u32, BotError> {
+        if !(8..=384).contains(&kbps) {
+            return Err(BotError::Rejected("Bitrate must be between 8 and 384 kbps"));
+        }
+
+        let channel = room_channel(discord, channel_id).await?;
+        let max_bitrate = discord
+            .premium_tier(channel.guild_id)
+            .map(|premium_tier| get_bitrate(&premium_tier))
+            .unwrap_or(get_bitrate(&PremiumTier::Tier0));
+        let bitrate = (kbps * 1000).min(max_bitrate);
+
+        tracing::info!("Room bitrate. AUTHOR({}) CHANNEL({}) BITRATE({})", author_id, channel.id.get(), bitrate);
+        discord
+            .edit_channel(channel.id, EditChannel::new().bitrate(bitrate))
+            .await
+            .map_err(|err| {
+                tracing::error!("set_room_bitrate serenity error AUTHOR({}) CHANNEL({}).\n{}", author_id, channel.id, err);
+                BotError::SerenityError
+            })?;
+
+        Ok(bitrate)
+    }
+
+    pub async fn transfer_user_room(
+        discord: &dyn DiscordGateway,
+        repositories: &Repositories,
+        guild_id: GuildId,
+        author_id: i64,
+        new_owner: &User
+    ) -> Result<(), BotError> {
+        if new_owner.bot {
+            return Err(BotError::BotOwner);
+        }
+        let author = UserId::new(author_id as u64);
+        let database_error = |err: sqlx::Error| {
+            tracing::error!("transfer_user_room database error AUTHOR({}) NEW_OWNER({}).\n{}", author_id, new_owner, err);
+            BotError::DatabaseError
+        };
+
+        let channel_id = get_owned_channel_id(discord, repositories, guild_id, author_id)
+            .await
+            .map_err(database_error)?
+            .ok_or(BotError::MonitoredAutoRoomNotFound)?;
+        if repositories.rooms.get_by_channel_id(channel_id.get() as i64).await.map_err(database_error)?.is_some() {
+            return transfer_ownership(discord, repositories.rooms.as_ref(), channel_id, author, new_owner.id).await;
+        }
+
+        let room = repositories.permanent.get_by_channel_id(channel_id.get() as i64)
+            .await
+            .map_err(database_error)?
+            .ok_or(BotError::MonitoredAutoRoomNotFound)?;
+        permanent_room::transfer_room(discord, repositories, &room, new_owner.id)
+            .await
+            .map_err(|err| match err {
+                PermanentRoomError::NotFound => BotError::MonitoredAutoRoomNotFound,
+                PermanentRoomError::Rejected(reason) => BotError::Rejected(reason),
+                PermanentRoomError::DatabaseError => BotError::DatabaseError,
+                _ => BotError::SerenityError,
+            })
+    }
 }
 
 struct CleanUpDbRecord {
     channel: Option<GuildChannel>,
-    autoroom: MonitoredAutoRoom
+    autoroom: MonitoredAutoRoom,
+    uncommitted: bool
 }
 
 #[derive(Default, Debug)]
 struct CleanUpDbResult {
     pub not_a_guild_channel: Vec<i64>,
     pub not_match_ids: Vec<i64>,
-    pub are_empty: Vec<GuildChannel>
+    pub are_empty: Vec<GuildChannel>,
+    pub are_pending: Vec<(ChannelId, f64)>,
+    pub are_rejoined: Vec<ChannelId>,
+    pub are_uncommitted: Vec<GuildChannel>,
+    pub are_settling: Vec<ChannelId>,
+    /// Every room still in place with its guild
+    pub existing: Vec<(GuildId, MonitoredAutoRoom)>
 }
 
 impl CleanUpDbResult {
@@ -169,31 +579,119 @@ impl CleanUpDbResult {
 }
 
 
-pub async fn cleanup_db_monitored_rooms(ctx: &Context) -> Result<(), String> {
+/// Deletes the channel when the guard of its guild allows it, otherwise records it as skipped.
+/// A dry run only records what would be deleted.
+async fn guarded_delete(
+    ctx: &Context,
+    repositories: &Repositories,
+    guards: &mut ChannelGuards,
+    report: &mut CleanupReport,
+    channel: &GuildChannel
+) -> Result<(), String> {
+    let guard = guards.get(repositories, channel.guild_id).await.map_err(|err| err.to_string())?;
+    if let Err(reason) = guard.check(channel.id) {
+        report.skip(channel.id, reason);
+        return Ok(());
+    }
+    if report.dry_run {
+        report.deleted.push(channel.id);
+        return Ok(());
+    }
+
+    match channel.delete(&ctx.http).await {
+        Ok(_) => {
+            report.deleted.push(channel.id);
+            if let Err(err) = repositories.channels.forget_created(&[channel.id.get() as i64]).await {
+                tracing::error!("Error to forget created channel ({}).\nError: {}", channel.id.get(), err);
+            }
+        },
+        Err(err) => tracing::error!("Error to delete channel ({}).\nError: {}", channel.id.get(), err),
+    };
+    Ok(())
+}
+
+/// Records rooms missing from the ledger that migration 0011 would have recorded: rooms of an autoroom of the same guild.
+/// Rows the old cleanup adopted have no autoroom, they may be channels of the admins and stay out.
+async fn backfill_ledger(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    existing: &[(GuildId, MonitoredAutoRoom)]
+) -> Result<(), sqlx::Error> {
+    let bot_id = discord.current_user_id().get() as i64;
+    let mut known = ChannelGuards::default();
+    for (guild_id, room) in existing {
+        let autoroom_channel_id = match room.autoroom_channel_id {
+            Some(autoroom_channel_id) => autoroom_channel_id,
+            None => continue,
+        };
+        let channel_id = ChannelId::new(room.channel_id as u64);
+        if known.get(repositories, *guild_id).await?.check(channel_id) != Err(SkipReason::NotCreatedByBot) {
+            continue;
+        }
+        let tied = repositories.autorooms
+            .get_by_channel_id(autoroom_channel_id)
+            .await?
+            .is_some_and(|autoroom| autoroom.guild_id == guild_id.get() as i64);
+        if !tied {
+            continue;
+        }
+        // Rows of bot owned rooms don't know who the room was created for
+        let creator_id = (room.owner_id != bot_id).then_some(room.owner_id);
+        tracing::info!("[Cleanup DB] Recording room CHANNEL({}) CREATOR({:?}) in the ledger", channel_id, creator_id);
+        repositories.channels.record_created(guild_id.get() as i64, room.channel_id, creator_id).await?;
+    }
+    Ok(())
+}
+
+pub async fn cleanup_db_monitored_rooms(
+    ctx: &Context,
+    repositories: &Repositories,
+    options: &CleanupOptions
+) -> Result<CleanupReport, String> {
     tracing::info!("Starting cleanup monitored rooms");
-    let pool = GLOBAL_SQL_POOL.get().unwrap().get_pool();
-    let autorooms = MonitoredAutoRoom::get_all(&pool)
+    let rooms = repositories.rooms.as_ref();
+    let autorooms = rooms.get_all()
         .await
         .map_err(|err| err.to_string())?;
 
     tracing::info!("Total monitored rooms {}", autorooms.len());
 
+    let pending_deletions: HashMap<i64, f64> = rooms.get_pending_deletions()
+        .await
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .collect();
+
+    let uncommitted: HashSet<i64> = rooms.get_uncommitted_ids()
+        .await
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .collect();
+
     let mut cleanup_result = CleanUpDbResult::default();
     let mut tasks = FuturesUnordered::new();
     let http = &ctx.http;
     let cache = &ctx.cache;
 
     for room in autorooms {
+        // Rooms still being set up are left alone, interrupted creations are rolled back
+        let is_uncommitted = uncommitted.contains(&room.channel_id);
+        if is_uncommitted && room_creation::is_in_flight(ChannelId::new(room.channel_id as u64)) {
+            continue;
+        }
 
         tasks.push(async move {
             let channel = match ChannelId::new(room.channel_id as u64).to_channel(http).await {
-                Ok(c) => c,
+                Ok(c) => c.guild(),
+                // The row of an interrupted creation goes away even when its channel is already gone
+                Err(_) if is_uncommitted => None,
                 Err(_) => return None,
             };
             
             Some(CleanUpDbRecord {
-                channel: channel.guild(),
-                autoroom: room
+                channel,
+                autoroom: room,
+                uncommitted: is_uncommitted
             })
         });
     };
@@ -203,47 +701,118 @@ pub async fn cleanup_db_monitored_rooms(ctx: &Context) -> Result<(), String> {
             let autoroom = record.autoroom;
             let channel = match record.channel {
                 Some(_c) => _c,
+                // Gone channels can't be tied to a guild, only a full cleanup removes their rows
+                None if options.guild_id.is_some() => continue,
                 None => {
                     cleanup_result.not_a_guild_channel.push(autoroom.channel_id);
                     continue;
                 },
             };
+            if !options.includes(channel.guild_id) {
+                continue;
+            }
             if channel.id.get() != autoroom.channel_id as u64 {
                 cleanup_result.not_match_ids.push(autoroom.channel_id);
                 continue;
             };
+            cleanup_result.existing.push((channel.guild_id, autoroom.clone()));
+            // The owner of a room set up moments ago may not be connected yet
+            if room_creation::is_settling(channel.id) {
+                cleanup_result.are_settling.push(channel.id);
+                continue;
+            }
+            if record.uncommitted {
+                cleanup_result.are_uncommitted.push(channel);
+                continue;
+            }
 
             let members = channel.members(cache).map_err(|err| err.to_string())?;
-            if members.len() > 0 {
+            let pending_deletion = pending_deletions.get(&autoroom.channel_id).copied();
+            if !members.is_empty() {
+                if pending_deletion.is_some() {
+                    cleanup_result.are_rejoined.push(channel.id);
+                };
+                continue;
+            };
+            if let Some(remaining) = pending_deletion.filter(|remaining| *remaining > 0.0) {
+                cleanup_result.are_pending.push((channel.id, remaining));
                 continue;
             };
             cleanup_result.are_empty.push(channel);
         };
     };
 
-    for channel in &cleanup_result.are_empty {
-        match channel.delete(http).await {
-            Ok(_) => (),
-            Err(_err) => tracing::error!("Error to delete channel ({}).\nError: {}", channel.id.get(), _err)
+    if !options.dry_run {
+        backfill_ledger(ctx, repositories, &cleanup_result.existing)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+
+    // Rows of skipped channels stay, the next cleanup looks at them again
+    let mut guards = ChannelGuards::default();
+    let mut report = CleanupReport::new(options);
+    for channel_id in &cleanup_result.are_settling {
+        report.skip(*channel_id, SkipReason::Settling);
+    }
+    for channel in cleanup_result.are_uncommitted.iter().chain(cleanup_result.are_empty.iter()) {
+        guarded_delete(ctx, repositories, &mut guards, &mut report, channel).await?;
+    }
+    report.stale_rows = cleanup_result.not_a_guild_channel
+        .iter()
+        .copied()
+        .chain(
+            cleanup_result.are_uncommitted
+                .iter()
+                .filter(|c| report.deleted.contains(&c.id))
+                .map(|c| c.id.get() as i64)
+        )
+        .collect();
+    report.mismatched_ids = cleanup_result.not_match_ids.clone();
+
+    if options.dry_run {
+        tracing::info!(
+            "[Cleanup DB] Dry run | Stale rows: {} | Mismatch IDs: {} | To delete: {} | Skipped: {}",
+            report.stale_rows.len(),
+            report.mismatched_ids.len(),
+            report.deleted.len(),
+            report.skipped.len()
+        );
+        return Ok(report);
+    }
+
+    for (channel_id, remaining) in &cleanup_result.are_pending {
+        room_deletion::arm(Arc::new(ctx.clone()), repositories.clone(), *channel_id, Duration::from_secs_f64(*remaining));
+    }
+    for channel_id in &cleanup_result.are_rejoined {
+        if let Err(err) = rooms.cancel_deletion(channel_id.get() as i64).await {
+            tracing::error!("Error to cancel channel ({}) deletion.\nError: {}", channel_id.get(), err);
         }
     }
 
-    let are_empty_ids = cleanup_result.are_empty
-            .iter()
-            .map(|c| c.id.get() as i64)
-            .collect();
-    let ids_to_delete = [cleanup_result.outdated(), are_empty_ids].concat();
+    // Only rows of channels deleted above go, failed deletions are retried by the next cleanup
+    let removed_channel_ids = report.deleted
+        .iter()
+        .map(|channel_id| channel_id.get() as i64)
+        .collect();
+    let ids_to_delete = [cleanup_result.outdated(), removed_channel_ids].concat();
 
-    match MonitoredAutoRoom::remove_many(&pool, &ids_to_delete).await {
+    match rooms.remove_many(&ids_to_delete).await {
         Ok(_) => {
+            for channel_id in &ids_to_delete {
+                channel_cache::set_monitored(ChannelId::new(*channel_id as u64), false);
+            }
             tracing::info!(
-                "[Cleanup DB] Completed | Cleaned: {} | Not a guild: {} | Mismatch IDs: {} | Discord removed: {}",
+                "[Cleanup DB] Completed | Cleaned: {} | Not a guild: {} | Mismatch IDs: {} | Discord removed: {} | Pending deletion: {} | Rejoined: {} | Uncommitted: {} | Skipped: {}",
                 ids_to_delete.len(),
                 cleanup_result.not_a_guild_channel.len(),
                 cleanup_result.not_match_ids.len(),
-                cleanup_result.are_empty.len()
+                report.deleted.len(),
+                cleanup_result.are_pending.len(),
+                cleanup_result.are_rejoined.len(),
+                cleanup_result.are_uncommitted.len(),
+                report.skipped.len()
             );
-            Ok(())
+            Ok(report)
         },
         Err(err) => Err(err.to_string()),
     }
@@ -251,43 +820,97 @@ pub async fn cleanup_db_monitored_rooms(ctx: &Context) -> Result<(), String> {
 }
 
 
+/// Where the owner of an adopted room was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OwnerSource {
+    Overwrite,
+    Creator,
+    LongestPresent,
+    Bot,
+}
+
+/// Owner of a room the bot lost track of: the owner overwrite, then the member the room was created for,
+/// then the member present the longest. The bot owns rooms with only bots inside.
+/// Legacy ledger rows name the bot as the creator of bot owned rooms, a bot creator counts as unknown.
+fn recover_owner(
+    discord: &dyn DiscordGateway,
+    guard: &ChannelGuard,
+    channel: &GuildChannel,
+    members: &[UserId]
+) -> (UserId, OwnerSource) {
+    let bot_id = discord.current_user_id();
+    if let Some(owner_id) = owner_from_overwrites(channel, bot_id) {
+        return (owner_id, OwnerSource::Overwrite);
+    }
+    let creator_id = guard.creator(channel.id).filter(|creator_id| *creator_id != bot_id && !discord.is_bot(*creator_id));
+    if let Some(creator_id) = creator_id {
+        return (creator_id, OwnerSource::Creator);
+    }
+    let humans: Vec<UserId> = members.iter().copied().filter(|user_id| !discord.is_bot(*user_id)).collect();
+    match voice_presence::longest_present(channel.id, &humans) {
+        Some(user_id) => (user_id, OwnerSource::LongestPresent),
+        None => (bot_id, OwnerSource::Bot),
+    }
+}
+
 enum CleanUpCategoriesRecord {
-    Found(GuildChannel),
-    NotFound(i64)
+    Found(Box<GuildChannel>),
+    NotFound(i64),
+    /// Discord could not be asked, nothing is known about the category
+    Unreachable(i64)
 }
 
 struct CleanUpCategoriesGuild {
-    pub guild: Guild,
+    pub channels: Vec<GuildChannel>,
     pub category_ids: HashSet<u64>
 }
 
 impl CleanUpCategoriesGuild {
-    pub fn new(guild:Guild, category_id: u64) -> Self {
+    pub fn new(channels: Vec<GuildChannel>, category_id: u64) -> Self {
         let mut set = HashSet::new();
         set.insert(category_id);
         Self {
-            guild: guild,
+            channels,
             category_ids: set,
         }
     }
 }
 
-pub async fn cleanup_categories_monitored_rooms(ctx: &Context) -> Result<(), String> {
+/// Main and overflow categories of the autorooms the cleanup covers.
+async fn scoped_category_ids(repositories: &Repositories, options: &CleanupOptions) -> Result<Vec<i64>, sqlx::Error> {
+    let guild_id = match options.guild_id {
+        Some(guild_id) => guild_id,
+        None => return repositories.autorooms.get_all_category_ids().await,
+    };
+
+    let mut category_ids = Vec::new();
+    for autoroom in repositories.autorooms.get_guild_autorooms(guild_id.get() as i64).await? {
+        category_ids.push(autoroom.category_id);
+        let overflow = repositories.autorooms.get_overflow_categories(autoroom.channel_id).await?;
+        category_ids.extend(overflow.iter().map(|category| category.category_id));
+    }
+    category_ids.sort_unstable();
+    category_ids.dedup();
+    Ok(category_ids)
+}
+
+pub async fn cleanup_categories_monitored_rooms(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    options: &CleanupOptions
+) -> Result<CleanupReport, String> {
     tracing::info!("Starting categories cleanup monitored rooms");
-    let pool = GLOBAL_SQL_POOL.get().unwrap().get_pool();
-    let category_ids = AutoRoom::get_all_category_ids(&pool)
+    let category_ids = scoped_category_ids(repositories, options)
         .await
         .map_err(|_err| _err.to_string())?;
     tracing::info!("Total category count in autoroom {}", category_ids.len());
     let mut tasks = FuturesUnordered::new();
 
-    let http = &ctx.http;
     for category_id in category_ids {
-        let http = http.clone();
         tasks.push(async move {
-            match ChannelId::new(category_id as u64).to_channel(http).await {
+            match discord.channel(ChannelId::new(category_id as u64)).await {
                 Ok(c) => {
-                    if let Some(g) = c.guild() {
+                    if let Some(g) = c {
                         if g.id.get() as i64 != category_id {
                             tracing::error!(
                                 "[Category id missmatch]: [Discord({})] |[Db({})]",
@@ -296,84 +919,148 @@ pub async fn cleanup_categories_monitored_rooms(ctx: &Context) -> Result<(), Str
                             );
                             return CleanUpCategoriesRecord::NotFound(category_id)
                         };
-                        return CleanUpCategoriesRecord::Found(g)
+                        return CleanUpCategoriesRecord::Found(Box::new(g))
                     };
                     CleanUpCategoriesRecord::NotFound(category_id)
                 },
                 Err(err) => {
-                    tracing::error!("Category({}) not found.\nError: {}", category_id, err.to_string());
-                    CleanUpCategoriesRecord::NotFound(category_id)
+                    tracing::error!("Failed to fetch Category({}).\nError: {}", category_id, err.to_string());
+                    CleanUpCategoriesRecord::Unreachable(category_id)
                 }
             }
         });
     }
 
     let mut outdated_categories: Vec<i64> = Vec::new();
-    let mut guilds: HashMap<u64, CleanUpCategoriesGuild> = HashMap::default();
+    let mut guilds: HashMap<GuildId, CleanUpCategoriesGuild> = HashMap::default();
     while let Some(result) = tasks.next().await {
         match result {
             CleanUpCategoriesRecord::NotFound(category_id) => {
                 outdated_categories.push(category_id);
             },
+            // The autoroom is kept, the next run checks the category again
+            CleanUpCategoriesRecord::Unreachable(category_id) => {
+                tracing::warn!("[Cleanup categories] Category({}) skipped, Discord is unreachable", category_id);
+            },
             CleanUpCategoriesRecord::Found(category) => {
-                let guild: Guild = (*category
-                // let guild: CacheRef<'_, GuildId, Guild, Infallible> = category
-                    .guild(ctx)
-                    .expect("GuildChannel without guild")).clone();
-                let guild_id = guild.id.get().clone();
-                guilds.entry(guild_id)
-                    .or_insert_with(
-                        ||
-                        CleanUpCategoriesGuild::new(guild, category.id.get())
-                    )
-                    .category_ids
-                    .insert(category.id.get());
+                if !options.includes(category.guild_id) {
+                    continue;
+                }
+                if let Some(guild) = guilds.get_mut(&category.guild_id) {
+                    guild.category_ids.insert(category.id.get());
+                    continue;
+                }
+                let channels = match discord.guild_channels(category.guild_id) {
+                    Some(channels) => channels,
+                    None => {
+                        tracing::error!("[Cleanup categories] GUILD({}) not found in cache", category.guild_id.get());
+                        continue;
+                    }
+                };
+                guilds.insert(category.guild_id, CleanUpCategoriesGuild::new(channels, category.id.get()));
             }
         }
     }
 
-    if !outdated_categories.is_empty() {
-        AutoRoom::delete(
-            &pool, 
-            AutoRoomDeleteStrategy::ManyByCategoryId(&outdated_categories)
-        )
+    let mut report = CleanupReport::new(options);
+    if !outdated_categories.is_empty() && !options.dry_run {
+        repositories.autorooms.delete(AutoRoomDeleteStrategy::ManyByCategoryId(&outdated_categories))
+            .await
+            .map_err(|err| err.to_string())?;
+        channel_cache::forget_triggers();
+        repositories.autorooms.remove_overflow_categories(&outdated_categories)
             .await
             .map_err(|err| err.to_string())?;
         tracing::info!("Removed ({}) outdated categories", outdated_categories.len());
     }
+    report.outdated_categories = outdated_categories;
 
     if !guilds.is_empty() {
-        let channels: Vec<&GuildChannel> = guilds
-            .values()
-            .flat_map(
-                |g| g.guild.channels
-                    .iter()
-                    .map(|c| c.1)
-                    .filter(|c| c.parent_id.is_some())
-                    .filter(|c| g.category_ids.contains(&c.parent_id.unwrap().get()))
-            )
+        // Permanent rooms live in the same categories but are never adopted or deleted here
+        let permanent_ids: HashSet<u64> = repositories.permanent.get_all()
+            .await
+            .map_err(|err| err.to_string())?
+            .iter()
+            .map(|room| room.channel_id as u64)
+            .collect();
+
+        let monitored_ids: HashSet<i64> = repositories.rooms.get_all_channel_ids()
+            .await
+            .map_err(|err| err.to_string())?
+            .into_iter()
             .collect();
 
         let mut autorooms_to_insert: Vec<MonitoredAutoRoom> = Vec::new();
+        // Owners found elsewhere than in their overwrite get the owner privileges back
+        let mut owners_to_grant: Vec<(ChannelId, UserId)> = Vec::new();
         let mut channels_to_delete: Vec<&GuildChannel> = Vec::new();
-        let bot_id = ctx.cache.current_user().id;
-        for channel in channels {
-            let members = channel.members(ctx).map_err(|err| err.to_string())?;
-            if members.len() > 0 {
-                autorooms_to_insert.push(MonitoredAutoRoom {
-                    channel_id: channel.id.get() as i64,
-                    owner_id: (channel.owner_id.unwrap_or(bot_id)).get() as i64
-                });
-                continue;
+        let mut forgotten_ids: Vec<i64> = Vec::new();
+        for (guild_id, guild) in &guilds {
+            let guard = ChannelGuard::load(repositories, *guild_id).await.map_err(|err| err.to_string())?;
+            let existing_ids: HashSet<u64> = guild.channels.iter().map(|c| c.id.get()).collect();
+            forgotten_ids.extend(guard.stale(&existing_ids));
+
+            let channels = guild.channels
+                .iter()
+                .filter(|c| c.parent_id.is_some_and(|parent_id| guild.category_ids.contains(&parent_id.get())))
+                .filter(|c| !permanent_ids.contains(&c.id.get()))
+                .filter(|c| !room_deletion::is_pending(c.id));
+            for channel in channels {
+                if let Err(reason) = guard.check(channel.id) {
+                    report.skip(channel.id, reason);
+                    continue;
+                }
+                if room_creation::is_settling(channel.id) {
+                    report.skip(channel.id, SkipReason::Settling);
+                    continue;
+                }
+                let members = discord
+                    .channel_members(channel.guild_id, channel.id)
+                    .ok_or_else(|| format!("GUILD({}) not found in cache", channel.guild_id.get()))?;
+                if !members.is_empty() {
+                    if monitored_ids.contains(&(channel.id.get() as i64)) {
+                        continue;
+                    }
+                    let (owner_id, source) = recover_owner(discord, &guard, channel, &members);
+                    tracing::info!(
+                        "[Cleanup categories] Adopt CHANNEL({}) OWNER({}) SOURCE({:?})",
+                        channel.id.get(),
+                        owner_id.get(),
+                        source
+                    );
+                    if matches!(source, OwnerSource::Creator | OwnerSource::LongestPresent) {
+                        owners_to_grant.push((channel.id, owner_id));
+                    }
+                    autorooms_to_insert.push(MonitoredAutoRoom {
+                        channel_id: channel.id.get() as i64,
+                        owner_id: owner_id.get() as i64,
+                        autoroom_channel_id: None
+                    });
+                    continue;
+                }
+                channels_to_delete.push(channel);
             }
-            channels_to_delete.push(&channel);
+        }
 
+        if options.dry_run {
+            report.deleted.extend(channels_to_delete.iter().map(|channel| channel.id));
+            report.adopted.extend(autorooms_to_insert.iter().map(|room| ChannelId::new(room.channel_id as u64)));
+            tracing::info!(
+                "[Cleanup categories] Dry run | To delete: {} | To adopt: {} | Skipped: {}",
+                report.deleted.len(),
+                report.adopted.len(),
+                report.skipped.len()
+            );
+            return Ok(report);
         }
 
         tracing::info!("[Cleanup categories] {} rooms to delete", channels_to_delete.len());
         for channel in &channels_to_delete {
-            match channel.delete(ctx).await {
-                Ok(_) => (),
+            match discord.delete_channel(channel.id).await {
+                Ok(_) => {
+                    report.deleted.push(channel.id);
+                    forgotten_ids.push(channel.id.get() as i64);
+                },
                 Err(err) => tracing::error!(
                     "[Cleanup categories] channel({}) delete error:\n`{}`",
                     channel.id.get(),
@@ -381,54 +1068,623 @@ pub async fn cleanup_categories_monitored_rooms(ctx: &Context) -> Result<(), Str
                 )
             };
         }
-        tracing::info!("[Cleanup categories] {} rooms removed", channels_to_delete.len());
+        tracing::info!("[Cleanup categories] {} rooms removed", report.deleted.len());
+
+        if !forgotten_ids.is_empty() {
+            repositories.channels.forget_created(&forgotten_ids).await.map_err(|err| err.to_string())?;
+        }
 
         tracing::info!("[Cleanup categories] {} rooms to create", autorooms_to_insert.len());
-        MonitoredAutoRoom::insert_many(&pool, &autorooms_to_insert).await.map_err(|err| err.to_string())?;
-        tracing::info!("[Cleanup categories] {} rooms created", autorooms_to_insert.len());
+        repositories.rooms.insert_many(&autorooms_to_insert).await.map_err(|err| err.to_string())?;
+        for autoroom in &autorooms_to_insert {
+            let channel_id = ChannelId::new(autoroom.channel_id as u64);
+            channel_cache::set_monitored(channel_id, true);
+            report.adopted.push(channel_id);
+        }
+        for (channel_id, owner_id) in &owners_to_grant {
+            // Errors are logged inside, the room is adopted either way
+            let _ = grant_owner_privileges(discord, channel_id, owner_id).await;
+        }
+        tracing::info!(
+            "[Cleanup categories] {} rooms created | {} channels skipped",
+            autorooms_to_insert.len(),
+            report.skipped.len()
+        );
     }
     
     tracing::info!("[Cleanup categories] monitored rooms have been completed");
-    Ok(())
+    Ok(report)
 }
 
 
 pub mod invite_modal {
-    use serenity::all::{ButtonStyle, ChannelId, Context, CreateActionRow, CreateButton, CreateMessage, CreateSelectMenu, CreateSelectMenuKind, UserId};
+    use serenity::all::{ButtonStyle, ChannelId, CreateActionRow, CreateButton, CreateMessage, UserId};
 
-    pub async fn deploy_encoded_menu(
-        ctx: &Context, 
-        channel_id: ChannelId, 
-        creator_id: UserId
-    ) -> Result<(), serenity::Error> {
-        
-        let select_id = format!("inv_sel_{}_{}", creator_id, channel_id);
-        let invite_id = format!("inv_inv_{}_{}", creator_id, channel_id);
-        let kick_id = format!("inv_kick_{}_{}", creator_id, channel_id);
-
-        let components = vec![
-            CreateActionRow::SelectMenu(
-                CreateSelectMenu::new(select_id, CreateSelectMenuKind::User { default_users: None })
-                    .placeholder("Choose a member")
-            ),
+    use crate::services::custom_id::{CustomId, PanelAction};
+    use crate::services::gateway::DiscordGateway;
+
+    pub fn claim_components(previous_owner_id: UserId, channel_id: ChannelId) -> Vec<CreateActionRow> {
+        let claim_id = CustomId::new(PanelAction::Claim, previous_owner_id, channel_id).encode();
+
+        vec![
             CreateActionRow::Buttons(vec![
-                CreateButton::new(invite_id)
-                    .label("Invite")
-                    .style(ButtonStyle::Success),
-                CreateButton::new(kick_id)
-                    .label("Kick")
-                    .style(ButtonStyle::Danger),
+                CreateButton::new(claim_id)
+                    .label("Claim")
+                    .style(ButtonStyle::Primary),
             ]),
-        ];
+        ]
+    }
 
-        tracing::info!("Sending invite_modal to Channel ({:?})", channel_id);
+    pub async fn deploy_claim_button(
+        discord: &dyn DiscordGateway,
+        channel_id: ChannelId,
+        previous_owner_id: UserId,
+    ) -> Result<(), serenity::Error> {
+        tracing::info!("Sending claim button to Channel ({:?})", channel_id);
 
-        channel_id.send_message(&ctx.http, 
+        discord.send_message(channel_id,
             CreateMessage::new()
-                .content("🛠 Channel Menu 🛠")
-                .components(components)
+                .content("The host has left the room. Press the button to become the new host")
+                .components(claim_components(previous_owner_id, channel_id))
         ).await?;
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serenity::all::{ChannelId, GuildId, PermissionOverwrite, PermissionOverwriteType, Permissions, RoleId, UserId};
+
+    use super::{backfill_ledger, cleanup_categories_monitored_rooms};
+    use crate::services::channel_guard::ChannelGuard;
+    use super::voice_channel::{
+        get_owned_channel_id, invite_users, kick_user, kick_users, rename_room, set_room_bitrate, set_room_hidden, set_room_locked,
+        set_room_user_limit, BotError
+    };
+    use crate::services::channel_guard::SkipReason;
+    use crate::services::cleanup::CleanupOptions;
+    use crate::services::fake_discord::{autoroom, next_id, user, FakeCall, FakeDiscord};
+    use crate::services::gateway::DiscordGateway;
+    use crate::services::rename_queue::RenameSlot;
+    use crate::sql::autoroom::{MonitoredAutoRoom, PermamentAutoRoom};
+    use crate::sql::memory::{InMemoryGuildSettingsRepository, InMemoryPermanentRoomRepository};
+    use crate::sql::prelude::Repositories;
+
+
+    struct Room {
+        discord: FakeDiscord,
+        repositories: Repositories,
+        guild_id: GuildId,
+        room_id: ChannelId,
+        owner_id: UserId,
+        guest_id: UserId,
+    }
+
+    async fn room() -> Room {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let room_id = discord.add_voice_channel(guild_id, Some(category_id), "alice`s room");
+        let owner_id = UserId::new(next_id());
+        let guest_id = UserId::new(next_id());
+        repositories.rooms.insert_many(&[MonitoredAutoRoom {
+            channel_id: room_id.get() as i64,
+            owner_id: owner_id.get() as i64,
+            autoroom_channel_id: None,
+        }]).await.unwrap();
+        discord.connect(guild_id, owner_id, room_id);
+        discord.connect(guild_id, guest_id, room_id);
+        Room { discord, repositories, guild_id, room_id, owner_id, guest_id }
+    }
+
+    impl Room {
+        async fn kick(&self, author_id: UserId) -> Result<(), BotError> {
+            let guest = user(self.guest_id, "bob");
+            kick_user(&self.discord, &self.repositories, self.guild_id, author_id.get() as i64, &guest).await
+        }
+
+        /// Another room of the owner in the guild, the owner stays in the first one.
+        async fn second_room(&self) -> ChannelId {
+            let channel_id = self.discord.add_voice_channel(self.guild_id, None, "alice`s other room");
+            self.repositories.rooms.insert_many(&[MonitoredAutoRoom {
+                channel_id: channel_id.get() as i64,
+                owner_id: self.owner_id.get() as i64,
+                autoroom_channel_id: None,
+            }]).await.unwrap();
+            channel_id
+        }
+
+        fn everyone_deny(&self, channel_id: ChannelId) -> Permissions {
+            self.discord.get(channel_id).unwrap().permission_overwrites
+                .iter()
+                .find(|overwrite| overwrite.kind == PermissionOverwriteType::Role(RoleId::new(self.guild_id.get())))
+                .map_or(Permissions::empty(), |overwrite| overwrite.deny)
+        }
+    }
+
+    #[tokio::test]
+    async fn kick_from_public_room_denies_connect() {
+        let room = room().await;
+        room.kick(room.owner_id).await.unwrap();
+
+        let overwrite = room.discord.member_overwrite(room.room_id, room.guest_id).unwrap();
+        assert_eq!(overwrite.deny, Permissions::CONNECT);
+        assert_eq!(room.discord.voice_channel_id(room.guild_id, room.guest_id), None);
+        assert_eq!(room.discord.voice_channel_id(room.guild_id, room.owner_id), Some(room.room_id));
+    }
+
+    #[tokio::test]
+    async fn kick_from_locked_room_drops_guest_overwrite() {
+        let room = room().await;
+        room.discord.set_overwrites(room.room_id, vec![
+            PermissionOverwrite {
+                allow: Permissions::empty(),
+                deny: Permissions::CONNECT,
+                kind: PermissionOverwriteType::Role(RoleId::new(room.guild_id.get())),
+            },
+            PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(room.guest_id),
+            },
+        ]);
+        room.kick(room.owner_id).await.unwrap();
+
+        assert!(room.discord.member_overwrite(room.room_id, room.guest_id).is_none());
+        assert_eq!(room.discord.voice_channel_id(room.guild_id, room.guest_id), None);
+    }
+
+    #[tokio::test]
+    async fn kick_without_room_is_rejected() {
+        let room = room().await;
+        let result = room.kick(UserId::new(next_id())).await;
+
+        assert!(matches!(result, Err(BotError::MonitoredAutoRoomNotFound)));
+        assert_eq!(room.discord.voice_channel_id(room.guild_id, room.guest_id), Some(room.room_id));
+    }
+
+    #[tokio::test]
+    async fn kick_users_handles_every_picked_member() {
+        let room = room().await;
+        let outside_id = UserId::new(next_id());
+        let picked = [room.guest_id, outside_id, room.owner_id];
+        let report = kick_users(&room.discord, room.room_id, room.owner_id.get() as i64, &picked).await.unwrap();
+
+        assert_eq!(report.done, vec![room.guest_id, outside_id]);
+        assert_eq!(report.skipped, vec![room.owner_id]);
+        assert!(report.failed.is_empty());
+        assert_eq!(room.discord.voice_channel_id(room.guild_id, room.guest_id), None);
+        assert_eq!(room.discord.member_overwrite(room.room_id, outside_id).unwrap().deny, Permissions::CONNECT);
+        assert_eq!(room.discord.voice_channel_id(room.guild_id, room.owner_id), Some(room.room_id));
+    }
+
+    #[tokio::test]
+    async fn owned_room_is_resolved_per_guild_and_voice_channel() {
+        let room = room().await;
+        let author_id = room.owner_id.get() as i64;
+        // The owner hosts a newer room on another server and an older one on this server
+        let other_guild_id = room.discord.add_guild();
+        let other_room_id = room.discord.add_voice_channel(other_guild_id, None, "elsewhere");
+        let older_room_id = room.discord.add_voice_channel(room.guild_id, None, "older room");
+        for channel_id in [other_room_id, older_room_id] {
+            room.repositories.rooms.insert_many(&[MonitoredAutoRoom {
+                channel_id: channel_id.get() as i64,
+                owner_id: author_id,
+                autoroom_channel_id: None,
+            }]).await.unwrap();
+        }
+
+        assert_eq!(get_owned_channel_id(&room.discord, &room.repositories, room.guild_id, author_id).await.unwrap(), Some(room.room_id));
+        assert_eq!(get_owned_channel_id(&room.discord, &room.repositories, other_guild_id, author_id).await.unwrap(), Some(other_room_id));
+
+        room.discord.connect(room.guild_id, room.owner_id, older_room_id);
+        assert_eq!(get_owned_channel_id(&room.discord, &room.repositories, room.guild_id, author_id).await.unwrap(), Some(older_room_id));
+
+        // Sitting in a room of somebody else falls back to the own room of the guild
+        room.repositories.rooms.set_owner(older_room_id.get() as i64, room.guest_id.get() as i64).await.unwrap();
+        assert_eq!(get_owned_channel_id(&room.discord, &room.repositories, room.guild_id, author_id).await.unwrap(), Some(room.room_id));
+    }
+
+    #[tokio::test]
+    async fn panel_actions_stay_in_their_room() {
+        let room = room().await;
+        let author_id = room.owner_id.get() as i64;
+        let other_id = room.second_room().await;
+        let friend_id = UserId::new(next_id());
+        // Guessing by the owner would pick the room they sit in
+        assert_eq!(get_owned_channel_id(&room.discord, &room.repositories, room.guild_id, author_id).await.unwrap(), Some(room.room_id));
+
+        invite_users(&room.discord, other_id, author_id, &[friend_id]).await.unwrap();
+        kick_users(&room.discord, other_id, author_id, &[room.guest_id]).await.unwrap();
+        set_room_locked(&room.discord, other_id, author_id, true).await.unwrap();
+        set_room_hidden(&room.discord, other_id, author_id, true).await.unwrap();
+
+        assert_eq!(room.everyone_deny(other_id), Permissions::CONNECT | Permissions::VIEW_CHANNEL);
+        assert!(room.discord.member_overwrite(other_id, room.discord.current_user_id()).is_some());
+        assert!(room.discord.member_overwrite(other_id, friend_id).is_some());
+        assert_eq!(room.discord.member_overwrite(other_id, room.guest_id).unwrap().deny, Permissions::CONNECT);
+
+        assert_eq!(room.everyone_deny(room.room_id), Permissions::empty());
+        assert!(room.discord.member_overwrite(room.room_id, friend_id).is_none());
+        assert!(room.discord.member_overwrite(room.room_id, room.guest_id).is_none());
+        // The guest sits in the other room and stays there
+        assert_eq!(room.discord.voice_channel_id(room.guild_id, room.guest_id), Some(room.room_id));
+    }
+
+    #[tokio::test]
+    async fn invite_users_reports_failures_per_member() {
+        let room = room().await;
+        let first_id = UserId::new(next_id());
+        let second_id = UserId::new(next_id());
+        let report = invite_users(&room.discord, room.room_id, room.owner_id.get() as i64, &[first_id, second_id]).await.unwrap();
+
+        assert_eq!(report.done, vec![first_id, second_id]);
+        assert!(room.discord.member_overwrite(room.room_id, second_id).unwrap().allow.contains(Permissions::VIEW_CHANNEL));
+
+        room.discord.fail(FakeCall::CreatePermission);
+        let report = invite_users(&room.discord, room.room_id, room.owner_id.get() as i64, &[room.guest_id]).await.unwrap();
+        assert_eq!(report.failed, vec![room.guest_id]);
+        assert!(report.to_message("Invited").starts_with("⚠️ Failed"));
+    }
+
+    #[tokio::test]
+    async fn rename_past_rate_limit_is_queued() {
+        let room = room().await;
+        let author_id = room.owner_id.get() as i64;
+        assert_eq!(rename_room(&room.discord, room.room_id, author_id, "first").await.unwrap(), RenameSlot::Now);
+        assert_eq!(rename_room(&room.discord, room.room_id, author_id, " second ").await.unwrap(), RenameSlot::Now);
+
+        let slot = rename_room(&room.discord, room.room_id, author_id, "third").await.unwrap();
+        assert!(matches!(slot, RenameSlot::Queued(_)));
+        assert_eq!(room.discord.channel(room.room_id).await.unwrap().unwrap().name, "second");
+    }
+
+    #[tokio::test]
+    async fn room_settings_stay_in_their_room() {
+        let room = room().await;
+        let author_id = room.owner_id.get() as i64;
+        let other_id = room.second_room().await;
+
+        rename_room(&room.discord, other_id, author_id, "renamed").await.unwrap();
+        set_room_user_limit(&room.discord, other_id, author_id, 4).await.unwrap();
+        set_room_bitrate(&room.discord, other_id, author_id, 64).await.unwrap();
+
+        let other = room.discord.get(other_id).unwrap();
+        assert_eq!((other.name.as_str(), other.user_limit, other.bitrate), ("renamed", Some(4), Some(64000)));
+        let first = room.discord.get(room.room_id).unwrap();
+        assert_eq!((first.name.as_str(), first.user_limit, first.bitrate), ("alice`s room", None, None));
+    }
+
+    #[tokio::test]
+    async fn bitrate_is_checked_and_capped() {
+        let room = room().await;
+        let author_id = room.owner_id.get() as i64;
+        for kbps in [0, 7, 385, u32::MAX] {
+            let result = set_room_bitrate(&room.discord, room.room_id, author_id, kbps).await;
+            assert!(matches!(result, Err(BotError::Rejected(_))));
+        }
+
+        // An unboosted guild allows 96 kbps at most
+        let applied = set_room_bitrate(&room.discord, room.room_id, author_id, 384).await.unwrap();
+        assert_eq!(applied, 96000);
+        assert_eq!(room.discord.get(room.room_id).unwrap().bitrate, Some(96000));
+    }
+
+    #[tokio::test]
+    async fn cleanup_categories_adopts_and_deletes_rooms() {
+        let discord = FakeDiscord::new();
+        let permanent = Arc::new(InMemoryPermanentRoomRepository::default());
+        let repositories = Repositories::in_memory_with(permanent.clone(), Arc::new(InMemoryGuildSettingsRepository::default()));
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let trigger_id = discord.add_voice_channel(guild_id, None, "Create room");
+        repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+
+        let empty_id = discord.add_voice_channel(guild_id, Some(category_id), "empty");
+        let occupied_id = discord.add_voice_channel(guild_id, Some(category_id), "occupied");
+        let permanent_id = discord.add_voice_channel(guild_id, Some(category_id), "permanent");
+        for channel_id in [empty_id, occupied_id] {
+            repositories.channels.record_created(guild_id.get() as i64, channel_id.get() as i64, None).await.unwrap();
+        }
+        let member_id = UserId::new(next_id());
+        discord.connect(guild_id, member_id, occupied_id);
+        permanent.insert(PermamentAutoRoom {
+            channel_id: permanent_id.get() as i64,
+            guild_id: guild_id.get() as i64,
+            owner_id: next_id() as i64,
+            placement_category_id: category_id.get() as i64,
+            storage_category_id: next_id() as i64,
+        });
+
+        // The category of this autoroom was deleted while the bot was offline
+        let gone_trigger_id = discord.add_voice_channel(guild_id, None, "Create gone room");
+        let gone_category_id = ChannelId::new(next_id());
+        repositories.autorooms.create(&autoroom(guild_id, gone_trigger_id, gone_category_id)).await.unwrap();
+        repositories.autorooms.push_overflow_category(trigger_id.get() as i64, gone_category_id.get() as i64, true).await.unwrap();
+
+        let report = cleanup_categories_monitored_rooms(&discord, &repositories, &CleanupOptions::default()).await.unwrap();
+
+        assert_eq!(report.deleted, vec![empty_id]);
+        assert_eq!(report.adopted, vec![occupied_id]);
+        assert!(discord.get(empty_id).is_none());
+        assert!(discord.get(permanent_id).is_some());
+        assert!(discord.get(trigger_id).is_some());
+        let adopted = repositories.rooms.get_by_channel_id(occupied_id.get() as i64).await.unwrap().unwrap();
+        assert_eq!(adopted.owner_id, member_id.get() as i64);
+        assert!(discord.member_overwrite(occupied_id, member_id).unwrap().allow.contains(Permissions::MANAGE_CHANNELS));
+        assert!(repositories.rooms.get_by_channel_id(permanent_id.get() as i64).await.unwrap().is_none());
+
+        assert!(repositories.autorooms.get_by_channel_id(gone_trigger_id.get() as i64).await.unwrap().is_none());
+        assert!(repositories.autorooms.get_by_channel_id(trigger_id.get() as i64).await.unwrap().is_some());
+        assert!(repositories.autorooms.get_overflow_categories(trigger_id.get() as i64).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cleanup_categories_skips_channels_outside_the_ledger() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        // The trigger sits in its own placement category
+        let trigger_id = discord.add_voice_channel(guild_id, Some(category_id), "Create room");
+        repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+
+        let manual_id = discord.add_voice_channel(guild_id, Some(category_id), "made by an admin");
+        let occupied_manual_id = discord.add_voice_channel(guild_id, Some(category_id), "admin lounge");
+        discord.connect(guild_id, UserId::new(next_id()), occupied_manual_id);
+        let protected_id = discord.add_voice_channel(guild_id, Some(category_id), "protected");
+        let gone_id = ChannelId::new(next_id());
+        for channel_id in [trigger_id, protected_id, gone_id] {
+            repositories.channels.record_created(guild_id.get() as i64, channel_id.get() as i64, None).await.unwrap();
+        }
+        repositories.channels.protect(guild_id.get() as i64, protected_id.get() as i64).await.unwrap();
+
+        let report = cleanup_categories_monitored_rooms(&discord, &repositories, &CleanupOptions::default()).await.unwrap();
+
+        assert!(report.deleted.is_empty());
+        assert!(report.adopted.is_empty());
+        let mut skipped = report.skipped.clone();
+        skipped.sort_by_key(|(channel_id, _)| *channel_id);
+        assert_eq!(skipped, vec![
+            (trigger_id, SkipReason::Trigger),
+            (manual_id, SkipReason::NotCreatedByBot),
+            (occupied_manual_id, SkipReason::NotCreatedByBot),
+            (protected_id, SkipReason::Protected),
+        ]);
+        for channel_id in [trigger_id, manual_id, occupied_manual_id, protected_id] {
+            assert!(discord.get(channel_id).is_some());
+        }
+        assert!(repositories.rooms.get_by_channel_id(occupied_manual_id.get() as i64).await.unwrap().is_none());
+
+        // Ledger entries of channels deleted meanwhile are dropped
+        let mut created = repositories.channels.get_created(guild_id.get() as i64).await.unwrap()
+            .iter()
+            .map(|channel| channel.channel_id)
+            .collect::<Vec<i64>>();
+        created.sort();
+        assert_eq!(created, vec![trigger_id.get() as i64, protected_id.get() as i64]);
+    }
+
+    #[tokio::test]
+    async fn ledger_backfill_skips_adopted_channels() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let trigger_id = discord.add_voice_channel(guild_id, Some(category_id), "Create room");
+        repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+
+        let owner_id = next_id() as i64;
+        let bot_id = discord.current_user_id().get() as i64;
+        let row = |channel_id: ChannelId, owner_id: i64, autoroom_channel_id: Option<ChannelId>| (guild_id, MonitoredAutoRoom {
+            channel_id: channel_id.get() as i64,
+            owner_id,
+            autoroom_channel_id: autoroom_channel_id.map(|id| id.get() as i64),
+        });
+        let room_id = discord.add_voice_channel(guild_id, Some(category_id), "alice`s room");
+        let bot_room_id = discord.add_voice_channel(guild_id, Some(category_id), "bot`s room");
+        // The old cleanup adopted every occupied channel of the category, the trigger too
+        let admin_id = discord.add_voice_channel(guild_id, Some(category_id), "admin lounge");
+        let existing = [
+            row(room_id, owner_id, Some(trigger_id)),
+            row(bot_room_id, bot_id, Some(trigger_id)),
+            row(admin_id, bot_id, None),
+            row(trigger_id, bot_id, None),
+        ];
+
+        backfill_ledger(&discord, &repositories, &existing).await.unwrap();
+
+        let guard = ChannelGuard::load(&repositories, guild_id).await.unwrap();
+        assert_eq!(guard.check(room_id), Ok(()));
+        assert_eq!(guard.creator(room_id), Some(UserId::new(owner_id as u64)));
+        assert_eq!(guard.check(bot_room_id), Ok(()));
+        assert_eq!(guard.creator(bot_room_id), None);
+        assert_eq!(guard.check(admin_id), Err(SkipReason::NotCreatedByBot));
+        assert_eq!(guard.check(trigger_id), Err(SkipReason::Trigger));
+        assert_eq!(repositories.channels.get_created(guild_id.get() as i64).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cleanup_categories_keeps_autorooms_when_discord_fails() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let trigger_id = discord.add_voice_channel(guild_id, None, "Create room");
+        repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+        repositories.autorooms.push_overflow_category(trigger_id.get() as i64, next_id() as i64, true).await.unwrap();
+        let empty_id = discord.add_voice_channel(guild_id, Some(category_id), "empty");
+        repositories.channels.record_created(guild_id.get() as i64, empty_id.get() as i64, None).await.unwrap();
+
+        // A timeout or an outage says nothing about the category
+        discord.fail(FakeCall::GetChannel);
+        let report = cleanup_categories_monitored_rooms(&discord, &repositories, &CleanupOptions::default()).await.unwrap();
+
+        assert!(report.outdated_categories.is_empty());
+        assert!(report.deleted.is_empty());
+        assert!(discord.get(empty_id).is_some());
+        assert!(repositories.autorooms.get_by_channel_id(trigger_id.get() as i64).await.unwrap().is_some());
+        assert_eq!(repositories.autorooms.get_overflow_categories(trigger_id.get() as i64).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cleanup_categories_dry_run_changes_nothing() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let trigger_id = discord.add_voice_channel(guild_id, None, "Create room");
+        repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+        let gone_trigger_id = discord.add_voice_channel(guild_id, None, "Create gone room");
+        let gone_category_id = ChannelId::new(next_id());
+        repositories.autorooms.create(&autoroom(guild_id, gone_trigger_id, gone_category_id)).await.unwrap();
+
+        let empty_id = discord.add_voice_channel(guild_id, Some(category_id), "empty");
+        let occupied_id = discord.add_voice_channel(guild_id, Some(category_id), "occupied");
+        for channel_id in [empty_id, occupied_id] {
+            repositories.channels.record_created(guild_id.get() as i64, channel_id.get() as i64, None).await.unwrap();
+        }
+        discord.connect(guild_id, UserId::new(next_id()), occupied_id);
+
+        let options = CleanupOptions { dry_run: true, guild_id: None };
+        let report = cleanup_categories_monitored_rooms(&discord, &repositories, &options).await.unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.deleted, vec![empty_id]);
+        assert_eq!(report.adopted, vec![occupied_id]);
+        assert_eq!(report.outdated_categories, vec![gone_category_id.get() as i64]);
+        assert!(discord.get(empty_id).is_some());
+        assert!(repositories.rooms.get_by_channel_id(occupied_id.get() as i64).await.unwrap().is_none());
+        assert!(repositories.autorooms.get_by_channel_id(gone_trigger_id.get() as i64).await.unwrap().is_some());
+        assert_eq!(repositories.channels.get_created(guild_id.get() as i64).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cleanup_categories_stays_in_scope() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let mut rooms = Vec::new();
+        for _ in 0..2 {
+            let guild_id = discord.add_guild();
+            let category_id = discord.add_category(guild_id, "Rooms");
+            let trigger_id = discord.add_voice_channel(guild_id, None, "Create room");
+            repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+            let room_id = discord.add_voice_channel(guild_id, Some(category_id), "empty");
+            repositories.channels.record_created(guild_id.get() as i64, room_id.get() as i64, None).await.unwrap();
+            rooms.push((guild_id, room_id));
+        }
+
+        let options = CleanupOptions { dry_run: false, guild_id: Some(rooms[0].0) };
+        let report = cleanup_categories_monitored_rooms(&discord, &repositories, &options).await.unwrap();
+
+        assert_eq!(report.deleted, vec![rooms[0].1]);
+        assert!(discord.get(rooms[0].1).is_none());
+        assert!(discord.get(rooms[1].1).is_some());
+    }
+
+    struct Adoption {
+        discord: FakeDiscord,
+        repositories: Repositories,
+        guild_id: GuildId,
+        room_id: ChannelId,
+    }
+
+    /// Occupied room created by the bot in an autoroom category, with no monitored row.
+    async fn adoption(creator_id: Option<UserId>) -> Adoption {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let trigger_id = discord.add_voice_channel(guild_id, None, "Create room");
+        repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+        let room_id = discord.add_voice_channel(guild_id, Some(category_id), "lost room");
+        repositories.channels
+            .record_created(guild_id.get() as i64, room_id.get() as i64, creator_id.map(|id| id.get() as i64))
+            .await
+            .unwrap();
+        Adoption { discord, repositories, guild_id, room_id }
+    }
+
+    impl Adoption {
+        async fn adopted_owner(&self) -> UserId {
+            cleanup_categories_monitored_rooms(&self.discord, &self.repositories, &CleanupOptions::default()).await.unwrap();
+            let room = self.repositories.rooms.get_by_channel_id(self.room_id.get() as i64).await.unwrap().unwrap();
+            UserId::new(room.owner_id as u64)
+        }
+    }
+
+    #[tokio::test]
+    async fn adoption_keeps_owner_from_overwrite() {
+        let creator_id = UserId::new(next_id());
+        let adoption = adoption(Some(creator_id)).await;
+        let owner_id = UserId::new(next_id());
+        let guest_id = UserId::new(next_id());
+        adoption.discord.set_overwrites(adoption.room_id, vec![
+            PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(guest_id),
+            },
+            PermissionOverwrite {
+                allow: Permissions::CONNECT | Permissions::MANAGE_CHANNELS,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(owner_id),
+            },
+        ]);
+        adoption.discord.connect(adoption.guild_id, guest_id, adoption.room_id);
+
+        assert_eq!(adoption.adopted_owner().await, owner_id);
+        assert!(adoption.discord.member_overwrite(adoption.room_id, creator_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn adoption_falls_back_to_creator() {
+        let creator_id = UserId::new(next_id());
+        let adoption = adoption(Some(creator_id)).await;
+        adoption.discord.connect(adoption.guild_id, UserId::new(next_id()), adoption.room_id);
+
+        assert_eq!(adoption.adopted_owner().await, creator_id);
+        let overwrite = adoption.discord.member_overwrite(adoption.room_id, creator_id).unwrap();
+        assert!(overwrite.allow.contains(Permissions::MANAGE_CHANNELS));
+    }
+
+    #[tokio::test]
+    async fn adoption_ignores_bot_creator() {
+        let adoption = adoption(None).await;
+        // Migration 0013 copied the bot owner of legacy rooms into the ledger as their creator
+        let room_id = adoption.room_id.get() as i64;
+        let bot_id = adoption.discord.current_user_id().get() as i64;
+        adoption.repositories.channels.forget_created(&[room_id]).await.unwrap();
+        adoption.repositories.channels.record_created(adoption.guild_id.get() as i64, room_id, Some(bot_id)).await.unwrap();
+        let member_id = UserId::new(next_id());
+        adoption.discord.connect(adoption.guild_id, member_id, adoption.room_id);
+
+        assert_eq!(adoption.adopted_owner().await, member_id);
+    }
+
+    #[tokio::test]
+    async fn adoption_skips_bots_for_longest_present() {
+        let adoption = adoption(None).await;
+        let music_bot_id = UserId::new(next_id());
+        let member_id = UserId::new(next_id());
+        adoption.discord.add_bot(music_bot_id);
+        adoption.discord.connect(adoption.guild_id, music_bot_id, adoption.room_id);
+        adoption.discord.connect(adoption.guild_id, member_id, adoption.room_id);
+
+        assert_eq!(adoption.adopted_owner().await, member_id);
+    }
+
+    #[tokio::test]
+    async fn adoption_of_bots_only_room_keeps_bot_owner() {
+        let adoption = adoption(None).await;
+        let music_bot_id = UserId::new(next_id());
+        adoption.discord.add_bot(music_bot_id);
+        adoption.discord.connect(adoption.guild_id, music_bot_id, adoption.room_id);
+
+        assert_eq!(adoption.adopted_owner().await, adoption.discord.current_user_id());
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serenity::all::ChannelId;
+
+use crate::sql::{prelude::Repositories, repository::{AutoRoomRepository, MonitoredRoomRepository, PermanentRoomRepository}};
+
+
+/// Write-through cache of "is this channel a trigger / a monitored room / a permanent room".
+/// Both answers are stored, so unrelated channels only reach the database once.
+struct ChannelCache {
+    channels: RwLock<HashMap<u64, bool>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ChannelCache {
+    fn new() -> Self {
+        Self {
+            channels: RwLock::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self, channel_id: ChannelId) -> Option<bool> {
+        let cached = self.channels.read().get(&channel_id.get()).copied();
+        match cached {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        cached
+    }
+
+    fn set(&self, channel_id: ChannelId, value: bool) {
+        self.channels.write().insert(channel_id.get(), value);
+    }
+
+    fn load(&self, ids: Vec<i64>) {
+        let mut channels = self.channels.write();
+        channels.clear();
+        channels.extend(ids.into_iter().map(|id| (id as u64, true)));
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.channels.read().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    pub fn to_display_string(&self) -> String {
+        format!("entries: {} | hits: {} | misses: {}", self.entries, self.hits, self.misses)
+    }
+}
+
+static TRIGGERS: Lazy<ChannelCache> = Lazy::new(ChannelCache::new);
+static ROOMS: Lazy<ChannelCache> = Lazy::new(ChannelCache::new);
+static PERMANENT: Lazy<ChannelCache> = Lazy::new(ChannelCache::new);
+
+
+/// Fills the cache with every trigger, monitored and permanent room, drops everything cached before.
+pub async fn load(repositories: &Repositories) -> Result<(), sqlx::Error> {
+    let triggers = repositories.autorooms.get_all_channel_ids().await?;
+    let rooms = repositories.rooms.get_all_channel_ids().await?;
+    let permanent: Vec<i64> = repositories.permanent.get_all().await?.iter().map(|room| room.channel_id).collect();
+    tracing::info!(
+        "Channel cache loaded | Triggers: {} | Rooms: {} | Permanent: {}",
+        triggers.len(),
+        rooms.len(),
+        permanent.len()
+    );
+    TRIGGERS.load(triggers);
+    ROOMS.load(rooms);
+    PERMANENT.load(permanent);
+    Ok(())
+}
+
+pub async fn is_trigger(autorooms: &dyn AutoRoomRepository, channel_id: ChannelId) -> Result<bool, sqlx::Error> {
+    if let Some(is_trigger) = TRIGGERS.get(channel_id) {
+        return Ok(is_trigger);
+    }
+    let is_trigger = autorooms.get_by_channel_id(channel_id.get() as i64).await?.is_some();
+    TRIGGERS.set(channel_id, is_trigger);
+    Ok(is_trigger)
+}
+
+pub async fn is_monitored(rooms: &dyn MonitoredRoomRepository, channel_id: ChannelId) -> Result<bool, sqlx::Error> {
+    if let Some(is_monitored) = ROOMS.get(channel_id) {
+        return Ok(is_monitored);
+    }
+    let is_monitored = rooms.get_by_channel_id(channel_id.get() as i64).await?.is_some();
+    ROOMS.set(channel_id, is_monitored);
+    Ok(is_monitored)
+}
+
+pub async fn is_permanent(permanent: &dyn PermanentRoomRepository, channel_id: ChannelId) -> Result<bool, sqlx::Error> {
+    if let Some(is_permanent) = PERMANENT.get(channel_id) {
+        return Ok(is_permanent);
+    }
+    let is_permanent = permanent.get_by_channel_id(channel_id.get() as i64).await?.is_some();
+    PERMANENT.set(channel_id, is_permanent);
+    Ok(is_permanent)
+}
+
+pub fn set_trigger(channel_id: ChannelId, is_trigger: bool) {
+    TRIGGERS.set(channel_id, is_trigger);
+}
+
+pub fn set_monitored(channel_id: ChannelId, is_monitored: bool) {
+    ROOMS.set(channel_id, is_monitored);
+}
+
+pub fn set_permanent(channel_id: ChannelId, is_permanent: bool) {
+    PERMANENT.set(channel_id, is_permanent);
+}
+
+/// Drops every cached trigger, used after bulk deletes that don't know the removed channels.
+pub fn forget_triggers() {
+    TRIGGERS.channels.write().clear();
+}
+
+pub fn trigger_stats() -> CacheStats {
+    TRIGGERS.stats()
+}
+
+pub fn room_stats() -> CacheStats {
+    ROOMS.stats()
+}
+
+pub fn permanent_stats() -> CacheStats {
+    PERMANENT.stats()
+}
@@ -0,0 +1,95 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use serenity::all::{ChannelId, GuildId, UserId};
+
+use crate::sql::prelude::Repositories;
+
+
+/// Why a cleanup left a channel alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Trigger,
+    Protected,
+    NotCreatedByBot,
+    Settling,
+}
+
+impl SkipReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Trigger => "autoroom trigger",
+            Self::Protected => "protected",
+            Self::NotCreatedByBot => "not created by the bot",
+            Self::Settling => "being set up",
+        }
+    }
+}
+
+/// Channels of one guild a cleanup may delete or adopt: created by the bot, not protected and not a trigger.
+pub struct ChannelGuard {
+    /// Created channels with the member they were created for
+    created: HashMap<u64, Option<UserId>>,
+    protected: HashSet<u64>,
+    triggers: HashSet<u64>,
+}
+
+impl ChannelGuard {
+    pub async fn load(repositories: &Repositories, guild_id: GuildId) -> Result<Self, sqlx::Error> {
+        let guild_id = guild_id.get() as i64;
+        let created = repositories.channels.get_created(guild_id).await?;
+        let protected = repositories.channels.get_protected(guild_id).await?;
+        let triggers = repositories.autorooms.get_guild_autorooms(guild_id).await?;
+        Ok(Self {
+            created: created
+                .into_iter()
+                .map(|channel| (channel.channel_id as u64, channel.creator_id.map(|id| UserId::new(id as u64))))
+                .collect(),
+            protected: protected.into_iter().map(|channel| channel.channel_id as u64).collect(),
+            triggers: triggers.into_iter().map(|autoroom| autoroom.channel_id as u64).collect(),
+        })
+    }
+
+    /// `Err` with the reason when the channel must be left alone.
+    pub fn check(&self, channel_id: ChannelId) -> Result<(), SkipReason> {
+        let id = channel_id.get();
+        if self.triggers.contains(&id) {
+            return Err(SkipReason::Trigger);
+        }
+        if self.protected.contains(&id) {
+            return Err(SkipReason::Protected);
+        }
+        if !self.created.contains_key(&id) {
+            return Err(SkipReason::NotCreatedByBot);
+        }
+        Ok(())
+    }
+
+    /// Member the bot created the channel for.
+    pub fn creator(&self, channel_id: ChannelId) -> Option<UserId> {
+        self.created.get(&channel_id.get()).copied().flatten()
+    }
+
+    /// Ledger entries of channels missing from `existing`, deleted while the bot was not looking.
+    pub fn stale<'a>(&'a self, existing: &'a HashSet<u64>) -> impl Iterator<Item = i64> + 'a {
+        self.created
+            .keys()
+            .filter(|id| !existing.contains(id))
+            .map(|id| *id as i64)
+    }
+}
+
+/// Guards of every guild a cleanup touched, loaded on first use.
+#[derive(Default)]
+pub struct ChannelGuards {
+    guards: HashMap<GuildId, ChannelGuard>,
+}
+
+impl ChannelGuards {
+    pub async fn get(&mut self, repositories: &Repositories, guild_id: GuildId) -> Result<&ChannelGuard, sqlx::Error> {
+        let guard = match self.guards.entry(guild_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(ChannelGuard::load(repositories, guild_id).await?),
+        };
+        Ok(guard)
+    }
+}
@@ -0,0 +1,117 @@
+use serenity::all::{ChannelId, Colour, CreateEmbed, GuildId};
+
+use crate::services::channel_guard::SkipReason;
+
+
+/// Guilds covered by a cleanup started from a command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum CleanupScope {
+    #[default]
+    #[name = "All guilds"]
+    All,
+    #[name = "This guild"]
+    Guild,
+}
+
+/// How a cleanup runs. A dry run only fills the report, nothing is deleted, adopted or removed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CleanupOptions {
+    pub dry_run: bool,
+    /// Only this guild is cleaned up, rows that can't be tied to a guild are left for a full run
+    pub guild_id: Option<GuildId>,
+}
+
+impl CleanupOptions {
+    pub fn includes(&self, guild_id: GuildId) -> bool {
+        self.guild_id.is_none_or(|scope| scope == guild_id)
+    }
+}
+
+/// What a cleanup did, or would do in a dry run.
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    pub dry_run: bool,
+    /// Rows of channels that are gone and of interrupted room creations
+    pub stale_rows: Vec<i64>,
+    /// Rows whose channel came back with another id
+    pub mismatched_ids: Vec<i64>,
+    /// Autoroom categories that no longer exist
+    pub outdated_categories: Vec<i64>,
+    pub deleted: Vec<ChannelId>,
+    pub adopted: Vec<ChannelId>,
+    pub skipped: Vec<(ChannelId, SkipReason)>,
+}
+
+/// Embed field values are limited to 1024 characters, longer lists are cut and counted.
+const FIELD_ENTRIES: usize = 20;
+
+fn field_value<T>(entries: &[T], display: impl Fn(&T) -> String) -> String {
+    if entries.is_empty() {
+        return "None".to_string();
+    }
+    let mut value = entries
+        .iter()
+        .take(FIELD_ENTRIES)
+        .map(display)
+        .collect::<Vec<String>>()
+        .join("\n");
+    if entries.len() > FIELD_ENTRIES {
+        value.push_str(&format!("\n...and {} more", entries.len() - FIELD_ENTRIES));
+    }
+    value
+}
+
+impl CleanupReport {
+    pub fn new(options: &CleanupOptions) -> Self {
+        Self { dry_run: options.dry_run, ..Default::default() }
+    }
+
+    pub fn skip(&mut self, channel_id: ChannelId, reason: SkipReason) {
+        tracing::warn!("[Cleanup] Skipped CHANNEL({}) REASON({})", channel_id.get(), reason.label());
+        self.skipped.push((channel_id, reason));
+    }
+
+    pub fn merge(&mut self, other: CleanupReport) {
+        self.stale_rows.extend(other.stale_rows);
+        self.mismatched_ids.extend(other.mismatched_ids);
+        self.outdated_categories.extend(other.outdated_categories);
+        self.deleted.extend(other.deleted);
+        self.adopted.extend(other.adopted);
+        self.skipped.extend(other.skipped);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stale_rows.is_empty()
+            && self.mismatched_ids.is_empty()
+            && self.outdated_categories.is_empty()
+            && self.deleted.is_empty()
+            && self.adopted.is_empty()
+    }
+
+    pub fn to_embed(&self) -> CreateEmbed {
+        let (title, colour, deleted, adopted) = match self.dry_run {
+            true => ("Cleanup plan (dry run)", Colour::GOLD, "Channels to delete", "Rooms to adopt"),
+            false => ("Cleanup report", Colour::DARK_GREEN, "Deleted channels", "Adopted rooms"),
+        };
+        let channel = |channel_id: &ChannelId| format!("<#{}> `{}`", channel_id.get(), channel_id.get());
+        let row = |id: &i64| format!("`{}`", id);
+
+        CreateEmbed::new()
+            .title(title)
+            .colour(colour)
+            .field(format!("Stale rows ({})", self.stale_rows.len()), field_value(&self.stale_rows, row), false)
+            .field(format!("Mismatched IDs ({})", self.mismatched_ids.len()), field_value(&self.mismatched_ids, row), false)
+            .field(
+                format!("Outdated categories ({})", self.outdated_categories.len()),
+                field_value(&self.outdated_categories, row),
+                false
+            )
+            .field(format!("{} ({})", deleted, self.deleted.len()), field_value(&self.deleted, channel), false)
+            .field(format!("{} ({})", adopted, self.adopted.len()), field_value(&self.adopted, channel), false)
+            .field(
+                format!("Skipped ({})", self.skipped.len()),
+                field_value(&self.skipped, |(channel_id, reason)| format!("{}: {}", channel(channel_id), reason.label())),
+                false
+            )
+    }
+}
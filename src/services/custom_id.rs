@@ -0,0 +1,253 @@
+use base64ct::{Base64UrlUnpadded, Encoding};
+use hmac::{Hmac, Mac};
+use once_cell::sync::OnceCell;
+use serenity::all::{ChannelId, UserId};
+use sha2::Sha256;
+use thiserror::Error;
+
+
+/// Layout version, bumped whenever the payload changes
+const VERSION: u8 = 1;
+/// Version, action and two ids
+const PAYLOAD_LEN: usize = 2 + 8 + 8;
+/// Truncated HMAC-SHA256, keeps the id well under the 100 characters Discord allows
+const TAG_LEN: usize = 12;
+
+/// Key of the tags, `CUSTOM_ID_SECRET` set by `init_secret` at startup.
+/// Changing it invalidates every panel already sent.
+static SECRET: OnceCell<Vec<u8>> = OnceCell::new();
+
+/// Reads `CUSTOM_ID_SECRET`, the bot refuses to start without it.
+pub fn init_secret() -> Result<(), &'static str> {
+    let secret = parse_secret(std::env::var("CUSTOM_ID_SECRET").ok())?;
+    SECRET.set(secret).map_err(|_| "custom id secret is already set")
+}
+
+fn parse_secret(value: Option<String>) -> Result<Vec<u8>, &'static str> {
+    match value {
+        Some(secret) if !secret.trim().is_empty() => Ok(secret.into_bytes()),
+        _ => Err("CUSTOM_ID_SECRET is not set or empty"),
+    }
+}
+
+fn secret() -> &'static [u8] {
+    #[cfg(test)]
+    SECRET.get_or_init(|| b"test secret".to_vec());
+    SECRET.get().expect("custom id secret is set at startup")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PanelAction {
+    /// Single-member select of the panels before the multi-selects, only migrated
+    Select = 1,
+    Invite = 2,
+    Kick = 3,
+    Claim = 4,
+    CleanupConfirm = 5,
+    Lock = 6,
+    Unlock = 7,
+    Hide = 8,
+    Unhide = 9,
+    Rename = 10,
+    Limit = 11,
+    Bitrate = 12,
+    TrustedList = 13,
+    BlockedList = 14,
+    Trust = 15,
+    Untrust = 16,
+    Block = 17,
+    Unblock = 18,
+}
+
+impl PanelAction {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Self::Select),
+            2 => Some(Self::Invite),
+            3 => Some(Self::Kick),
+            4 => Some(Self::Claim),
+            5 => Some(Self::CleanupConfirm),
+            6 => Some(Self::Lock),
+            7 => Some(Self::Unlock),
+            8 => Some(Self::Hide),
+            9 => Some(Self::Unhide),
+            10 => Some(Self::Rename),
+            11 => Some(Self::Limit),
+            12 => Some(Self::Bitrate),
+            13 => Some(Self::TrustedList),
+            14 => Some(Self::BlockedList),
+            15 => Some(Self::Trust),
+            16 => Some(Self::Untrust),
+            17 => Some(Self::Block),
+            18 => Some(Self::Unblock),
+            _ => None,
+        }
+    }
+
+    /// Action of the `inv_{action}_{user}_{channel}` ids sent before the codec.
+    fn from_legacy(name: &str) -> Option<Self> {
+        match name {
+            "sel" => Some(Self::Select),
+            "inv" => Some(Self::Invite),
+            "kick" => Some(Self::Kick),
+            "claim" => Some(Self::Claim),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CustomIdError {
+    #[error("malformed custom id")]
+    Malformed,
+    #[error("unsupported custom id version {0}")]
+    Version(u8),
+    #[error("unknown custom id action {0}")]
+    Action(u8),
+    #[error("custom id signature mismatch")]
+    Signature,
+}
+
+/// State of a component, signed so a client can't point a panel at another room or owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CustomId {
+    pub action: PanelAction,
+    /// Owner of the panel, the previous owner for `Claim`, the invoker for `CleanupConfirm`
+    pub user_id: UserId,
+    pub channel_id: ChannelId,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(key: &[u8], payload: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac
+}
+
+impl CustomId {
+    pub fn new(action: PanelAction, user_id: UserId, channel_id: ChannelId) -> Self {
+        Self { action, user_id, channel_id }
+    }
+
+    pub fn encode(&self) -> String {
+        self.encode_with(secret())
+    }
+
+    pub fn decode(custom_id: &str) -> Result<Self, CustomIdError> {
+        Self::decode_with(custom_id, secret())
+    }
+
+    fn encode_with(&self, key: &[u8]) -> String {
+        let mut bytes = Vec::with_capacity(PAYLOAD_LEN + TAG_LEN);
+        bytes.push(VERSION);
+        bytes.push(self.action as u8);
+        bytes.extend_from_slice(&self.user_id.get().to_be_bytes());
+        bytes.extend_from_slice(&self.channel_id.get().to_be_bytes());
+        let tag = mac(key, &bytes).finalize().into_bytes();
+        bytes.extend_from_slice(&tag[..TAG_LEN]);
+        Base64UrlUnpadded::encode_string(&bytes)
+    }
+
+    fn decode_with(custom_id: &str, key: &[u8]) -> Result<Self, CustomIdError> {
+        let bytes = Base64UrlUnpadded::decode_vec(custom_id).map_err(|_| CustomIdError::Malformed)?;
+        if bytes.len() != PAYLOAD_LEN + TAG_LEN {
+            return Err(CustomIdError::Malformed);
+        }
+        let (payload, tag) = bytes.split_at(PAYLOAD_LEN);
+        // The tag is checked first, nothing of a forged id is trusted
+        mac(key, payload).verify_truncated_left(tag).map_err(|_| CustomIdError::Signature)?;
+        if payload[0] != VERSION {
+            return Err(CustomIdError::Version(payload[0]));
+        }
+        let action = PanelAction::from_byte(payload[1]).ok_or(CustomIdError::Action(payload[1]))?;
+        let user_id = u64::from_be_bytes(payload[2..10].try_into().unwrap());
+        let channel_id = u64::from_be_bytes(payload[10..18].try_into().unwrap());
+        if user_id == 0 || channel_id == 0 {
+            return Err(CustomIdError::Malformed);
+        }
+        Ok(Self::new(action, UserId::new(user_id), ChannelId::new(channel_id)))
+    }
+
+    /// Unsigned `inv_{action}_{user}_{channel}` id of a panel sent before the codec,
+    /// only to be trusted on a message the bot sent itself.
+    pub fn parse_legacy(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.strip_prefix("inv_")?.split('_');
+        let action = PanelAction::from_legacy(parts.next()?)?;
+        let user_id = parts.next()?.parse::<u64>().ok().filter(|id| *id != 0)?;
+        let channel_id = parts.next()?.parse::<u64>().ok().filter(|id| *id != 0)?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(action, UserId::new(user_id), ChannelId::new(channel_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64ct::{Base64UrlUnpadded, Encoding};
+    use hmac::Mac;
+    use serenity::all::{ChannelId, UserId};
+
+    use super::{mac, parse_secret, CustomId, CustomIdError, PanelAction, TAG_LEN};
+
+
+    const KEY: &[u8] = b"test secret";
+
+    fn id() -> CustomId {
+        CustomId::new(PanelAction::Kick, UserId::new(402_112_330_987_716_608), ChannelId::new(1_187_403_521_002_176_543))
+    }
+
+    #[test]
+    fn round_trips_under_discord_limit() {
+        let encoded = id().encode_with(KEY);
+        assert!(encoded.len() <= 100);
+        assert_eq!(CustomId::decode_with(&encoded, KEY), Ok(id()));
+    }
+
+    #[test]
+    fn rejects_tampered_and_foreign_ids() {
+        let encoded = id().encode_with(KEY);
+        assert_eq!(CustomId::decode_with(&encoded, b"other secret"), Err(CustomIdError::Signature));
+
+        let mut bytes = Base64UrlUnpadded::decode_vec(&encoded).unwrap();
+        bytes[1] = PanelAction::Claim as u8;
+        let forged = Base64UrlUnpadded::encode_string(&bytes);
+        assert_eq!(CustomId::decode_with(&forged, KEY), Err(CustomIdError::Signature));
+
+        assert_eq!(CustomId::decode_with("inv_kick_1_2", KEY), Err(CustomIdError::Malformed));
+        assert_eq!(CustomId::decode_with("", KEY), Err(CustomIdError::Malformed));
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let mut bytes = vec![2, PanelAction::Kick as u8];
+        bytes.extend_from_slice(&1u64.to_be_bytes());
+        bytes.extend_from_slice(&2u64.to_be_bytes());
+        let tag = mac(KEY, &bytes).finalize().into_bytes();
+        bytes.extend_from_slice(&tag[..TAG_LEN]);
+        let encoded = Base64UrlUnpadded::encode_string(&bytes);
+        assert_eq!(CustomId::decode_with(&encoded, KEY), Err(CustomIdError::Version(2)));
+    }
+
+    #[test]
+    fn requires_a_secret() {
+        assert_eq!(parse_secret(None), Err("CUSTOM_ID_SECRET is not set or empty"));
+        assert_eq!(parse_secret(Some(String::new())), Err("CUSTOM_ID_SECRET is not set or empty"));
+        assert_eq!(parse_secret(Some("  ".to_string())), Err("CUSTOM_ID_SECRET is not set or empty"));
+        assert_eq!(parse_secret(Some("secret".to_string())), Ok(b"secret".to_vec()));
+    }
+
+    #[test]
+    fn parses_legacy_ids() {
+        assert_eq!(
+            CustomId::parse_legacy("inv_kick_402112330987716608_1187403521002176543"),
+            Some(id())
+        );
+        assert_eq!(CustomId::parse_legacy("inv_sel_1_2").map(|id| id.action), Some(PanelAction::Select));
+        assert_eq!(CustomId::parse_legacy("inv_ban_1_2"), None);
+        assert_eq!(CustomId::parse_legacy("inv_kick_1_2_3"), None);
+        assert_eq!(CustomId::parse_legacy("cleanup_confirm_1"), None);
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use serenity::all::{
+    ChannelId, ChannelType, CreateChannel, CreateMessage, EditChannel, EditMessage, GuildChannel, GuildId,
+    MessageId, PermissionOverwrite, PermissionOverwriteType, PremiumTier, User, UserId, VoiceState
+};
+
+use crate::services::gateway::DiscordGateway;
+use crate::sql::autoroom::{AutoRoom, RoomPrivacy};
+
+
+// Ids are unique across every fake, the channel cache is shared by all tests of the process
+static NEXT_ID: AtomicU64 = AtomicU64::new(1 << 40);
+
+pub fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Requests that can be made to fail with [`FakeDiscord::fail`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FakeCall {
+    GetChannel,
+    CreateChannel,
+    EditChannel,
+    DeleteChannel,
+    MoveMember,
+    DisconnectMember,
+    CreatePermission,
+    DeletePermission,
+    SendMessage,
+    EditMessage,
+    DeleteMessage,
+}
+
+struct FakeGuild {
+    premium_tier: PremiumTier,
+    system_channel_id: Option<ChannelId>,
+    channels: HashMap<ChannelId, GuildChannel>,
+    voice: HashMap<UserId, ChannelId>,
+}
+
+struct FakeMessage {
+    id: MessageId,
+    channel_id: ChannelId,
+    /// The message as the builders serialize it, edits replace the fields they set
+    body: Value,
+}
+
+struct FakeState {
+    guilds: HashMap<GuildId, FakeGuild>,
+    bots: HashSet<UserId>,
+    messages: Vec<FakeMessage>,
+    failures: HashSet<FakeCall>,
+}
+
+/// Guilds simulated in memory. Requests change the simulated guilds the way Discord would,
+/// so a scenario can be checked by looking at the channels, overwrites and voice states afterwards.
+pub struct FakeDiscord {
+    bot_id: UserId,
+    state: Mutex<FakeState>,
+}
+
+fn fake_error(reason: &'static str) -> serenity::Error {
+    serenity::Error::Other(reason)
+}
+
+fn guild_channel(value: Value) -> GuildChannel {
+    serde_json::from_value(value).expect("Fake channel is a valid guild channel")
+}
+
+impl Default for FakeDiscord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeDiscord {
+    pub fn new() -> Self {
+        Self {
+            bot_id: UserId::new(next_id()),
+            state: Mutex::new(FakeState {
+                guilds: HashMap::new(),
+                bots: HashSet::new(),
+                messages: Vec::new(),
+                failures: HashSet::new(),
+            }),
+        }
+    }
+
+    pub fn add_guild(&self) -> GuildId {
+        let guild_id = GuildId::new(next_id());
+        self.state.lock().guilds.insert(guild_id, FakeGuild {
+            premium_tier: PremiumTier::Tier0,
+            system_channel_id: None,
+            channels: HashMap::new(),
+            voice: HashMap::new(),
+        });
+        guild_id
+    }
+
+    fn add_channel(&self, guild_id: GuildId, kind: ChannelType, parent_id: Option<ChannelId>, name: &str) -> ChannelId {
+        let channel_id = ChannelId::new(next_id());
+        let channel = guild_channel(json!({
+            "id": channel_id,
+            "guild_id": guild_id,
+            "type": u8::from(kind),
+            "name": name,
+            "parent_id": parent_id,
+        }));
+        self.state.lock().guilds.get_mut(&guild_id).expect("Fake guild exists").channels.insert(channel_id, channel);
+        channel_id
+    }
+
+    pub fn add_category(&self, guild_id: GuildId, name: &str) -> ChannelId {
+        self.add_channel(guild_id, ChannelType::Category, None, name)
+    }
+
+    pub fn add_voice_channel(&self, guild_id: GuildId, parent_id: Option<ChannelId>, name: &str) -> ChannelId {
+        self.add_channel(guild_id, ChannelType::Voice, parent_id, name)
+    }
+
+    pub fn add_text_channel(&self, guild_id: GuildId, name: &str) -> ChannelId {
+        self.add_channel(guild_id, ChannelType::Text, None, name)
+    }
+
+    /// Puts the member into the voice channel without any request, like a member joining by hand.
+    pub fn connect(&self, guild_id: GuildId, user_id: UserId, channel_id: ChannelId) {
+        self.state.lock().guilds.get_mut(&guild_id).expect("Fake guild exists").voice.insert(user_id, channel_id);
+    }
+
+    /// Takes the member out of voice without any request, like a member leaving by hand.
+    pub fn disconnect(&self, guild_id: GuildId, user_id: UserId) {
+        self.state.lock().guilds.get_mut(&guild_id).expect("Fake guild exists").voice.remove(&user_id);
+    }
+
+    pub fn add_bot(&self, user_id: UserId) {
+        self.state.lock().bots.insert(user_id);
+    }
+
+    pub fn set_overwrites(&self, channel_id: ChannelId, overwrites: Vec<PermissionOverwrite>) {
+        let mut state = self.state.lock();
+        let channel = state.guilds
+            .values_mut()
+            .find_map(|guild| guild.channels.get_mut(&channel_id))
+            .expect("Fake channel exists");
+        channel.permission_overwrites = overwrites;
+    }
+
+    /// Makes every following request of the kind fail.
+    pub fn fail(&self, call: FakeCall) {
+        self.state.lock().failures.insert(call);
+    }
+
+    pub fn get(&self, channel_id: ChannelId) -> Option<GuildChannel> {
+        self.state.lock().guilds.values().find_map(|guild| guild.channels.get(&channel_id).cloned())
+    }
+
+    pub fn children(&self, category_id: ChannelId) -> Vec<GuildChannel> {
+        self.state
+            .lock()
+            .guilds
+            .values()
+            .flat_map(|guild| guild.channels.values())
+            .filter(|channel| channel.parent_id == Some(category_id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn messages(&self, channel_id: ChannelId) -> Vec<String> {
+        self.state
+            .lock()
+            .messages
+            .iter()
+            .filter(|message| message.channel_id == channel_id)
+            .map(|message| message.body["content"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    pub fn message(&self, message_id: MessageId) -> Option<Value> {
+        self.state
+            .lock()
+            .messages
+            .iter()
+            .find(|message| message.id == message_id)
+            .map(|message| message.body.clone())
+    }
+
+    pub fn member_overwrite(&self, channel_id: ChannelId, user_id: UserId) -> Option<PermissionOverwrite> {
+        self.get(channel_id)?
+            .permission_overwrites
+            .into_iter()
+            .find(|overwrite| overwrite.kind == PermissionOverwriteType::Member(user_id))
+    }
+
+    fn check(&self, state: &FakeState, call: FakeCall) -> Result<(), &'static str> {
+        match state.failures.contains(&call) {
+            true => Err("Injected failure"),
+            false => Ok(()),
+        }
+    }
+
+    fn edit<T>(&self, call: FakeCall, channel_id: ChannelId, change: impl FnOnce(&mut GuildChannel) -> T) -> Result<T, &'static str> {
+        let mut state = self.state.lock();
+        self.check(&state, call)?;
+        let channel = state.guilds
+            .values_mut()
+            .find_map(|guild| guild.channels.get_mut(&channel_id))
+            .ok_or("Unknown Channel")?;
+        Ok(change(channel))
+    }
+}
+
+/// Voice state of a member that just joined `channel_id`, as sent by the gateway.
+pub fn voice_state(guild_id: GuildId, user_id: UserId, name: &str, channel_id: ChannelId) -> VoiceState {
+    serde_json::from_value(json!({
+        "channel_id": channel_id,
+        "guild_id": guild_id,
+        "user_id": user_id,
+        "session_id": "fake",
+        "deaf": false,
+        "mute": false,
+        "self_deaf": false,
+        "self_mute": false,
+        "self_video": false,
+        "suppress": false,
+        "member": {
+            "user": user_json(user_id, name),
+            "roles": [],
+            "deaf": false,
+            "mute": false,
+            "flags": 0,
+            "guild_id": guild_id,
+        },
+    })).expect("Fake voice state is valid")
+}
+
+pub fn user(user_id: UserId, name: &str) -> User {
+    serde_json::from_value(user_json(user_id, name)).expect("Fake user is valid")
+}
+
+fn user_json(user_id: UserId, name: &str) -> Value {
+    json!({
+        "id": user_id,
+        "username": name,
+        "discriminator": "0000",
+        "global_name": null,
+        "avatar": null,
+    })
+}
+
+/// Public autoroom of the trigger channel placing rooms into `category_id`.
+pub fn autoroom(guild_id: GuildId, trigger_id: ChannelId, category_id: ChannelId) -> AutoRoom {
+    AutoRoom {
+        channel_id: trigger_id.get() as i64,
+        guild_id: guild_id.get() as i64,
+        category_id: category_id.get() as i64,
+        suffix: "room".to_string(),
+        grace_period_secs: 0,
+        privacy: RoomPrivacy::Public,
+        name_template: "{nick}`s {suffix}".to_string(),
+        user_limit: None,
+        bitrate_cap: None,
+        rtc_region: None,
+        video_quality: None,
+        nsfw: false,
+        clone_trigger: false,
+        room_panel: true,
+    }
+}
+
+/// Every request yields first, like a request to Discord does, so concurrent handlers interleave at each call.
+#[async_trait]
+impl DiscordGateway for FakeDiscord {
+    fn current_user_id(&self) -> UserId {
+        self.bot_id
+    }
+
+    fn guild_channels(&self, guild_id: GuildId) -> Option<Vec<GuildChannel>> {
+        self.state.lock().guilds.get(&guild_id).map(|guild| guild.channels.values().cloned().collect())
+    }
+
+    fn premium_tier(&self, guild_id: GuildId) -> Option<PremiumTier> {
+        self.state.lock().guilds.get(&guild_id).map(|guild| guild.premium_tier)
+    }
+
+    fn system_channel_id(&self, guild_id: GuildId) -> Option<ChannelId> {
+        self.state.lock().guilds.get(&guild_id).and_then(|guild| guild.system_channel_id)
+    }
+
+    fn voice_channel_id(&self, guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+        self.state.lock().guilds.get(&guild_id).and_then(|guild| guild.voice.get(&user_id).copied())
+    }
+
+    fn channel_members(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Vec<UserId>> {
+        self.state.lock().guilds.get(&guild_id).map(|guild| {
+            guild.voice
+                .iter()
+                .filter(|(_, connected_to)| **connected_to == channel_id)
+                .map(|(user_id, _)| *user_id)
+                .collect()
+        })
+    }
+
+    fn playing(&self, _guild_id: GuildId, _user_id: UserId) -> Option<String> {
+        None
+    }
+
+    fn is_bot(&self, user_id: UserId) -> bool {
+        user_id == self.bot_id || self.state.lock().bots.contains(&user_id)
+    }
+
+    async fn channel(&self, channel_id: ChannelId) -> Result<Option<GuildChannel>, serenity::Error> {
+        tokio::task::yield_now().await;
+        self.check(&self.state.lock(), FakeCall::GetChannel).map_err(fake_error)?;
+        Ok(self.get(channel_id))
+    }
+
+    async fn create_channel(&self, guild_id: GuildId, builder: CreateChannel<'_>) -> Result<GuildChannel, serenity::Error> {
+        tokio::task::yield_now().await;
+        let mut state = self.state.lock();
+        self.check(&state, FakeCall::CreateChannel).map_err(fake_error)?;
+        let guild = state.guilds.get_mut(&guild_id).ok_or(fake_error("Unknown Guild"))?;
+
+        let mut value = serde_json::to_value(&builder).expect("Builder serializes");
+        value["id"] = json!(next_id());
+        value["guild_id"] = json!(guild_id);
+        let channel = guild_channel(value);
+        guild.channels.insert(channel.id, channel.clone());
+        Ok(channel)
+    }
+
+    async fn edit_channel(&self, channel_id: ChannelId, builder: EditChannel<'_>) -> Result<GuildChannel, serenity::Error> {
+        tokio::task::yield_now().await;
+        let changes = serde_json::to_value(&builder).expect("Builder serializes");
+        self.edit(FakeCall::EditChannel, channel_id, |channel| {
+            let mut value = serde_json::to_value(&*channel).expect("Channel serializes");
+            if let (Some(value), Some(changes)) = (value.as_object_mut(), changes.as_object()) {
+                value.extend(changes.clone());
+            }
+            *channel = guild_channel(value);
+            channel.clone()
+        }).map_err(fake_error)
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), serenity::Error> {
+        tokio::task::yield_now().await;
+        let mut state = self.state.lock();
+        self.check(&state, FakeCall::DeleteChannel).map_err(fake_error)?;
+        let guild = state.guilds
+            .values_mut()
+            .find(|guild| guild.channels.contains_key(&channel_id))
+            .ok_or(fake_error("Unknown Channel"))?;
+        guild.channels.remove(&channel_id);
+        // Discord disconnects everybody from a deleted voice channel
+        guild.voice.retain(|_, connected_to| *connected_to != channel_id);
+        Ok(())
+    }
+
+    async fn move_member(&self, guild_id: GuildId, user_id: UserId, channel_id: ChannelId) -> Result<(), serenity::Error> {
+        tokio::task::yield_now().await;
+        let mut state = self.state.lock();
+        self.check(&state, FakeCall::MoveMember).map_err(fake_error)?;
+        let guild = state.guilds.get_mut(&guild_id).ok_or(fake_error("Unknown Guild"))?;
+        if !guild.channels.contains_key(&channel_id) {
+            return Err(fake_error("Unknown Channel"));
+        }
+        match guild.voice.get_mut(&user_id) {
+            Some(connected_to) => *connected_to = channel_id,
+            None => return Err(fake_error("Target user is not connected to voice")),
+        };
+        Ok(())
+    }
+
+    async fn disconnect_member(&self, guild_id: GuildId, user_id: UserId) -> Result<(), serenity::Error> {
+        tokio::task::yield_now().await;
+        let mut state = self.state.lock();
+        self.check(&state, FakeCall::DisconnectMember).map_err(fake_error)?;
+        let guild = state.guilds.get_mut(&guild_id).ok_or(fake_error("Unknown Guild"))?;
+        guild.voice.remove(&user_id);
+        Ok(())
+    }
+
+    async fn create_permission(&self, channel_id: ChannelId, overwrite: PermissionOverwrite) -> Result<(), serenity::Error> {
+        tokio::task::yield_now().await;
+        self.edit(FakeCall::CreatePermission, channel_id, |channel| {
+            channel.permission_overwrites.retain(|existing| existing.kind != overwrite.kind);
+            channel.permission_overwrites.push(overwrite);
+        }).map_err(fake_error)
+    }
+
+    async fn delete_permission(&self, channel_id: ChannelId, kind: PermissionOverwriteType) -> Result<(), serenity::Error> {
+        tokio::task::yield_now().await;
+        self.edit(FakeCall::DeletePermission, channel_id, |channel| {
+            channel.permission_overwrites.retain(|existing| existing.kind != kind);
+        }).map_err(fake_error)
+    }
+
+    async fn send_message(&self, channel_id: ChannelId, message: CreateMessage) -> Result<MessageId, serenity::Error> {
+        tokio::task::yield_now().await;
+        let mut state = self.state.lock();
+        self.check(&state, FakeCall::SendMessage).map_err(fake_error)?;
+        let id = MessageId::new(next_id());
+        let body = serde_json::to_value(&message).expect("Builder serializes");
+        state.messages.push(FakeMessage { id, channel_id, body });
+        Ok(id)
+    }
+
+    async fn edit_message(&self, channel_id: ChannelId, message_id: MessageId, builder: EditMessage) -> Result<(), serenity::Error> {
+        tokio::task::yield_now().await;
+        let mut state = self.state.lock();
+        self.check(&state, FakeCall::EditMessage).map_err(fake_error)?;
+        let message = state.messages
+            .iter_mut()
+            .find(|message| message.id == message_id && message.channel_id == channel_id)
+            .ok_or(fake_error("Unknown Message"))?;
+        if let Value::Object(fields) = serde_json::to_value(&builder).expect("Builder serializes") {
+            for (key, value) in fields {
+                message.body[key] = value;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<(), serenity::Error> {
+        tokio::task::yield_now().await;
+        let mut state = self.state.lock();
+        self.check(&state, FakeCall::DeleteMessage).map_err(fake_error)?;
+        let count = state.messages.len();
+        state.messages.retain(|message| message.id != message_id || message.channel_id != channel_id);
+        match state.messages.len() < count {
+            true => Ok(()),
+            false => Err(fake_error("Unknown Message")),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use serenity::all::{
+    ActivityType, ChannelId, Context, CreateChannel, CreateMessage, EditChannel, EditMessage, GuildChannel, GuildId,
+    MessageId, PermissionOverwrite, PermissionOverwriteType, PremiumTier, UserId
+};
+use serenity::http::{HttpError, StatusCode};
+
+
+/// Discord answered `404 Not Found`, the requested channel, message or member is gone.
+pub fn is_not_found(err: &serenity::Error) -> bool {
+    matches!(
+        err,
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) if response.status_code == StatusCode::NOT_FOUND
+    )
+}
+
+/// Discord answered `429 Too Many Requests`, the request may be retried later.
+pub fn is_rate_limited(err: &serenity::Error) -> bool {
+    matches!(
+        err,
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) if response.status_code == StatusCode::TOO_MANY_REQUESTS
+    )
+}
+
+/// Everything the room services need from Discord. Cache reads are synchronous,
+/// requests to the API are async and return the serenity error unchanged.
+#[async_trait]
+pub trait DiscordGateway: Send + Sync {
+    fn current_user_id(&self) -> UserId;
+    /// Channels of the guild, `None` when the guild is not cached.
+    fn guild_channels(&self, guild_id: GuildId) -> Option<Vec<GuildChannel>>;
+    fn premium_tier(&self, guild_id: GuildId) -> Option<PremiumTier>;
+    fn system_channel_id(&self, guild_id: GuildId) -> Option<ChannelId>;
+    /// Voice channel the member is connected to.
+    fn voice_channel_id(&self, guild_id: GuildId, user_id: UserId) -> Option<ChannelId>;
+    /// Members connected to the voice channel, `None` when the guild is not cached.
+    fn channel_members(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Vec<UserId>>;
+    /// Name of the game the member is playing.
+    fn playing(&self, guild_id: GuildId, user_id: UserId) -> Option<String>;
+    /// The user is a bot, `false` when the user is not cached.
+    fn is_bot(&self, user_id: UserId) -> bool;
+
+    /// The channel, `None` when Discord doesn't know it or it is not a guild channel.
+    /// Other failures, like timeouts or outages, are errors and say nothing about the channel.
+    async fn channel(&self, channel_id: ChannelId) -> Result<Option<GuildChannel>, serenity::Error>;
+    async fn create_channel(&self, guild_id: GuildId, builder: CreateChannel<'_>) -> Result<GuildChannel, serenity::Error>;
+    async fn edit_channel(&self, channel_id: ChannelId, builder: EditChannel<'_>) -> Result<GuildChannel, serenity::Error>;
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), serenity::Error>;
+    async fn move_member(&self, guild_id: GuildId, user_id: UserId, channel_id: ChannelId) -> Result<(), serenity::Error>;
+    async fn disconnect_member(&self, guild_id: GuildId, user_id: UserId) -> Result<(), serenity::Error>;
+    async fn create_permission(&self, channel_id: ChannelId, overwrite: PermissionOverwrite) -> Result<(), serenity::Error>;
+    async fn delete_permission(&self, channel_id: ChannelId, kind: PermissionOverwriteType) -> Result<(), serenity::Error>;
+    async fn send_message(&self, channel_id: ChannelId, message: CreateMessage) -> Result<MessageId, serenity::Error>;
+    async fn edit_message(&self, channel_id: ChannelId, message_id: MessageId, builder: EditMessage) -> Result<(), serenity::Error>;
+    async fn delete_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<(), serenity::Error>;
+}
+
+#[async_trait]
+impl DiscordGateway for Context {
+    fn current_user_id(&self) -> UserId {
+        self.cache.current_user().id
+    }
+
+    fn guild_channels(&self, guild_id: GuildId) -> Option<Vec<GuildChannel>> {
+        self.cache
+            .guild(guild_id)
+            .map(|guild| guild.channels.values().cloned().collect())
+    }
+
+    fn premium_tier(&self, guild_id: GuildId) -> Option<PremiumTier> {
+        self.cache.guild(guild_id).map(|guild| guild.premium_tier)
+    }
+
+    fn system_channel_id(&self, guild_id: GuildId) -> Option<ChannelId> {
+        self.cache.guild(guild_id).and_then(|guild| guild.system_channel_id)
+    }
+
+    fn voice_channel_id(&self, guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+        self.cache
+            .guild(guild_id)
+            .and_then(|guild| guild.voice_states.get(&user_id).and_then(|state| state.channel_id))
+    }
+
+    fn channel_members(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Vec<UserId>> {
+        let guild = self.cache.guild(guild_id)?;
+        Some(guild.voice_states
+            .values()
+            .filter(|state| state.channel_id == Some(channel_id))
+            .map(|state| state.user_id)
+            .collect())
+    }
+
+    fn playing(&self, guild_id: GuildId, user_id: UserId) -> Option<String> {
+        self.cache.guild(guild_id).and_then(|guild| {
+            guild.presences
+                .get(&user_id)
+                .and_then(|presence| {
+                    presence.activities
+                        .iter()
+                        .find(|activity| activity.kind == ActivityType::Playing)
+                        .map(|activity| activity.name.clone())
+                })
+        })
+    }
+
+    fn is_bot(&self, user_id: UserId) -> bool {
+        self.cache.user(user_id).is_some_and(|user| user.bot)
+    }
+
+    async fn channel(&self, channel_id: ChannelId) -> Result<Option<GuildChannel>, serenity::Error> {
+        match channel_id.to_channel(self).await {
+            Ok(channel) => Ok(channel.guild()),
+            Err(err) if is_not_found(&err) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn create_channel(&self, guild_id: GuildId, builder: CreateChannel<'_>) -> Result<GuildChannel, serenity::Error> {
+        guild_id.create_channel(&self.http, builder).await
+    }
+
+    async fn edit_channel(&self, channel_id: ChannelId, builder: EditChannel<'_>) -> Result<GuildChannel, serenity::Error> {
+        channel_id.edit(&self.http, builder).await
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), serenity::Error> {
+        channel_id.delete(&self.http).await.map(|_| ())
+    }
+
+    async fn move_member(&self, guild_id: GuildId, user_id: UserId, channel_id: ChannelId) -> Result<(), serenity::Error> {
+        guild_id.move_member(&self.http, user_id, channel_id).await.map(|_| ())
+    }
+
+    async fn disconnect_member(&self, guild_id: GuildId, user_id: UserId) -> Result<(), serenity::Error> {
+        guild_id.disconnect_member(&self.http, user_id).await.map(|_| ())
+    }
+
+    async fn create_permission(&self, channel_id: ChannelId, overwrite: PermissionOverwrite) -> Result<(), serenity::Error> {
+        channel_id.create_permission(&self.http, overwrite).await
+    }
+
+    async fn delete_permission(&self, channel_id: ChannelId, kind: PermissionOverwriteType) -> Result<(), serenity::Error> {
+        channel_id.delete_permission(&self.http, kind).await
+    }
+
+    async fn send_message(&self, channel_id: ChannelId, message: CreateMessage) -> Result<MessageId, serenity::Error> {
+        channel_id.send_message(&self.http, message).await.map(|message| message.id)
+    }
+
+    async fn edit_message(&self, channel_id: ChannelId, message_id: MessageId, builder: EditMessage) -> Result<(), serenity::Error> {
+        channel_id.edit_message(&self.http, message_id, builder).await.map(|_| ())
+    }
+
+    async fn delete_message(&self, channel_id: ChannelId, message_id: MessageId) -> Result<(), serenity::Error> {
+        channel_id.delete_message(&self.http, message_id).await
+    }
+}
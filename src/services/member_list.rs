@@ -0,0 +1,336 @@
+use serenity::all::{
+    ButtonStyle, ChannelId, CreateActionRow, CreateButton, CreateSelectMenu, CreateSelectMenuKind, GuildChannel, GuildId,
+    Mentionable, PermissionOverwrite, PermissionOverwriteType, Permissions, UserId
+};
+
+use crate::services::autoroom::voice_channel::{get_owned_channel_id, BotError, MembersReport};
+use crate::services::autoroom::{grant_guest_privileges, room_privacy};
+use crate::services::custom_id::{CustomId, PanelAction};
+use crate::services::gateway::DiscordGateway;
+use crate::sql::{autoroom::RoomPrivacy, member_list::MemberList, prelude::Repositories};
+
+
+/// Every listed member costs a request when a room is created
+const MAX_LISTED_MEMBERS: usize = 50;
+/// Most members one select can pick, the limit of Discord
+const MAX_PICKED_MEMBERS: u8 = 25;
+
+/// Blocked members can neither see nor join the room, whatever its privacy.
+async fn deny_blocked(discord: &dyn DiscordGateway, channel_id: ChannelId, user_id: UserId) -> Result<(), serenity::Error> {
+    discord.create_permission(channel_id, PermissionOverwrite {
+        allow: Permissions::empty(),
+        deny: Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+        kind: PermissionOverwriteType::Member(user_id),
+    }).await
+}
+
+async fn block_in_room(discord: &dyn DiscordGateway, guild_id: GuildId, channel_id: ChannelId, user_id: UserId) -> Result<(), serenity::Error> {
+    deny_blocked(discord, channel_id, user_id).await?;
+    if discord.voice_channel_id(guild_id, user_id) == Some(channel_id) {
+        discord.disconnect_member(guild_id, user_id).await?;
+    }
+    Ok(())
+}
+
+/// Invites the trusted members of the host to a new room, denies and disconnects the blocked ones.
+/// A member that fails is logged and skipped, the room is usable anyway.
+pub async fn apply_to_room(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    privacy: RoomPrivacy,
+    owner_id: UserId
+) -> Result<(), sqlx::Error> {
+    let owner = owner_id.get() as i64;
+    for member_id in repositories.lists.get_members(MemberList::Trusted, owner).await? {
+        let user_id = UserId::new(member_id as u64);
+        if let Err(err) = grant_guest_privileges(discord, &channel_id, &user_id, privacy).await {
+            tracing::error!("Failed to invite trusted member CHANNEL({}) OWNER({}) MEMBER({}).\n{}", channel_id, owner_id, user_id, err);
+        }
+    }
+    for member_id in repositories.lists.get_members(MemberList::Blocked, owner).await? {
+        let user_id = UserId::new(member_id as u64);
+        if let Err(err) = block_in_room(discord, guild_id, channel_id, user_id).await {
+            tracing::error!("Failed to block member CHANNEL({}) OWNER({}) MEMBER({}).\n{}", channel_id, owner_id, user_id, err);
+        }
+    }
+    Ok(())
+}
+
+fn database_error(author_id: i64, err: sqlx::Error) -> BotError {
+    tracing::error!("member_list database error AUTHOR({}).\n{}", author_id, err);
+    BotError::DatabaseError
+}
+
+/// Room of the host the list changes apply to right away, the lists work without a room too.
+async fn current_room(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    author_id: i64
+) -> Result<Option<GuildChannel>, BotError> {
+    let channel_id = match get_owned_channel_id(discord, repositories, guild_id, author_id).await {
+        Ok(Some(channel_id)) => channel_id,
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(database_error(author_id, err)),
+    };
+    discord.channel(channel_id).await.map_err(|err| {
+        tracing::error!("member_list serenity error AUTHOR({}) CHANNEL({}).\n{}", author_id, channel_id, err);
+        BotError::SerenityError
+    })
+}
+
+/// Puts the member on the list, taking them off the other one.
+async fn list_member(repositories: &Repositories, list: MemberList, author_id: i64, member_id: i64) -> Result<(), sqlx::Error> {
+    repositories.lists.remove_member(list.other(), author_id, member_id).await?;
+    repositories.lists.add_member(list, author_id, member_id).await?;
+    Ok(())
+}
+
+/// Applies the members to the current room of the host and puts them on the list.
+/// A member is only listed once the room took the change, so a failed member is not listed either.
+pub async fn add_members(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    list: MemberList,
+    author_id: i64,
+    user_ids: &[UserId]
+) -> Result<MembersReport, BotError> {
+    let listed = repositories.lists.get_members(list, author_id).await.map_err(|err| database_error(author_id, err))?;
+    let new_members = user_ids.iter().filter(|user_id| !listed.contains(&(user_id.get() as i64))).count();
+    if listed.len() + new_members > MAX_LISTED_MEMBERS {
+        return Err(BotError::Rejected("A list holds at most 50 members"));
+    }
+    let room = current_room(discord, repositories, guild_id, author_id).await?;
+    let mut report = MembersReport::default();
+
+    for user_id in user_ids {
+        if user_id.get() as i64 == author_id {
+            report.skipped.push(*user_id);
+            continue;
+        }
+        let applied = match (&room, list) {
+            (Some(room), MemberList::Trusted) => grant_guest_privileges(discord, &room.id, user_id, room_privacy(room)).await,
+            (Some(room), MemberList::Blocked) => block_in_room(discord, guild_id, room.id, *user_id).await,
+            (None, _) => Ok(()),
+        };
+        if let Err(err) = applied {
+            tracing::error!("add_members serenity error AUTHOR({}) MEMBER({}).\n{}", author_id, user_id, err);
+            report.failed.push(*user_id);
+            continue;
+        }
+        match list_member(repositories, list, author_id, user_id.get() as i64).await {
+            Ok(_) => {
+                tracing::info!("Member listed. AUTHOR({}) MEMBER({}) LIST({:?}) CHANNEL({:?})", author_id, user_id, list, room.as_ref().map(|room| room.id));
+                report.done.push(*user_id);
+            },
+            Err(err) => {
+                database_error(author_id, err);
+                report.failed.push(*user_id);
+            },
+        }
+    }
+
+    Ok(report)
+}
+
+/// Takes the members off the host's list. Unblocked members also lose the deny of the current room first,
+/// untrusted members keep their invite to it and are only left out of the next rooms.
+pub async fn remove_members(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    list: MemberList,
+    author_id: i64,
+    user_ids: &[UserId]
+) -> Result<MembersReport, BotError> {
+    let room = match list {
+        MemberList::Blocked => current_room(discord, repositories, guild_id, author_id).await?,
+        MemberList::Trusted => None,
+    };
+    let listed = repositories.lists.get_members(list, author_id).await.map_err(|err| database_error(author_id, err))?;
+    let mut report = MembersReport::default();
+
+    for user_id in user_ids {
+        let member_id = user_id.get() as i64;
+        // Members who were not on the list keep whatever the room gave them
+        let applied = match &room {
+            Some(room) if listed.contains(&member_id) => discord.delete_permission(room.id, PermissionOverwriteType::Member(*user_id)).await,
+            _ => Ok(()),
+        };
+        if let Err(err) = applied {
+            tracing::error!("remove_members serenity error AUTHOR({}) MEMBER({}).\n{}", author_id, user_id, err);
+            report.failed.push(*user_id);
+            continue;
+        }
+        match repositories.lists.remove_member(list, author_id, member_id).await {
+            Ok(removed) => {
+                tracing::info!("Member unlisted. AUTHOR({}) MEMBER({}) LIST({:?}) REMOVED({})", author_id, user_id, list, removed);
+                report.done.push(*user_id);
+            },
+            Err(err) => {
+                database_error(author_id, err);
+                report.failed.push(*user_id);
+            },
+        }
+    }
+
+    Ok(report)
+}
+
+/// Panel row opening the lists of the host.
+pub fn list_buttons(owner_id: UserId, channel_id: ChannelId) -> CreateActionRow {
+    let id = |action| CustomId::new(action, owner_id, channel_id).encode();
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(id(PanelAction::TrustedList))
+            .label("⭐ Trusted")
+            .style(ButtonStyle::Secondary),
+        CreateButton::new(id(PanelAction::BlockedList))
+            .label("🚫 Blocked")
+            .style(ButtonStyle::Secondary),
+    ])
+}
+
+/// List a panel button opens.
+pub fn shown_list(action: PanelAction) -> Option<MemberList> {
+    match action {
+        PanelAction::TrustedList => Some(MemberList::Trusted),
+        PanelAction::BlockedList => Some(MemberList::Blocked),
+        _ => None,
+    }
+}
+
+/// Current members of the list with selects adding and removing members.
+pub async fn menu(
+    repositories: &Repositories,
+    list: MemberList,
+    owner_id: UserId,
+    channel_id: ChannelId
+) -> Result<(String, Vec<CreateActionRow>), BotError> {
+    let owner = owner_id.get() as i64;
+    let members = repositories.lists.get_members(list, owner).await.map_err(|err| database_error(owner, err))?;
+    let (title, add, remove) = match list {
+        MemberList::Trusted => ("Trusted members, invited to every room you create", PanelAction::Trust, PanelAction::Untrust),
+        MemberList::Blocked => ("Blocked members, kept out of every room you create", PanelAction::Block, PanelAction::Unblock),
+    };
+    let mentions = match members.is_empty() {
+        true => "Nobody yet".to_string(),
+        false => members.iter().map(|id| UserId::new(*id as u64).mention().to_string()).collect::<Vec<_>>().join(" "),
+    };
+    let select = |action, placeholder: &str| CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(CustomId::new(action, owner_id, channel_id).encode(), CreateSelectMenuKind::User { default_users: None })
+            .placeholder(placeholder)
+            .min_values(1)
+            .max_values(MAX_PICKED_MEMBERS)
+    );
+
+    Ok((
+        format!("{}:\n{}", title, mentions),
+        vec![select(add, "Add members"), select(remove, "Remove members")],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::{ChannelId, GuildId, Permissions, UserId};
+
+    use super::{add_members, apply_to_room, remove_members};
+    use crate::services::fake_discord::{next_id, FakeCall, FakeDiscord};
+    use crate::services::gateway::DiscordGateway;
+    use crate::sql::autoroom::{MonitoredAutoRoom, RoomPrivacy};
+    use crate::sql::member_list::MemberList;
+    use crate::sql::prelude::Repositories;
+
+
+    struct Host {
+        discord: FakeDiscord,
+        repositories: Repositories,
+        guild_id: GuildId,
+        owner_id: UserId,
+    }
+
+    fn host() -> Host {
+        let discord = FakeDiscord::new();
+        let guild_id = discord.add_guild();
+        Host { discord, repositories: Repositories::in_memory(), guild_id, owner_id: UserId::new(next_id()) }
+    }
+
+    impl Host {
+        async fn open_room(&self) -> ChannelId {
+            let room_id = self.discord.add_voice_channel(self.guild_id, None, "alice`s room");
+            self.repositories.rooms.insert_many(&[MonitoredAutoRoom {
+                channel_id: room_id.get() as i64,
+                owner_id: self.owner_id.get() as i64,
+                autoroom_channel_id: None,
+            }]).await.unwrap();
+            self.discord.connect(self.guild_id, self.owner_id, room_id);
+            room_id
+        }
+
+        async fn list(&self, list: MemberList) -> Vec<i64> {
+            self.repositories.lists.get_members(list, self.owner_id.get() as i64).await.unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_are_applied_to_new_room() {
+        let host = host();
+        let (friend_id, pest_id) = (UserId::new(next_id()), UserId::new(next_id()));
+        let author_id = host.owner_id.get() as i64;
+        add_members(&host.discord, &host.repositories, host.guild_id, MemberList::Trusted, author_id, &[friend_id, host.owner_id]).await.unwrap();
+        add_members(&host.discord, &host.repositories, host.guild_id, MemberList::Blocked, author_id, &[pest_id]).await.unwrap();
+        assert_eq!(host.list(MemberList::Trusted).await, vec![friend_id.get() as i64]);
+
+        let room_id = host.discord.add_voice_channel(host.guild_id, None, "alice`s room");
+        host.discord.connect(host.guild_id, pest_id, room_id);
+        apply_to_room(&host.discord, &host.repositories, host.guild_id, room_id, RoomPrivacy::Locked, host.owner_id).await.unwrap();
+
+        let friend = host.discord.member_overwrite(room_id, friend_id).unwrap();
+        assert!(friend.allow.contains(Permissions::VIEW_CHANNEL | Permissions::CONNECT));
+        let pest = host.discord.member_overwrite(room_id, pest_id).unwrap();
+        assert_eq!(pest.deny, Permissions::VIEW_CHANNEL | Permissions::CONNECT);
+        assert_eq!(host.discord.voice_channel_id(host.guild_id, pest_id), None);
+    }
+
+    #[tokio::test]
+    async fn blocking_moves_member_between_lists_and_out_of_room() {
+        let host = host();
+        let room_id = host.open_room().await;
+        let guest_id = UserId::new(next_id());
+        let author_id = host.owner_id.get() as i64;
+        host.discord.connect(host.guild_id, guest_id, room_id);
+
+        add_members(&host.discord, &host.repositories, host.guild_id, MemberList::Trusted, author_id, &[guest_id]).await.unwrap();
+        let report = add_members(&host.discord, &host.repositories, host.guild_id, MemberList::Blocked, author_id, &[guest_id]).await.unwrap();
+        assert_eq!(report.done, vec![guest_id]);
+        assert!(host.list(MemberList::Trusted).await.is_empty());
+        assert_eq!(host.discord.voice_channel_id(host.guild_id, guest_id), None);
+
+        let report = remove_members(&host.discord, &host.repositories, host.guild_id, MemberList::Blocked, author_id, &[guest_id]).await.unwrap();
+        assert_eq!(report.done, vec![guest_id]);
+        assert!(host.list(MemberList::Blocked).await.is_empty());
+        assert!(host.discord.member_overwrite(room_id, guest_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn member_is_listed_only_when_room_takes_it() {
+        let host = host();
+        let room_id = host.open_room().await;
+        let (guest_id, other_id) = (UserId::new(next_id()), UserId::new(next_id()));
+        let author_id = host.owner_id.get() as i64;
+        add_members(&host.discord, &host.repositories, host.guild_id, MemberList::Blocked, author_id, &[other_id]).await.unwrap();
+
+        host.discord.fail(FakeCall::CreatePermission);
+        let report = add_members(&host.discord, &host.repositories, host.guild_id, MemberList::Blocked, author_id, &[guest_id]).await.unwrap();
+        assert_eq!(report.failed, vec![guest_id]);
+        assert_eq!(host.list(MemberList::Blocked).await, vec![other_id.get() as i64]);
+
+        host.discord.fail(FakeCall::DeletePermission);
+        let report = remove_members(&host.discord, &host.repositories, host.guild_id, MemberList::Blocked, author_id, &[other_id]).await.unwrap();
+        assert_eq!(report.failed, vec![other_id]);
+        assert_eq!(host.list(MemberList::Blocked).await, vec![other_id.get() as i64]);
+        assert!(host.discord.member_overwrite(room_id, other_id).is_some());
+    }
+}
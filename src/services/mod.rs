@@ -1 +1,21 @@
-pub mod autoroom;
\ No newline at end of file
+pub mod autoroom;
+pub mod channel_cache;
+pub mod channel_guard;
+pub mod cleanup;
+pub mod custom_id;
+#[cfg(test)]
+pub mod fake_discord;
+pub mod gateway;
+pub mod member_list;
+pub mod overflow;
+pub mod ownership;
+pub mod permanent_room;
+pub mod reconciler;
+pub mod rename_queue;
+pub mod room_creation;
+pub mod room_deletion;
+pub mod room_name;
+pub mod room_panel;
+pub mod voice_interface;
+pub mod voice_presence;
+pub mod voice_queue;
\ No newline at end of file
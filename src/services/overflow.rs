@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serenity::all::{ChannelId, ChannelType, CreateChannel, CreateMessage, GuildId, PermissionOverwrite};
+
+use crate::services::gateway::DiscordGateway;
+use crate::sql::prelude::{AutoRoom, Repositories};
+
+
+/// Discord limit of channels in one category
+pub const CATEGORY_CHANNEL_LIMIT: usize = 50;
+/// Discord limit of channels and categories in one guild
+pub const GUILD_CHANNEL_LIMIT: usize = 500;
+/// Admins are alerted once the guild has this many channels
+pub const GUILD_CHANNEL_ALERT: usize = 450;
+const ALERT_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+
+static LAST_ALERT: Lazy<Mutex<HashMap<u64, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+
+struct GuildLayout {
+    total: usize,
+    children: HashMap<ChannelId, usize>,
+    main_name: String,
+    main_position: u16,
+    main_permissions: Vec<PermissionOverwrite>,
+}
+
+fn guild_layout(discord: &dyn DiscordGateway, guild_id: GuildId, main_id: ChannelId) -> Option<GuildLayout> {
+    let channels = discord.guild_channels(guild_id)?;
+    let main = channels.iter().find(|channel| channel.id == main_id)?;
+
+    let mut children: HashMap<ChannelId, usize> = HashMap::new();
+    for parent_id in channels.iter().filter_map(|channel| channel.parent_id) {
+        *children.entry(parent_id).or_default() += 1;
+    }
+    Some(GuildLayout {
+        total: channels.len(),
+        children,
+        main_name: main.name.clone(),
+        main_position: main.position,
+        main_permissions: main.permission_overwrites.clone(),
+    })
+}
+
+/// Category the next room of the autoroom goes to: the main category, then the overflow
+/// categories in order, then a new numbered overflow category when all of them are full.
+pub async fn pick_category(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    autoroom: &AutoRoom
+) -> Result<ChannelId, String> {
+    let guild_id = GuildId::new(autoroom.guild_id as u64);
+    let main_id = ChannelId::new(autoroom.category_id as u64);
+    let layout = guild_layout(discord, guild_id, main_id)
+        .ok_or_else(|| format!("Category({}) not found in cache", main_id.get()))?;
+    let overflow = repositories.autorooms.get_overflow_categories(autoroom.channel_id)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    alert_channel_cap(discord, repositories, guild_id, layout.total).await;
+
+    let candidates = std::iter::once(main_id)
+        .chain(overflow.iter().map(|category| ChannelId::new(category.category_id as u64)));
+    for category_id in candidates {
+        match layout.children.get(&category_id) {
+            Some(count) if *count >= CATEGORY_CHANNEL_LIMIT => continue,
+            _ => return Ok(category_id),
+        }
+    }
+
+    // A new category and the room itself
+    if layout.total + 2 > GUILD_CHANNEL_LIMIT {
+        return Err(format!("Guild({}) reached the channel limit", guild_id.get()));
+    }
+
+    let number = overflow.len() + 2;
+    let name: String = format!("{} {}", layout.main_name, number).chars().take(100).collect();
+    tracing::info!(
+        "Creating overflow category AUTOROOM({}) GUILD({}) NAME({})",
+        autoroom.channel_id,
+        guild_id.get(),
+        name
+    );
+    let builder = CreateChannel::new(name)
+        .kind(ChannelType::Category)
+        .position(layout.main_position.saturating_add(number as u16 - 1))
+        .permissions(layout.main_permissions);
+    let category = discord.create_channel(guild_id, builder).await.map_err(|err| err.to_string())?;
+
+    if let Err(err) = repositories.autorooms.push_overflow_category(autoroom.channel_id, category.id.get() as i64, true).await {
+        let _ = discord.delete_channel(category.id).await;
+        return Err(err.to_string());
+    }
+    if let Err(err) = repositories.channels.record_created(guild_id.get() as i64, category.id.get() as i64, None).await {
+        tracing::error!("Failed to record created CATEGORY({}) GUILD({}).\n{}", category.id.get(), guild_id.get(), err);
+    }
+    Ok(category.id)
+}
+
+/// Tells the guild admins that the channel limit is close, at most once per [`ALERT_COOLDOWN`].
+pub async fn alert_channel_cap(discord: &dyn DiscordGateway, repositories: &Repositories, guild_id: GuildId, total: usize) {
+    if total < GUILD_CHANNEL_ALERT {
+        return;
+    }
+    {
+        let mut last_alert = LAST_ALERT.lock();
+        if last_alert.get(&guild_id.get()).is_some_and(|at| at.elapsed() < ALERT_COOLDOWN) {
+            return;
+        }
+        last_alert.insert(guild_id.get(), Instant::now());
+    }
+
+    tracing::warn!("Guild({}) is close to the channel limit: {}/{}", guild_id.get(), total, GUILD_CHANNEL_LIMIT);
+    let alert_channel_id = match repositories.settings.get(guild_id.get() as i64).await {
+        Ok(settings) => settings.alert_channel_id.map(|id| ChannelId::new(id as u64)),
+        Err(err) => {
+            tracing::error!("Failed to get guild settings GUILD({}).\n{}", guild_id.get(), err);
+            None
+        }
+    };
+    let alert_channel_id = match alert_channel_id.or_else(|| discord.system_channel_id(guild_id)) {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+
+    let message = format!(
+        "⚠️ The server has {} of {} channels. New rooms can't be created once the limit is reached, \
+        consider removing unused channels.",
+        total,
+        GUILD_CHANNEL_LIMIT
+    );
+    if let Err(err) = discord.send_message(alert_channel_id, CreateMessage::new().content(message)).await {
+        tracing::error!("Failed to send channel limit alert CHANNEL({}).\n{}", alert_channel_id.get(), err);
+    }
+}
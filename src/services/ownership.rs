@@ -0,0 +1,233 @@
+use serenity::all::{ChannelId, CreateMessage, GuildId, Mentionable, PermissionOverwriteType, UserId, VoiceState};
+
+use crate::services::autoroom::{grant_owner_privileges, invite_modal::deploy_claim_button, voice_channel::BotError};
+use crate::services::{channel_cache, gateway::DiscordGateway, voice_presence};
+use crate::sql::{guild_settings::OwnershipTransferMode, prelude::Repositories, repository::MonitoredRoomRepository};
+
+
+pub async fn transfer_ownership(
+    discord: &dyn DiscordGateway,
+    rooms: &dyn MonitoredRoomRepository,
+    channel_id: ChannelId,
+    old_owner_id: UserId,
+    new_owner_id: UserId
+) -> Result<(), BotError> {
+    tracing::info!(
+        "Transfer ownership CHANNEL({}) from OWNER({}) to OWNER({})",
+        channel_id.get(),
+        old_owner_id.get(),
+        new_owner_id.get()
+    );
+
+    // The row changes first, a failed grant puts the old owner back instead of leaving two hosts
+    match rooms.set_owner(channel_id.get() as i64, new_owner_id.get() as i64).await {
+        Ok(true) => (),
+        Ok(false) => return Err(BotError::MonitoredAutoRoomNotFound),
+        Err(err) => {
+            tracing::error!("transfer_ownership database error CHANNEL({}).\n{}", channel_id.get(), err);
+            return Err(BotError::DatabaseError);
+        }
+    };
+
+    if let Err(err) = grant_owner_privileges(discord, &channel_id, &new_owner_id).await {
+        tracing::error!("Failed to grant owner permissions CHANNEL({}) OWNER({}).\n{}", channel_id.get(), new_owner_id.get(), err);
+        if let Err(err) = rooms.set_owner(channel_id.get() as i64, old_owner_id.get() as i64).await {
+            tracing::error!("Failed to restore owner CHANNEL({}) OWNER({}).\n{}", channel_id.get(), old_owner_id.get(), err);
+        }
+        return Err(BotError::SerenityError);
+    }
+
+    if old_owner_id != new_owner_id {
+        if let Err(err) = discord.delete_permission(channel_id, PermissionOverwriteType::Member(old_owner_id)).await {
+            tracing::error!(
+                "Failed to revoke owner permissions CHANNEL({}) OWNER({}).\n{}",
+                channel_id.get(),
+                old_owner_id.get(),
+                err
+            );
+        }
+    }
+
+    let announcement = CreateMessage::new().content(format!("👑 {} is now the host of the room", new_owner_id.mention()));
+    if let Err(err) = discord.send_message(channel_id, announcement).await {
+        tracing::error!("Failed to announce new owner CHANNEL({}).\n{}", channel_id.get(), err);
+    }
+
+    Ok(())
+}
+
+/// Hands the room over when its owner leaves while other members stay,
+/// either directly or through a claim button depending on the guild settings.
+pub async fn on_owner_leave(discord: &dyn DiscordGateway, repositories: &Repositories, old: &VoiceState) -> Result<(), String> {
+    let (channel_id, guild_id) = match (old.channel_id, old.guild_id) {
+        (Some(channel_id), Some(guild_id)) => (channel_id, guild_id),
+        _ => return Ok(()),
+    };
+    if !channel_cache::is_monitored(repositories.rooms.as_ref(), channel_id).await.map_err(|err| err.to_string())? {
+        return Ok(());
+    }
+    let room = match repositories.rooms.get_by_channel_id(channel_id.get() as i64)
+        .await
+        .map_err(|err| err.to_string())? {
+        Some(room) => room,
+        None => return Ok(()),
+    };
+    if room.owner_id as u64 != old.user_id.get() {
+        return Ok(());
+    }
+
+    let members: Vec<UserId> = discord
+        .channel_members(guild_id, channel_id)
+        .ok_or_else(|| format!("Guild of CHANNEL({}) is not cached", channel_id))?
+        .into_iter()
+        .filter(|user_id| *user_id != old.user_id && !discord.is_bot(*user_id))
+        .collect();
+    if members.is_empty() {
+        return Ok(());
+    }
+
+    let settings = repositories.settings
+        .get(guild_id.get() as i64)
+        .await
+        .map_err(|err| err.to_string())?;
+    match settings.ownership_transfer {
+        OwnershipTransferMode::Auto => {
+            let new_owner_id = match voice_presence::longest_present(channel_id, &members) {
+                Some(user_id) => user_id,
+                None => return Ok(()),
+            };
+            transfer_ownership(discord, repositories.rooms.as_ref(), channel_id, old.user_id, new_owner_id)
+                .await
+                .map_err(|err| err.to_string())
+        },
+        OwnershipTransferMode::Claim => {
+            deploy_claim_button(discord, channel_id, old.user_id)
+                .await
+                .map_err(|err| err.to_string())
+        },
+    }
+}
+
+/// Gives the room to `claimer_id` if they are inside it and the current owner is not.
+pub async fn claim(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    claimer_id: UserId
+) -> Result<(), BotError> {
+    let room = match repositories.rooms.get_by_channel_id(channel_id.get() as i64).await {
+        Ok(Some(room)) => room,
+        Ok(None) => return Err(BotError::MonitoredAutoRoomNotFound),
+        Err(err) => {
+            tracing::error!("claim database error CHANNEL({}) CLAIMER({}).\n{}", channel_id.get(), claimer_id.get(), err);
+            return Err(BotError::DatabaseError);
+        }
+    };
+    let owner_id = UserId::new(room.owner_id as u64);
+
+    if discord.voice_channel_id(guild_id, claimer_id) != Some(channel_id) {
+        return Err(BotError::NotInRoom);
+    }
+    if owner_id == claimer_id || discord.voice_channel_id(guild_id, owner_id) == Some(channel_id) {
+        return Err(BotError::OwnerPresent);
+    }
+
+    transfer_ownership(discord, repositories.rooms.as_ref(), channel_id, owner_id, claimer_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serenity::all::{Permissions, UserId};
+
+    use super::{on_owner_leave, transfer_ownership};
+    use crate::services::autoroom::voice_channel::BotError;
+    use crate::services::fake_discord::{next_id, voice_state, FakeCall, FakeDiscord};
+    use crate::services::gateway::DiscordGateway;
+    use crate::sql::autoroom::MonitoredAutoRoom;
+    use crate::sql::guild_settings::{GuildSettings, OwnershipTransferMode};
+    use crate::sql::memory::InMemoryGuildSettingsRepository;
+    use crate::sql::prelude::Repositories;
+
+
+    #[tokio::test]
+    async fn failed_grant_keeps_the_old_owner() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let room_id = discord.add_voice_channel(guild_id, None, "alice`s room");
+        let owner_id = UserId::new(next_id());
+        let guest_id = UserId::new(next_id());
+        repositories.rooms.insert_many(&[MonitoredAutoRoom {
+            channel_id: room_id.get() as i64,
+            owner_id: owner_id.get() as i64,
+            autoroom_channel_id: None,
+        }]).await.unwrap();
+
+        discord.fail(FakeCall::CreatePermission);
+        let result = transfer_ownership(&discord, repositories.rooms.as_ref(), room_id, owner_id, guest_id).await;
+
+        assert!(matches!(result, Err(BotError::SerenityError)));
+        let room = repositories.rooms.get_by_channel_id(room_id.get() as i64).await.unwrap().unwrap();
+        assert_eq!(room.owner_id, owner_id.get() as i64);
+        assert!(discord.member_overwrite(room_id, guest_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn transfer_moves_owner_privileges() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let room_id = discord.add_voice_channel(guild_id, None, "alice`s room");
+        let owner_id = UserId::new(next_id());
+        let guest_id = UserId::new(next_id());
+        repositories.rooms.insert_many(&[MonitoredAutoRoom {
+            channel_id: room_id.get() as i64,
+            owner_id: owner_id.get() as i64,
+            autoroom_channel_id: None,
+        }]).await.unwrap();
+
+        transfer_ownership(&discord, repositories.rooms.as_ref(), room_id, owner_id, guest_id).await.unwrap();
+
+        let room = repositories.rooms.get_by_channel_id(room_id.get() as i64).await.unwrap().unwrap();
+        assert_eq!(room.owner_id, guest_id.get() as i64);
+        assert!(discord.member_overwrite(room_id, guest_id).unwrap().allow.contains(Permissions::MANAGE_CHANNELS));
+        assert!(discord.member_overwrite(room_id, owner_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn owner_leave_in_claim_mode_offers_the_room() {
+        let discord = FakeDiscord::new();
+        let settings = Arc::new(InMemoryGuildSettingsRepository::default());
+        let repositories = Repositories::in_memory_with(Arc::default(), settings.clone());
+        let guild_id = discord.add_guild();
+        let mut claim = GuildSettings::new(guild_id.get() as i64);
+        claim.ownership_transfer = OwnershipTransferMode::Claim;
+        settings.set(claim);
+
+        let room_id = discord.add_voice_channel(guild_id, None, "alice`s room");
+        let owner_id = UserId::new(next_id());
+        let guest_id = UserId::new(next_id());
+        repositories.rooms.insert_many(&[MonitoredAutoRoom {
+            channel_id: room_id.get() as i64,
+            owner_id: owner_id.get() as i64,
+            autoroom_channel_id: None,
+        }]).await.unwrap();
+        discord.connect(guild_id, guest_id, room_id);
+
+        let old = voice_state(guild_id, owner_id, "alice", room_id);
+        on_owner_leave(&discord, &repositories, &old).await.unwrap();
+
+        let room = repositories.rooms.get_by_channel_id(room_id.get() as i64).await.unwrap().unwrap();
+        assert_eq!(room.owner_id, owner_id.get() as i64);
+        assert_eq!(discord.messages(room_id).len(), 1);
+
+        // The last member leaving offers nothing
+        discord.disconnect_member(guild_id, guest_id).await.unwrap();
+        let old = voice_state(guild_id, guest_id, "bob", room_id);
+        on_owner_leave(&discord, &repositories, &old).await.unwrap();
+        assert_eq!(discord.messages(room_id).len(), 1);
+    }
+}
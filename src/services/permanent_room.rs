@@ -0,0 +1,364 @@
+use serenity::all::{
+    ChannelId, ChannelType, CreateChannel, EditChannel, GuildChannel, GuildId,
+    PermissionOverwrite, PermissionOverwriteType, Permissions, PremiumTier, RoleId, User, UserId, VoiceState
+};
+
+use crate::bitrate::get_bitrate;
+use crate::services::autoroom::{grant_owner_privileges, set_everyone_deny};
+use crate::services::{channel_cache, gateway::DiscordGateway, room_panel};
+use crate::sql::{autoroom::PermamentAutoRoom, prelude::Repositories};
+
+
+#[derive(thiserror::Error, Debug)]
+pub enum PermanentRoomError {
+    #[error("Permanent room was not found")]
+    NotFound,
+
+    #[error("{0}")]
+    Rejected(&'static str),
+
+    #[error("Only the room owner can do this")]
+    NotOwner,
+
+    #[error("Internal server error. Please try again later")]
+    DatabaseError,
+
+    #[error("Could not reach Discord. Please try again later")]
+    SerenityError,
+}
+
+/// Moves the room into its placement category and lifts the `@everyone` view deny set by [`store_room`].
+pub async fn place_room(discord: &dyn DiscordGateway, channel: &GuildChannel, room: &PermamentAutoRoom) -> Result<(), serenity::Error> {
+    let placement_id = ChannelId::new(room.placement_category_id as u64);
+    tracing::info!("Placing permanent room CHANNEL({}) to CATEGORY({})", channel.id.get(), placement_id.get());
+
+    discord.edit_channel(channel.id, EditChannel::new().category(placement_id)).await?;
+    set_everyone_deny(discord, channel, Permissions::VIEW_CHANNEL, false).await
+}
+
+/// Hides the room from `@everyone` and moves it into its storage category.
+/// Member overwrites are left untouched, so the owner and guests still see the room.
+pub async fn store_room(discord: &dyn DiscordGateway, channel: &GuildChannel, room: &PermamentAutoRoom) -> Result<(), serenity::Error> {
+    let storage_id = ChannelId::new(room.storage_category_id as u64);
+    tracing::info!("Storing permanent room CHANNEL({}) to CATEGORY({})", channel.id.get(), storage_id.get());
+
+    set_everyone_deny(discord, channel, Permissions::VIEW_CHANNEL, true).await?;
+    discord.edit_channel(channel.id, EditChannel::new().category(storage_id)).await?;
+    Ok(())
+}
+
+pub async fn on_join(discord: &dyn DiscordGateway, repositories: &Repositories, new: &VoiceState) -> Result<(), String> {
+    let channel_id = match new.channel_id {
+        Some(channel_id) => channel_id,
+        None => return Ok(()),
+    };
+    // Most voice events are about other channels, the cache answers them without the database
+    if !channel_cache::is_permanent(repositories.permanent.as_ref(), channel_id).await.map_err(|err| err.to_string())? {
+        return Ok(());
+    }
+    let room = match repositories.permanent.get_by_channel_id(channel_id.get() as i64)
+        .await
+        .map_err(|err| err.to_string())? {
+        Some(room) => room,
+        None => return Ok(()),
+    };
+
+    let channel = match discord.channel(channel_id).await.map_err(|err| err.to_string())? {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+    if channel.parent_id.map(|id| id.get() as i64) == Some(room.placement_category_id) {
+        return Ok(());
+    }
+
+    place_room(discord, &channel, &room).await.map_err(|err| err.to_string())
+}
+
+pub async fn on_leave(discord: &dyn DiscordGateway, repositories: &Repositories, old: &VoiceState) -> Result<(), String> {
+    let channel_id = match old.channel_id {
+        Some(channel_id) => channel_id,
+        None => return Ok(()),
+    };
+    // Most voice events are about other channels, the cache answers them without the database
+    if !channel_cache::is_permanent(repositories.permanent.as_ref(), channel_id).await.map_err(|err| err.to_string())? {
+        return Ok(());
+    }
+    let room = match repositories.permanent.get_by_channel_id(channel_id.get() as i64)
+        .await
+        .map_err(|err| err.to_string())? {
+        Some(room) => room,
+        None => return Ok(()),
+    };
+
+    let channel = match discord.channel(channel_id).await.map_err(|err| err.to_string())? {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+    let members = discord
+        .channel_members(channel.guild_id, channel.id)
+        .ok_or_else(|| format!("Guild of permanent room CHANNEL({}) is not cached", channel.id))?;
+    if !members.is_empty() || channel.parent_id.map(|id| id.get() as i64) == Some(room.storage_category_id) {
+        return Ok(());
+    }
+
+    store_room(discord, &channel, &room).await.map_err(|err| err.to_string())
+}
+
+pub async fn create_room(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    owner: &User,
+    name: String,
+    placement_category_id: ChannelId,
+    storage_category_id: ChannelId,
+) -> Result<GuildChannel, PermanentRoomError> {
+    let max_bitrate = get_bitrate(&discord.premium_tier(guild_id).unwrap_or(PremiumTier::Tier0));
+
+    let builder = CreateChannel::new(name)
+        .category(storage_category_id)
+        .kind(ChannelType::Voice)
+        .bitrate(max_bitrate)
+        .permissions(vec![PermissionOverwrite {
+            allow: Permissions::empty(),
+            deny: Permissions::VIEW_CHANNEL,
+            kind: PermissionOverwriteType::Role(RoleId::new(guild_id.get())),
+        }]);
+    let channel = discord.create_channel(guild_id, builder).await.map_err(|err| {
+        tracing::error!("Failed to create permanent room GUILD({}) OWNER({}).\n{}", guild_id, owner.id, err);
+        PermanentRoomError::SerenityError
+    })?;
+
+    let room = PermamentAutoRoom {
+        channel_id: channel.id.get() as i64,
+        guild_id: guild_id.get() as i64,
+        owner_id: owner.id.get() as i64,
+        placement_category_id: placement_category_id.get() as i64,
+        storage_category_id: storage_category_id.get() as i64,
+    };
+
+    let setup = async {
+        grant_owner_privileges(discord, &channel.id, &owner.id)
+            .await
+            .map_err(|_| PermanentRoomError::SerenityError)?;
+        repositories.permanent.create(&room).await.map_err(PermanentRoomError::Rejected)
+    };
+    if let Err(err) = setup.await {
+        let _ = discord.delete_channel(channel.id).await;
+        return Err(err);
+    }
+    channel_cache::set_permanent(channel.id, true);
+
+    if let Err(err) = room_panel::deploy(discord, repositories.rooms.as_ref(), channel.id, owner.id).await {
+        tracing::error!(
+            "Failed to deploy menu to permanent room CHANNEL({}) OWNER({}).\nError: \"{:?}\"",
+            channel.id,
+            owner.id,
+            err
+        );
+    }
+
+    Ok(channel)
+}
+
+pub async fn delete_room(discord: &dyn DiscordGateway, repositories: &Repositories, room: &PermamentAutoRoom) -> Result<(), PermanentRoomError> {
+    let channel_id = ChannelId::new(room.channel_id as u64);
+    tracing::info!("Deleting permanent room CHANNEL({}) OWNER({})", room.channel_id, room.owner_id);
+
+    if let Err(err) = discord.delete_channel(channel_id).await {
+        // The channel may already be gone, the row has to be removed either way
+        tracing::warn!("Failed to delete permanent room CHANNEL({}).\n{}", room.channel_id, err);
+    }
+
+    repositories.permanent
+        .remove(room.channel_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to remove permanent room CHANNEL({}).\n{}", room.channel_id, err);
+            PermanentRoomError::DatabaseError
+        })?;
+    channel_cache::set_permanent(channel_id, false);
+    Ok(())
+}
+
+pub async fn transfer_room(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    room: &PermamentAutoRoom,
+    new_owner_id: UserId,
+) -> Result<(), PermanentRoomError> {
+    let channel_id = ChannelId::new(room.channel_id as u64);
+    let old_owner_id = UserId::new(room.owner_id as u64);
+    tracing::info!(
+        "Transfer permanent room CHANNEL({}) from OWNER({}) to OWNER({})",
+        room.channel_id,
+        room.owner_id,
+        new_owner_id
+    );
+
+    // The row changes first, a failed grant puts the old owner back instead of leaving two hosts
+    match repositories.permanent.set_owner(room.channel_id, new_owner_id.get() as i64).await {
+        Ok(true) => (),
+        Ok(false) => return Err(PermanentRoomError::NotFound),
+        Err(err) => {
+            if let sqlx::Error::Database(db_err) = &err {
+                if db_err.code() == Some("23505".into()) {
+                    return Err(PermanentRoomError::Rejected("Member already owns a permanent room"));
+                }
+            }
+            tracing::error!("Failed to transfer permanent room CHANNEL({}).\n{}", room.channel_id, err);
+            return Err(PermanentRoomError::DatabaseError);
+        }
+    };
+
+    if let Err(err) = grant_owner_privileges(discord, &channel_id, &new_owner_id).await {
+        tracing::error!("Failed to grant owner permissions CHANNEL({}) OWNER({}).\n{}", room.channel_id, new_owner_id, err);
+        if let Err(err) = repositories.permanent.set_owner(room.channel_id, room.owner_id).await {
+            tracing::error!("Failed to restore permanent room CHANNEL({}) OWNER({}).\n{}", room.channel_id, room.owner_id, err);
+        }
+        return Err(PermanentRoomError::SerenityError);
+    }
+
+    if old_owner_id != new_owner_id {
+        if let Err(err) = discord.delete_permission(channel_id, PermissionOverwriteType::Member(old_owner_id)).await {
+            tracing::error!(
+                "Failed to revoke owner permissions CHANNEL({}) OWNER({}).\n{}",
+                room.channel_id,
+                room.owner_id,
+                err
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Brings every permanent room in line with its voice state: rows of deleted channels are removed,
+/// empty rooms go to storage and occupied rooms go to placement.
+pub async fn reconcile_permanent_rooms(discord: &dyn DiscordGateway, repositories: &Repositories) -> Result<(), String> {
+    tracing::info!("Starting permanent rooms reconciliation");
+    let rooms = repositories.permanent
+        .get_all()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut outdated: Vec<i64> = Vec::new();
+    let (mut placed, mut stored) = (0, 0);
+    for room in rooms {
+        // Only a channel Discord doesn't know is gone, other failures leave the room for the next run
+        let channel = match discord.channel(ChannelId::new(room.channel_id as u64)).await {
+            Ok(Some(channel)) => channel,
+            Ok(None) => {
+                tracing::warn!("Permanent room CHANNEL({}) not found", room.channel_id);
+                outdated.push(room.channel_id);
+                continue;
+            },
+            Err(err) => {
+                tracing::error!("Failed to fetch permanent room CHANNEL({}).\n{}", room.channel_id, err);
+                continue;
+            }
+        };
+
+        let members = match discord.channel_members(channel.guild_id, channel.id) {
+            Some(members) => members,
+            None => {
+                tracing::warn!("Guild of permanent room CHANNEL({}) is not cached", room.channel_id);
+                continue;
+            }
+        };
+        let parent_id = channel.parent_id.map(|id| id.get() as i64);
+        let result = if members.is_empty() && parent_id != Some(room.storage_category_id) {
+            stored += 1;
+            store_room(discord, &channel, &room).await
+        } else if !members.is_empty() && parent_id != Some(room.placement_category_id) {
+            placed += 1;
+            place_room(discord, &channel, &room).await
+        } else {
+            Ok(())
+        };
+        if let Err(err) = result {
+            tracing::error!("Failed to reconcile permanent room CHANNEL({}).\n{}", room.channel_id, err);
+        }
+    }
+
+    if !outdated.is_empty() {
+        repositories.permanent
+            .remove_many(&outdated)
+            .await
+            .map_err(|err| err.to_string())?;
+        for channel_id in &outdated {
+            channel_cache::set_permanent(ChannelId::new(*channel_id as u64), false);
+        }
+    }
+
+    tracing::info!(
+        "[Permanent rooms] Completed | Removed: {} | Placed: {} | Stored: {}",
+        outdated.len(),
+        placed,
+        stored
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serenity::all::{ChannelId, UserId};
+
+    use super::{on_join, on_leave, reconcile_permanent_rooms};
+    use crate::services::fake_discord::{next_id, voice_state, FakeDiscord};
+    use crate::services::gateway::DiscordGateway;
+    use crate::sql::autoroom::PermamentAutoRoom;
+    use crate::sql::memory::InMemoryPermanentRoomRepository;
+    use crate::sql::prelude::Repositories;
+
+
+    #[tokio::test]
+    async fn room_follows_its_members() {
+        let discord = FakeDiscord::new();
+        let permanent = Arc::new(InMemoryPermanentRoomRepository::default());
+        let repositories = Repositories::in_memory_with(permanent.clone(), Arc::default());
+        let guild_id = discord.add_guild();
+        let placement_id = discord.add_category(guild_id, "Rooms");
+        let storage_id = discord.add_category(guild_id, "Storage");
+        let room_id = discord.add_voice_channel(guild_id, Some(storage_id), "alice`s room");
+        let owner_id = UserId::new(next_id());
+        permanent.insert(PermamentAutoRoom {
+            channel_id: room_id.get() as i64,
+            guild_id: guild_id.get() as i64,
+            owner_id: owner_id.get() as i64,
+            placement_category_id: placement_id.get() as i64,
+            storage_category_id: storage_id.get() as i64,
+        });
+
+        discord.connect(guild_id, owner_id, room_id);
+        let state = voice_state(guild_id, owner_id, "alice", room_id);
+        on_join(&discord, &repositories, &state).await.unwrap();
+        assert_eq!(discord.get(room_id).unwrap().parent_id, Some(placement_id));
+
+        discord.disconnect_member(guild_id, owner_id).await.unwrap();
+        on_leave(&discord, &repositories, &state).await.unwrap();
+        assert_eq!(discord.get(room_id).unwrap().parent_id, Some(storage_id));
+    }
+
+    #[tokio::test]
+    async fn reconcile_removes_rows_of_deleted_rooms() {
+        let discord = FakeDiscord::new();
+        let permanent = Arc::new(InMemoryPermanentRoomRepository::default());
+        let repositories = Repositories::in_memory_with(permanent.clone(), Arc::default());
+        let guild_id = discord.add_guild();
+        let storage_id = discord.add_category(guild_id, "Storage");
+        let gone_id = ChannelId::new(next_id());
+        permanent.insert(PermamentAutoRoom {
+            channel_id: gone_id.get() as i64,
+            guild_id: guild_id.get() as i64,
+            owner_id: next_id() as i64,
+            placement_category_id: storage_id.get() as i64,
+            storage_category_id: storage_id.get() as i64,
+        });
+
+        reconcile_permanent_rooms(&discord, &repositories).await.unwrap();
+        assert!(repositories.permanent.get_all().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serenity::all::Context;
+
+use crate::services::autoroom::{cleanup_categories_monitored_rooms, cleanup_db_monitored_rooms};
+use crate::services::cleanup::{CleanupOptions, CleanupReport};
+use crate::sql::reconcile_run::{ReconcileCounts, ReconcileLock, ReconcileRun};
+use crate::sql::repository::{context_pool, Repositories};
+
+
+const DEFAULT_INTERVAL_SECS: u64 = 15 * 60;
+const DEFAULT_JITTER_SECS: u64 = 60;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Schedule of the background reconciliation.
+#[derive(Debug, Clone, Copy)]
+pub struct ReconcilerConfig {
+    pub interval: Duration,
+    /// Up to this much is added to every interval, so instances started together don't compete for the lock
+    pub jitter: Duration,
+}
+
+fn env_secs(name: &str, default: u64) -> u64 {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!("{} is not a number of seconds: \"{}\", using {}", name, value, default);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Uniform number in `[0, 1)`, precise enough to spread the runs.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl ReconcilerConfig {
+    /// Reads `RECONCILE_INTERVAL_SECS` and `RECONCILE_JITTER_SECS`, `None` when the interval is `0`.
+    pub fn from_env() -> Option<Self> {
+        let interval = env_secs("RECONCILE_INTERVAL_SECS", DEFAULT_INTERVAL_SECS);
+        if interval == 0 {
+            return None;
+        }
+        Some(Self {
+            interval: Duration::from_secs(interval),
+            jitter: Duration::from_secs(env_secs("RECONCILE_JITTER_SECS", DEFAULT_JITTER_SECS)),
+        })
+    }
+
+    fn next_delay(&self) -> Duration {
+        self.interval + self.jitter.mul_f64(random_fraction())
+    }
+}
+
+/// Starts the scheduler once per process, `ready` fires again on every reconnect.
+pub fn start(ctx: &Context, config: ReconcilerConfig) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tracing::info!("Reconciler started INTERVAL({:?}) JITTER({:?})", config.interval, config.jitter);
+
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(config.next_delay()).await;
+            run(&ctx).await;
+        }
+    });
+}
+
+fn counts(report: &CleanupReport) -> ReconcileCounts {
+    ReconcileCounts {
+        stale_rows: report.stale_rows.len() as i32,
+        deleted: report.deleted.len() as i32,
+        adopted: report.adopted.len() as i32,
+        skipped: report.skipped.len() as i32,
+    }
+}
+
+async fn reconcile(ctx: &Context, repositories: &Repositories) -> Result<CleanupReport, String> {
+    let options = CleanupOptions::default();
+    let mut report = cleanup_db_monitored_rooms(ctx, repositories, &options).await?;
+    report.merge(cleanup_categories_monitored_rooms(ctx, repositories, &options).await?);
+    Ok(report)
+}
+
+/// Runs the reconciliation while holding [`ReconcileLock`], `None` when another instance or command holds it.
+/// Every entry point takes the lock, so two cleanups never delete and adopt the same channels at once.
+pub async fn exclusive<T>(ctx: &Context, reconciliation: impl Future<Output = T>) -> Result<Option<T>, sqlx::Error> {
+    let pool = context_pool(ctx).await;
+    let lock = match ReconcileLock::try_acquire(&pool).await? {
+        Some(lock) => lock,
+        None => return Ok(None),
+    };
+    let result = reconciliation.await;
+    if let Err(err) = lock.release().await {
+        tracing::error!("[Reconciler] Failed to release the lock.\n{}", err);
+    }
+    Ok(Some(result))
+}
+
+/// Runs the DB and category reconcilers when no other instance is running them, and records the run.
+pub async fn run(ctx: &Context) {
+    match exclusive(ctx, recorded_run(ctx)).await {
+        Ok(Some(())) => (),
+        Ok(None) => tracing::info!("[Reconciler] Another instance is reconciling, skipped"),
+        Err(err) => tracing::error!("[Reconciler] Failed to take the lock.\n{}", err),
+    };
+}
+
+async fn recorded_run(ctx: &Context) {
+    let pool = context_pool(ctx).await;
+    let run_id = match ReconcileRun::start(&pool).await {
+        Ok(id) => Some(id),
+        Err(err) => {
+            tracing::error!("[Reconciler] Failed to record the run.\n{}", err);
+            None
+        }
+    };
+
+    let repositories = Repositories::from_context(ctx).await;
+    let result = reconcile(ctx, &repositories).await;
+    let (counts, error) = match &result {
+        Ok(report) => (counts(report), None),
+        Err(err) => (ReconcileCounts::default(), Some(err.as_str())),
+    };
+    match error {
+        None => tracing::info!(
+            "[Reconciler] Completed | Stale rows: {} | Deleted: {} | Adopted: {} | Skipped: {}",
+            counts.stale_rows,
+            counts.deleted,
+            counts.adopted,
+            counts.skipped
+        ),
+        Some(err) => tracing::error!("[Reconciler] Failed.\n{}", err),
+    };
+
+    if let Some(run_id) = run_id {
+        if let Err(err) = ReconcileRun::finish(&pool, run_id, &counts, error).await {
+            tracing::error!("[Reconciler] Failed to record the end of RUN({}).\n{}", run_id, err);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serenity::all::{ChannelId, Context, EditChannel};
+
+use crate::services::gateway::{is_rate_limited, DiscordGateway};
+
+
+/// Discord lets a channel be renamed twice per ten minutes, further renames wait for the rate limit
+const RENAME_WINDOW: Duration = Duration::from_secs(10 * 60);
+const RENAMES_PER_WINDOW: usize = 2;
+/// How often queued names are checked, they are applied at most this late
+const WORKER_TICK: Duration = Duration::from_secs(5);
+/// Serenity sleeps inside a rate limited request until the limit lifts, a rename taking longer is given up and queued
+const RENAME_TIMEOUT: Duration = Duration::from_secs(5);
+/// Discord refused a rename the bot expected to pass, the channel was renamed by somebody else meanwhile
+const LIMITED_RETRY: Duration = Duration::from_secs(60);
+
+static QUEUE: Lazy<Mutex<RenameQueue>> = Lazy::new(|| Mutex::new(RenameQueue::default()));
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// When a requested rename can be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameSlot {
+    Now,
+    /// The name is queued and applied after the delay, replacing a name queued before
+    Queued(Duration),
+}
+
+#[derive(Default)]
+struct ChannelRenames {
+    /// Renames made by the bot within the last window, oldest first
+    recent: VecDeque<Instant>,
+    /// Discord refused a rename, nothing is tried before this
+    blocked_until: Option<Instant>,
+    pending: Option<String>,
+}
+
+impl ChannelRenames {
+    fn forget_expired(&mut self, now: Instant) {
+        while self.recent.front().is_some_and(|renamed_at| *renamed_at + RENAME_WINDOW <= now) {
+            self.recent.pop_front();
+        }
+    }
+
+    fn next_slot(&self, now: Instant) -> Instant {
+        let slot = match self.recent.len() < RENAMES_PER_WINDOW {
+            true => now,
+            false => self.recent[self.recent.len() - RENAMES_PER_WINDOW] + RENAME_WINDOW,
+        };
+        self.blocked_until.map_or(slot, |blocked_until| slot.max(blocked_until))
+    }
+
+    fn is_idle(&self, now: Instant) -> bool {
+        self.pending.is_none()
+            && self.recent.is_empty()
+            && self.blocked_until.is_none_or(|blocked_until| blocked_until <= now)
+    }
+}
+
+/// Renames of every room, kept per channel.
+#[derive(Default)]
+struct RenameQueue {
+    channels: HashMap<ChannelId, ChannelRenames>,
+}
+
+impl RenameQueue {
+    /// `Now` when the channel has a free slot, otherwise queues the name.
+    /// The slot is only taken by [`Self::renamed`], once Discord accepted the rename.
+    fn request(&mut self, channel_id: ChannelId, name: &str, now: Instant) -> RenameSlot {
+        let renames = self.channels.entry(channel_id).or_default();
+        renames.forget_expired(now);
+        let slot = renames.next_slot(now);
+        if slot <= now {
+            renames.pending = None;
+            return RenameSlot::Now;
+        }
+        renames.pending = Some(name.to_string());
+        RenameSlot::Queued(slot - now)
+    }
+
+    fn renamed(&mut self, channel_id: ChannelId, now: Instant) {
+        let renames = self.channels.entry(channel_id).or_default();
+        renames.recent.push_back(now);
+        renames.blocked_until = None;
+    }
+
+    /// Discord refused the rename, it is retried later unless a newer name was queued meanwhile.
+    fn limited(&mut self, channel_id: ChannelId, name: &str, now: Instant) -> RenameSlot {
+        let renames = self.channels.entry(channel_id).or_default();
+        renames.blocked_until = Some(now + LIMITED_RETRY);
+        renames.pending.get_or_insert_with(|| name.to_string());
+        RenameSlot::Queued(renames.next_slot(now) - now)
+    }
+
+    /// Queued names whose slot came, they are queued again when Discord refuses them.
+    fn take_due(&mut self, now: Instant) -> Vec<(ChannelId, String)> {
+        let mut due = Vec::new();
+        for (channel_id, renames) in self.channels.iter_mut() {
+            renames.forget_expired(now);
+            if renames.pending.is_some() && renames.next_slot(now) <= now {
+                due.push((*channel_id, renames.pending.take().unwrap()));
+            }
+        }
+        self.channels.retain(|_, renames| !renames.is_idle(now));
+        due
+    }
+}
+
+enum RenameError {
+    Limited,
+    Failed(serenity::Error),
+}
+
+async fn try_rename(discord: &dyn DiscordGateway, channel_id: ChannelId, name: &str) -> Result<(), RenameError> {
+    match tokio::time::timeout(RENAME_TIMEOUT, discord.edit_channel(channel_id, EditChannel::new().name(name))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(err)) if is_rate_limited(&err) => Err(RenameError::Limited),
+        Ok(Err(err)) => Err(RenameError::Failed(err)),
+        Err(_) => Err(RenameError::Limited),
+    }
+}
+
+/// Renames the channel right away when a slot is free, otherwise queues the name.
+/// A rename Discord rate limits is queued as well.
+pub async fn rename(discord: &dyn DiscordGateway, channel_id: ChannelId, name: &str) -> Result<RenameSlot, serenity::Error> {
+    let slot = QUEUE.lock().request(channel_id, name, Instant::now());
+    if slot != RenameSlot::Now {
+        return Ok(slot);
+    }
+    match try_rename(discord, channel_id, name).await {
+        Ok(()) => {
+            QUEUE.lock().renamed(channel_id, Instant::now());
+            Ok(RenameSlot::Now)
+        },
+        Err(RenameError::Limited) => {
+            tracing::warn!("Rename rate limited, queued CHANNEL({}) NAME({})", channel_id, name);
+            Ok(QUEUE.lock().limited(channel_id, name, Instant::now()))
+        },
+        Err(RenameError::Failed(err)) => Err(err),
+    }
+}
+
+/// Applies the queued names whose slot came, each in its own task so a rate limited channel holds up nothing else.
+pub fn apply_due(discord: Arc<dyn DiscordGateway>) {
+    let due = QUEUE.lock().take_due(Instant::now());
+    for (channel_id, name) in due {
+        let discord = discord.clone();
+        tokio::spawn(async move {
+            tracing::info!("Applying queued rename CHANNEL({}) NAME({})", channel_id, name);
+            match try_rename(discord.as_ref(), channel_id, &name).await {
+                Ok(()) => QUEUE.lock().renamed(channel_id, Instant::now()),
+                Err(RenameError::Limited) => {
+                    tracing::warn!("Queued rename rate limited again CHANNEL({}) NAME({})", channel_id, name);
+                    QUEUE.lock().limited(channel_id, &name, Instant::now());
+                },
+                Err(RenameError::Failed(err)) => {
+                    tracing::error!("Failed to apply queued rename CHANNEL({}) NAME({}).\n{}", channel_id, name, err);
+                },
+            };
+        });
+    }
+}
+
+/// Starts the worker once per process, `ready` fires again on every reconnect.
+pub fn start(ctx: &Context) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let discord: Arc<dyn DiscordGateway> = Arc::new(ctx.clone());
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(WORKER_TICK).await;
+            apply_due(discord.clone());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use serenity::all::ChannelId;
+
+    use super::{rename, RenameQueue, RenameSlot, LIMITED_RETRY, QUEUE, RENAME_WINDOW};
+    use crate::services::fake_discord::{FakeCall, FakeDiscord};
+
+
+    /// Request that Discord accepts.
+    fn accepted(queue: &mut RenameQueue, channel_id: ChannelId, name: &str, now: Instant) -> RenameSlot {
+        let slot = queue.request(channel_id, name, now);
+        if slot == RenameSlot::Now {
+            queue.renamed(channel_id, now);
+        }
+        slot
+    }
+
+    #[test]
+    fn third_rename_waits_for_the_window() {
+        let mut queue = RenameQueue::default();
+        let channel_id = ChannelId::new(1);
+        let start = Instant::now();
+
+        assert_eq!(accepted(&mut queue, channel_id, "one", start), RenameSlot::Now);
+        assert_eq!(accepted(&mut queue, channel_id, "two", start + Duration::from_secs(60)), RenameSlot::Now);
+        assert_eq!(
+            accepted(&mut queue, channel_id, "three", start + Duration::from_secs(120)),
+            RenameSlot::Queued(RENAME_WINDOW - Duration::from_secs(120))
+        );
+        assert!(queue.take_due(start + Duration::from_secs(300)).is_empty());
+    }
+
+    #[test]
+    fn latest_queued_name_wins() {
+        let mut queue = RenameQueue::default();
+        let channel_id = ChannelId::new(2);
+        let start = Instant::now();
+        accepted(&mut queue, channel_id, "one", start);
+        accepted(&mut queue, channel_id, "two", start);
+        accepted(&mut queue, channel_id, "three", start);
+        accepted(&mut queue, channel_id, "four", start + Duration::from_secs(30));
+
+        assert_eq!(queue.take_due(start + RENAME_WINDOW), vec![(channel_id, "four".to_string())]);
+        queue.renamed(channel_id, start + RENAME_WINDOW);
+        // The applied name took one of the two freed slots
+        assert_eq!(accepted(&mut queue, channel_id, "five", start + RENAME_WINDOW), RenameSlot::Now);
+        assert!(matches!(accepted(&mut queue, channel_id, "six", start + RENAME_WINDOW), RenameSlot::Queued(_)));
+    }
+
+    #[test]
+    fn channels_are_limited_separately() {
+        let mut queue = RenameQueue::default();
+        let start = Instant::now();
+        accepted(&mut queue, ChannelId::new(3), "one", start);
+        accepted(&mut queue, ChannelId::new(3), "two", start);
+
+        assert_eq!(accepted(&mut queue, ChannelId::new(4), "one", start), RenameSlot::Now);
+        assert!(queue.take_due(start + RENAME_WINDOW).is_empty());
+        assert!(queue.channels.is_empty());
+    }
+
+    #[test]
+    fn refused_rename_is_retried_later() {
+        let mut queue = RenameQueue::default();
+        let channel_id = ChannelId::new(5);
+        let start = Instant::now();
+
+        // Somebody else used up the slots, Discord refuses the first rename of the bot
+        assert_eq!(queue.request(channel_id, "one", start), RenameSlot::Now);
+        assert_eq!(queue.limited(channel_id, "one", start), RenameSlot::Queued(LIMITED_RETRY));
+        assert!(matches!(queue.request(channel_id, "two", start), RenameSlot::Queued(_)));
+        assert!(queue.take_due(start + Duration::from_secs(30)).is_empty());
+
+        // The newer name is not replaced by a refused older one
+        queue.limited(channel_id, "one", start);
+        assert_eq!(queue.take_due(start + LIMITED_RETRY), vec![(channel_id, "two".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn failed_rename_keeps_the_slot() {
+        let discord = FakeDiscord::new();
+        let guild_id = discord.add_guild();
+        let channel_id = discord.add_voice_channel(guild_id, None, "room");
+
+        discord.fail(FakeCall::EditChannel);
+        assert!(rename(&discord, channel_id, "one").await.is_err());
+        assert!(rename(&discord, channel_id, "two").await.is_err());
+        assert!(rename(&discord, channel_id, "three").await.is_err());
+
+        assert_eq!(QUEUE.lock().request(channel_id, "four", Instant::now()), RenameSlot::Now);
+    }
+}
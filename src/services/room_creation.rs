@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serenity::all::{ChannelId, Timestamp};
+
+use crate::services::{channel_cache, gateway::DiscordGateway};
+use crate::sql::repository::MonitoredRoomRepository;
+
+
+#[derive(thiserror::Error, Debug)]
+pub enum RoomCreationError {
+    #[error("Guild is not cached")]
+    GuildNotCached,
+
+    #[error("No category for the room: {0}")]
+    NoCategory(String),
+
+    #[error("Failed to create the channel: {0}")]
+    CreateChannel(serenity::Error),
+
+    #[error("Failed to move the owner: {0}")]
+    MoveMember(serenity::Error),
+
+    #[error("Failed to grant owner privileges: {0}")]
+    GrantOwner(serenity::Error),
+
+    #[error("Failed to apply room privacy: {0}")]
+    Privacy(serenity::Error),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl RoomCreationError {
+    /// Stable name of the failure for metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::GuildNotCached => "guild_not_cached",
+            Self::NoCategory(_) => "no_category",
+            Self::CreateChannel(_) => "create_channel",
+            Self::MoveMember(_) => "move_member",
+            Self::GrantOwner(_) => "grant_owner",
+            Self::Privacy(_) => "privacy",
+            Self::Database(_) => "database",
+        }
+    }
+}
+
+static FAILURES: Lazy<Mutex<HashMap<&'static str, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static IN_FLIGHT: Lazy<Mutex<HashSet<u64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// The room is being set up right now, its uncommitted row must not be rolled back by a cleanup.
+pub fn is_in_flight(channel_id: ChannelId) -> bool {
+    IN_FLIGHT.lock().contains(&channel_id.get())
+}
+
+/// Rooms younger than this may still wait for their owner to be moved in
+const SETTLING_SECS: i64 = 60;
+
+/// The room is being set up or was created moments ago, so being empty doesn't mean it is abandoned.
+/// The age covers rooms set up by another instance, [`is_in_flight`] only knows this one.
+pub fn is_settling(channel_id: ChannelId) -> bool {
+    is_in_flight(channel_id) || Timestamp::now().unix_timestamp() - channel_id.created_at().unix_timestamp() < SETTLING_SECS
+}
+
+/// Counts the failure by its [`RoomCreationError::label`], returns the count since startup.
+pub fn record_failure(err: &RoomCreationError) -> u64 {
+    let mut failures = FAILURES.lock();
+    let count = failures.entry(err.label()).or_default();
+    *count += 1;
+    *count
+}
+
+/// Failed room creations since startup by [`RoomCreationError::label`].
+pub fn failure_counts() -> HashMap<&'static str, u64> {
+    FAILURES.lock().clone()
+}
+
+enum Compensation {
+    DeleteChannel(ChannelId),
+    RemoveRow(ChannelId),
+}
+
+/// Undo log of a room creation. Every completed step registers the action that reverts it,
+/// [`RoomTransaction::rollback`] runs them in reverse order, [`RoomTransaction::commit`] marks the row as done.
+#[derive(Default)]
+pub struct RoomTransaction {
+    channel_id: Option<ChannelId>,
+    compensations: Vec<Compensation>,
+}
+
+impl Drop for RoomTransaction {
+    fn drop(&mut self) {
+        if let Some(channel_id) = self.channel_id {
+            IN_FLIGHT.lock().remove(&channel_id.get());
+        }
+    }
+}
+
+impl RoomTransaction {
+    pub fn channel_created(&mut self, channel_id: ChannelId) {
+        IN_FLIGHT.lock().insert(channel_id.get());
+        self.channel_id = Some(channel_id);
+        self.compensations.push(Compensation::DeleteChannel(channel_id));
+    }
+
+    pub fn row_inserted(&mut self, channel_id: ChannelId) {
+        self.compensations.push(Compensation::RemoveRow(channel_id));
+    }
+
+    pub async fn commit(self, discord: &dyn DiscordGateway, rooms: &dyn MonitoredRoomRepository, channel_id: ChannelId) -> Result<(), RoomCreationError> {
+        match rooms.commit(channel_id.get() as i64).await {
+            Ok(true) => Ok(()),
+            // The room was removed while it was set up, e.g. the owner left at once
+            Ok(false) => {
+                self.rollback(discord, rooms).await;
+                Err(RoomCreationError::Database(sqlx::Error::RowNotFound))
+            },
+            Err(err) => {
+                self.rollback(discord, rooms).await;
+                Err(err.into())
+            },
+        }
+    }
+
+    pub async fn rollback(mut self, discord: &dyn DiscordGateway, rooms: &dyn MonitoredRoomRepository) {
+        for compensation in std::mem::take(&mut self.compensations).into_iter().rev() {
+            match compensation {
+                Compensation::DeleteChannel(channel_id) => {
+                    tracing::info!("Rollback room creation, delete CHANNEL({})", channel_id.get());
+                    if let Err(err) = discord.delete_channel(channel_id).await {
+                        tracing::error!("Rollback failed to delete CHANNEL({}).\n{}", channel_id.get(), err);
+                    }
+                },
+                Compensation::RemoveRow(channel_id) => {
+                    tracing::info!("Rollback room creation, remove row CHANNEL({})", channel_id.get());
+                    if let Err(err) = rooms.remove(channel_id.get() as i64).await {
+                        tracing::error!("Rollback failed to remove row CHANNEL({}).\n{}", channel_id.get(), err);
+                    }
+                    channel_cache::set_monitored(channel_id, false);
+                },
+            }
+        }
+    }
+}
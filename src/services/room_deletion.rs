@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serenity::all::ChannelId;
+use tokio::task::AbortHandle;
+
+use crate::services::{gateway::DiscordGateway, voice_queue};
+use crate::sql::{prelude::Repositories, repository::MonitoredRoomRepository};
+use crate::voice::remove_channel_by_id_proccessing;
+
+
+struct PendingDeletion {
+    generation: u64,
+    handle: AbortHandle
+}
+
+static PENDING_DELETIONS: Lazy<Mutex<HashMap<u64, PendingDeletion>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+
+pub fn is_pending(channel_id: ChannelId) -> bool {
+    PENDING_DELETIONS.lock().contains_key(&channel_id.get())
+}
+
+/// Starts the in-memory timer only. Used to re-arm deletions already stored in the database.
+pub fn arm(discord: Arc<dyn DiscordGateway>, repositories: Repositories, channel_id: ChannelId, delay: Duration) {
+    let generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+
+    // Hold the lock until the entry is inserted so the task can't look it up too early
+    let mut pending = PENDING_DELETIONS.lock();
+    let handle = tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+
+        {
+            let mut pending = PENDING_DELETIONS.lock();
+            match pending.get(&channel_id.get()) {
+                Some(deletion) if deletion.generation == generation => {
+                    pending.remove(&channel_id.get());
+                },
+                _ => return,
+            };
+        }
+
+        let _guard = voice_queue::lock_channel(channel_id).await;
+        tracing::info!("Grace period is over, Remove Room: {}", channel_id.get());
+        if let Err(err) = remove_channel_by_id_proccessing(discord.as_ref(), &channel_id, repositories.rooms.as_ref()).await {
+            tracing::error!("Delayed Remove Room({}) Error: {}", channel_id.get(), err);
+        }
+        // The room survives when somebody rejoined at the last moment, drop the mark either way
+        if let Err(err) = repositories.rooms.cancel_deletion(channel_id.get() as i64).await {
+            tracing::error!("Delayed Remove Room({}) Error: {}", channel_id.get(), err);
+        }
+    }).abort_handle();
+
+    if let Some(previous) = pending.insert(channel_id.get(), PendingDeletion { generation, handle }) {
+        previous.handle.abort();
+    }
+}
+
+pub async fn schedule(
+    discord: Arc<dyn DiscordGateway>,
+    repositories: &Repositories,
+    channel_id: ChannelId,
+    delay: Duration
+) -> Result<(), String> {
+    tracing::info!("Schedule Remove Room: {} in {}s", channel_id.get(), delay.as_secs());
+    repositories
+        .rooms
+        .schedule_deletion(channel_id.get() as i64, delay.as_secs_f64())
+        .await
+        .map_err(|err| err.to_string())?;
+
+    arm(discord, repositories.clone(), channel_id, delay);
+    Ok(())
+}
+
+/// Stops a pending deletion of the room. Returns `false` when nothing was scheduled.
+pub async fn cancel(rooms: &dyn MonitoredRoomRepository, channel_id: ChannelId) -> Result<bool, String> {
+    let pending = PENDING_DELETIONS.lock().remove(&channel_id.get());
+    let deletion = match pending {
+        Some(deletion) => deletion,
+        None => return Ok(false),
+    };
+    deletion.handle.abort();
+    tracing::info!("Cancel Remove Room: {}", channel_id.get());
+
+    rooms.cancel_deletion(channel_id.get() as i64)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(true)
+}
@@ -0,0 +1,154 @@
+use serenity::all::Member;
+
+use crate::services::gateway::DiscordGateway;
+
+
+/// Discord limit for channel names
+pub const MAX_NAME_LENGTH: usize = 100;
+pub const DEFAULT_TEMPLATE: &str = "{nick}`s {suffix}";
+const FALLBACK_NAME: &str = "room";
+/// Markdown and mention syntax, dropped from member provided values before they reach a name
+const MARKUP: &[char] = &['`', '*', '_', '~', '|', '<', '>', '@'];
+
+
+/// Values of the placeholders supported by room name templates.
+#[derive(Debug, Default)]
+pub struct RoomNameValues<'a> {
+    /// `{nick}`: server nickname, falls back to the global name and then the username
+    pub nick: &'a str,
+    /// `{username}`: account username
+    pub username: &'a str,
+    /// `{n}`: lowest free room number in the category
+    pub number: Option<i32>,
+    /// `{game}`: game the member is playing, needs the presence intent
+    pub game: Option<String>,
+    /// `{suffix}`: suffix configured for the autoroom
+    pub suffix: &'a str,
+}
+
+impl<'a> RoomNameValues<'a> {
+    pub fn from_member(discord: &dyn DiscordGateway, member: &'a Member, suffix: &'a str) -> Self {
+        let game = discord.playing(member.guild_id, member.user.id);
+
+        Self {
+            nick: member.display_name(),
+            username: &member.user.name,
+            number: None,
+            game,
+            suffix,
+        }
+    }
+}
+
+pub fn uses_number(template: &str) -> bool {
+    template.contains("{n}")
+}
+
+/// Replaces known placeholders in a single pass, so braces inside values are never expanded again.
+/// Unknown placeholders are kept as they are, markup is only stripped from the values.
+pub fn render(template: &str, values: &RoomNameValues<'_>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let end = match tail.find('}') {
+            Some(end) => end,
+            None => {
+                rest = tail;
+                break;
+            },
+        };
+
+        match &tail[1..end] {
+            "nick" => result.push_str(&strip_markup(values.nick)),
+            "username" => result.push_str(&strip_markup(values.username)),
+            "n" => result.push_str(&values.number.unwrap_or(1).to_string()),
+            "game" => result.push_str(&strip_markup(values.game.as_deref().unwrap_or_default())),
+            "suffix" => result.push_str(values.suffix),
+            _ => result.push_str(&tail[..=end]),
+        };
+        rest = &tail[end + 1..];
+    }
+    result.push_str(rest);
+
+    sanitize(&result)
+}
+
+fn strip_markup(value: &str) -> String {
+    value.replace(MARKUP, "")
+}
+
+/// Drops control characters, collapses whitespace and cuts the name to the Discord limit.
+pub fn sanitize(name: &str) -> String {
+    let name = name
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let name: String = name.chars().take(MAX_NAME_LENGTH).collect();
+    match name.trim_end() {
+        "" => FALLBACK_NAME.to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Lowest positive number not taken by another room.
+pub fn lowest_free_number(taken: &[i32]) -> i32 {
+    (1..).find(|number| !taken.contains(number)).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, sanitize, RoomNameValues, DEFAULT_TEMPLATE, MAX_NAME_LENGTH};
+
+
+    fn values<'a>(nick: &'a str, game: Option<&str>) -> RoomNameValues<'a> {
+        RoomNameValues {
+            nick,
+            username: "alice",
+            number: Some(3),
+            game: game.map(str::to_string),
+            suffix: "room",
+        }
+    }
+
+    #[test]
+    fn renders_known_placeholders() {
+        assert_eq!(render(DEFAULT_TEMPLATE, &values("Alice", None)), "Alice`s room");
+        assert_eq!(render("#{n} {username} | {game}", &values("Alice", Some("Chess"))), "#3 alice | Chess");
+    }
+
+    #[test]
+    fn keeps_unknown_placeholders() {
+        assert_eq!(render("{nick} {unknown} {", &values("Alice", None)), "Alice {unknown} {");
+        // Braces coming from a value are not expanded again
+        assert_eq!(render("{nick}", &values("{game}", Some("Chess"))), "{game}");
+    }
+
+    #[test]
+    fn strips_markup_from_values() {
+        assert_eq!(render("{nick}", &values("**bold** ~~gone~~ `code` ||spoiler||", None)), "bold gone code spoiler");
+        assert_eq!(render("{nick} {game}", &values("@everyone", Some("<@123456>"))), "everyone 123456");
+        // The template itself is left as the admin wrote it
+        assert_eq!(render("`{nick}`", &values("Alice", None)), "`Alice`");
+    }
+
+    #[test]
+    fn caps_the_length() {
+        let long = "ё".repeat(MAX_NAME_LENGTH + 20);
+        assert_eq!(render("{nick}", &values(&long, None)).chars().count(), MAX_NAME_LENGTH);
+        // A cut right after a space leaves no trailing whitespace
+        let spaced = format!("{} tail", "a".repeat(MAX_NAME_LENGTH - 1));
+        assert_eq!(sanitize(&spaced), "a".repeat(MAX_NAME_LENGTH - 1));
+    }
+
+    #[test]
+    fn collapses_whitespace_and_falls_back() {
+        assert_eq!(sanitize("  Alice \n\t room  "), "Alice room");
+        assert_eq!(render("{game}", &values("Alice", None)), "room");
+        assert_eq!(render("{nick}", &values("***", None)), "room");
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serenity::all::{
+    ActionRow, ActionRowComponent, ButtonStyle, ChannelId, Colour, Context, CreateActionRow, CreateButton,
+    CreateEmbed, CreateInputText, CreateMessage, CreateModal, CreateSelectMenu, CreateSelectMenuKind,
+    EditMessage, GuildChannel, InputTextStyle, Mentionable, MessageId, PermissionOverwriteType, Permissions,
+    RoleId, UserId
+};
+use poise::ChoiceParameter;
+
+use crate::services::autoroom::{room_privacy, voice_channel::BotError};
+use crate::services::{channel_cache, member_list};
+use crate::services::custom_id::{CustomId, PanelAction};
+use crate::services::gateway::DiscordGateway;
+use crate::sql::{autoroom::RoomPrivacy, prelude::Repositories, repository::MonitoredRoomRepository};
+
+
+/// Joins, leaves and edits arriving within this delay update the panel once
+const REFRESH_DELAY: Duration = Duration::from_secs(3);
+/// Most members one select can pick, the limit of Discord
+const MAX_PICKED_MEMBERS: u8 = 25;
+/// Embed field values are limited to 1024 characters, a mention takes up to 22
+const LISTED_MEMBERS: usize = 40;
+
+static PENDING_REFRESHES: Lazy<Mutex<HashSet<ChannelId>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// What the control panel of a room shows, read from the channel and its voice states.
+#[derive(Debug, Clone)]
+pub struct PanelState {
+    pub name: String,
+    pub owner_id: UserId,
+    pub privacy: RoomPrivacy,
+    /// `CONNECT` is denied to `@everyone`
+    pub locked: bool,
+    /// `VIEW_CHANNEL` is denied to `@everyone`
+    pub hidden: bool,
+    pub user_limit: Option<u32>,
+    pub bitrate: Option<u32>,
+    pub guests: Vec<UserId>,
+    pub members: Vec<UserId>,
+}
+
+fn mentions(user_ids: &[UserId], empty: &str) -> String {
+    if user_ids.is_empty() {
+        return empty.to_string();
+    }
+    let mut value = user_ids
+        .iter()
+        .take(LISTED_MEMBERS)
+        .map(|user_id| user_id.mention().to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+    if user_ids.len() > LISTED_MEMBERS {
+        value.push_str(&format!(" ...and {} more", user_ids.len() - LISTED_MEMBERS));
+    }
+    value
+}
+
+impl PanelState {
+    pub fn read(discord: &dyn DiscordGateway, channel: &GuildChannel, owner_id: UserId) -> Self {
+        let everyone = PermissionOverwriteType::Role(RoleId::new(channel.guild_id.get()));
+        let everyone_deny = channel.permission_overwrites
+            .iter()
+            .find(|overwrite| overwrite.kind == everyone)
+            .map(|overwrite| overwrite.deny)
+            .unwrap_or_default();
+        let bot_id = discord.current_user_id();
+        // Guests keep the overwrite `grant_guest_privileges` created, kicked members only have a deny
+        let guests = channel.permission_overwrites
+            .iter()
+            .filter(|overwrite| overwrite.allow.contains(Permissions::VIEW_CHANNEL))
+            .filter_map(|overwrite| match overwrite.kind {
+                PermissionOverwriteType::Member(user_id) if user_id != owner_id && user_id != bot_id => Some(user_id),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            name: channel.name.clone(),
+            owner_id,
+            privacy: room_privacy(channel),
+            locked: everyone_deny.contains(Permissions::CONNECT),
+            hidden: everyone_deny.contains(Permissions::VIEW_CHANNEL),
+            user_limit: channel.user_limit,
+            bitrate: channel.bitrate,
+            guests,
+            members: discord.channel_members(channel.guild_id, channel.id).unwrap_or_default(),
+        }
+    }
+
+    pub fn embed(&self) -> CreateEmbed {
+        let user_limit = match self.user_limit {
+            Some(user_limit) if user_limit > 0 => format!("{} / {}", self.members.len(), user_limit),
+            _ => "No limit".to_string(),
+        };
+        let bitrate = match self.bitrate {
+            Some(bitrate) => format!("{}kbps", bitrate / 1000),
+            None => "Default".to_string(),
+        };
+
+        CreateEmbed::new()
+            .title(format!("🛠 {}", self.name))
+            .colour(match self.privacy {
+                RoomPrivacy::Public => Colour::DARK_GREEN,
+                RoomPrivacy::Locked => Colour::ORANGE,
+                RoomPrivacy::Hidden => Colour::DARK_GREY,
+            })
+            .field("Host", self.owner_id.mention().to_string(), true)
+            .field("Privacy", self.privacy.name(), true)
+            .field("Limit", user_limit, true)
+            .field("Bitrate", bitrate, true)
+            .field(format!("Guests ({})", self.guests.len()), mentions(&self.guests, "None"), false)
+            .field(format!("Members ({})", self.members.len()), mentions(&self.members, "Empty"), false)
+    }
+
+    /// Picking members runs the action right away, buttons show the action the current state allows.
+    pub fn components(&self, channel_id: ChannelId) -> Vec<CreateActionRow> {
+        let id = |action| CustomId::new(action, self.owner_id, channel_id).encode();
+        let (lock_action, lock_label) = match self.locked {
+            true => (PanelAction::Unlock, "🔓 Unlock"),
+            false => (PanelAction::Lock, "🔒 Lock"),
+        };
+        let (hide_action, hide_label) = match self.hidden {
+            true => (PanelAction::Unhide, "👁 Show"),
+            false => (PanelAction::Hide, "🙈 Hide"),
+        };
+
+        vec![
+            CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(id(PanelAction::Invite), CreateSelectMenuKind::User { default_users: None })
+                    .placeholder("Invite members")
+                    .min_values(1)
+                    .max_values(MAX_PICKED_MEMBERS)
+            ),
+            CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(id(PanelAction::Kick), CreateSelectMenuKind::User { default_users: None })
+                    .placeholder("Kick members")
+                    .min_values(1)
+                    .max_values(MAX_PICKED_MEMBERS)
+            ),
+            CreateActionRow::Buttons(vec![
+                CreateButton::new(id(lock_action))
+                    .label(lock_label)
+                    .style(ButtonStyle::Secondary),
+                CreateButton::new(id(hide_action))
+                    .label(hide_label)
+                    .style(ButtonStyle::Secondary),
+            ]),
+            CreateActionRow::Buttons(vec![
+                CreateButton::new(id(PanelAction::Rename))
+                    .label("✏️ Rename")
+                    .style(ButtonStyle::Secondary),
+                CreateButton::new(id(PanelAction::Limit))
+                    .label("👥 Limit")
+                    .style(ButtonStyle::Secondary),
+                CreateButton::new(id(PanelAction::Bitrate))
+                    .label("🎚 Bitrate")
+                    .style(ButtonStyle::Secondary),
+            ]),
+            member_list::list_buttons(self.owner_id, channel_id),
+        ]
+    }
+
+    fn edit_message(&self, channel_id: ChannelId) -> EditMessage {
+        // The content is cleared for panels migrated from the text-only menu
+        EditMessage::new()
+            .content("")
+            .embed(self.embed())
+            .components(self.components(channel_id))
+    }
+}
+
+/// Form of the settings buttons, prefilled with the current value when the channel is known.
+/// The submitted form keeps the id of the button.
+pub fn settings_modal(id: CustomId, channel: Option<&GuildChannel>) -> Option<CreateModal> {
+    let (title, input) = match id.action {
+        PanelAction::Rename => (
+            "Rename the room",
+            CreateInputText::new(InputTextStyle::Short, "Name", "value")
+                .min_length(1)
+                .max_length(100)
+                .value(channel.map(|channel| channel.name.clone()).unwrap_or_default()),
+        ),
+        PanelAction::Limit => (
+            "Set the user limit",
+            CreateInputText::new(InputTextStyle::Short, "Members, 0 removes the limit", "value")
+                .min_length(1)
+                .max_length(2)
+                .value(channel.and_then(|channel| channel.user_limit).unwrap_or(0).to_string()),
+        ),
+        PanelAction::Bitrate => (
+            "Set the bitrate",
+            CreateInputText::new(InputTextStyle::Short, "Bitrate in kbps, 8 to 384", "value")
+                .min_length(1)
+                .max_length(3)
+                .value(channel.and_then(|channel| channel.bitrate).map(|bitrate| (bitrate / 1000).to_string()).unwrap_or_default()),
+        ),
+        _ => return None,
+    };
+    Some(CreateModal::new(id.encode(), title).components(vec![CreateActionRow::InputText(input)]))
+}
+
+/// Text entered into a submitted settings form.
+pub fn submitted_value(components: &[ActionRow]) -> &str {
+    components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) => input.value.as_deref(),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Parses a number entered into a settings form, the services check the range.
+pub fn parse_number(value: &str) -> Result<u32, BotError> {
+    value.trim().parse::<u32>().map_err(|_| BotError::Rejected("Please enter a whole number"))
+}
+
+/// Sends the panel of a room and remembers it, so it can be edited after a restart too.
+/// Only temporary rooms keep the message id, panels of permanent rooms are not refreshed.
+pub async fn deploy(
+    discord: &dyn DiscordGateway,
+    rooms: &dyn MonitoredRoomRepository,
+    channel_id: ChannelId,
+    owner_id: UserId
+) -> Result<(), serenity::Error> {
+    let channel = match discord.channel(channel_id).await? {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+    let state = PanelState::read(discord, &channel, owner_id);
+
+    tracing::info!("Sending room panel to CHANNEL({}) OWNER({})", channel_id, owner_id);
+    let message_id = discord.send_message(channel_id,
+        CreateMessage::new()
+            .embed(state.embed())
+            .components(state.components(channel_id))
+    ).await?;
+
+    if let Err(err) = rooms.set_panel_message(channel_id.get() as i64, Some(message_id.get() as i64)).await {
+        tracing::error!("Failed to store room panel CHANNEL({}) MESSAGE({}).\n{}", channel_id, message_id, err);
+    }
+    Ok(())
+}
+
+async fn render(discord: &dyn DiscordGateway, channel_id: ChannelId, message_id: MessageId, owner_id: UserId) -> Result<(), String> {
+    let channel = match discord.channel(channel_id).await.map_err(|err| err.to_string())? {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+
+    discord
+        .edit_message(channel_id, message_id, PanelState::read(discord, &channel, owner_id).edit_message(channel_id))
+        .await
+        .map_err(|err| err.to_string())
+}
+
+/// Edits the stored panel of the room to its current state, rooms without one are left alone.
+pub async fn refresh(discord: &dyn DiscordGateway, rooms: &dyn MonitoredRoomRepository, channel_id: ChannelId) -> Result<(), String> {
+    let room = match rooms.get_by_channel_id(channel_id.get() as i64).await.map_err(|err| err.to_string())? {
+        Some(room) => room,
+        None => return Ok(()),
+    };
+    let message_id = match rooms.get_panel_message(channel_id.get() as i64).await.map_err(|err| err.to_string())? {
+        Some(message_id) => MessageId::new(message_id as u64),
+        None => return Ok(()),
+    };
+    render(discord, channel_id, message_id, UserId::new(room.owner_id as u64)).await
+}
+
+/// Turns a message of an outdated menu into the panel of its room.
+/// Temporary rooms keep it as their live panel, permanent rooms get it redrawn once.
+pub async fn adopt(
+    discord: &dyn DiscordGateway,
+    rooms: &dyn MonitoredRoomRepository,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    owner_id: UserId
+) -> Result<(), String> {
+    rooms
+        .set_panel_message(channel_id.get() as i64, Some(message_id.get() as i64))
+        .await
+        .map_err(|err| err.to_string())?;
+    render(discord, channel_id, message_id, owner_id).await
+}
+
+/// Refreshes the panel once the changes coming in a burst settled, other channels cost a cache lookup.
+pub fn schedule_refresh(ctx: &Context, channel_id: ChannelId) {
+    if !PENDING_REFRESHES.lock().insert(channel_id) {
+        return;
+    }
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(REFRESH_DELAY).await;
+        PENDING_REFRESHES.lock().remove(&channel_id);
+
+        let repositories = Repositories::from_context(&ctx).await;
+        match channel_cache::is_monitored(repositories.rooms.as_ref(), channel_id).await {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(err) => {
+                tracing::error!("Failed to check room CHANNEL({}).\n{}", channel_id, err);
+                return;
+            },
+        };
+        if let Err(err) = refresh(&ctx, repositories.rooms.as_ref(), channel_id).await {
+            tracing::error!("Failed to refresh room panel CHANNEL({}).\n{}", channel_id, err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::{ChannelId, GuildId, MessageId, PermissionOverwrite, PermissionOverwriteType, Permissions, RoleId, UserId};
+    use serde_json::Value;
+
+    use super::{deploy, refresh};
+    use crate::services::custom_id::{CustomId, PanelAction};
+    use crate::services::fake_discord::{next_id, FakeDiscord};
+    use crate::sql::autoroom::MonitoredAutoRoom;
+    use crate::sql::prelude::Repositories;
+
+
+    struct Panel {
+        discord: FakeDiscord,
+        repositories: Repositories,
+        guild_id: GuildId,
+        room_id: ChannelId,
+        owner_id: UserId,
+    }
+
+    async fn panel() -> Panel {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let room_id = discord.add_voice_channel(guild_id, Some(category_id), "alice`s room");
+        let owner_id = UserId::new(next_id());
+        repositories.rooms.insert_many(&[MonitoredAutoRoom {
+            channel_id: room_id.get() as i64,
+            owner_id: owner_id.get() as i64,
+            autoroom_channel_id: None,
+        }]).await.unwrap();
+        discord.connect(guild_id, owner_id, room_id);
+        deploy(&discord, repositories.rooms.as_ref(), room_id, owner_id).await.unwrap();
+        Panel { discord, repositories, guild_id, room_id, owner_id }
+    }
+
+    impl Panel {
+        async fn message(&self) -> Value {
+            let message_id = self.repositories.rooms.get_panel_message(self.room_id.get() as i64).await.unwrap().unwrap();
+            self.discord.message(MessageId::new(message_id as u64)).unwrap()
+        }
+    }
+
+    fn field<'a>(message: &'a Value, name: &str) -> &'a str {
+        message["embeds"][0]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|field| field["name"].as_str().unwrap().starts_with(name))
+            .and_then(|field| field["value"].as_str())
+            .unwrap()
+    }
+
+    fn buttons(message: &Value, row: usize) -> Vec<PanelAction> {
+        message["components"][row]["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|button| CustomId::decode(button["custom_id"].as_str().unwrap()).unwrap().action)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn deploy_stores_panel_of_current_state() {
+        let panel = panel().await;
+        let message = panel.message().await;
+
+        assert_eq!(field(&message, "Host"), format!("<@{}>", panel.owner_id));
+        assert_eq!(field(&message, "Privacy"), "Public");
+        assert_eq!(field(&message, "Members"), format!("<@{}>", panel.owner_id));
+        assert_eq!(buttons(&message, 2), vec![PanelAction::Lock, PanelAction::Hide]);
+        assert_eq!(buttons(&message, 3), vec![PanelAction::Rename, PanelAction::Limit, PanelAction::Bitrate]);
+        assert_eq!(buttons(&message, 4), vec![PanelAction::TrustedList, PanelAction::BlockedList]);
+    }
+
+    #[tokio::test]
+    async fn refresh_edits_panel_in_place() {
+        let panel = panel().await;
+        let guest_id = UserId::new(next_id());
+        panel.discord.set_overwrites(panel.room_id, vec![
+            PermissionOverwrite {
+                allow: Permissions::empty(),
+                deny: Permissions::CONNECT,
+                kind: PermissionOverwriteType::Role(RoleId::new(panel.guild_id.get())),
+            },
+            PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::CONNECT | Permissions::MANAGE_CHANNELS,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(panel.owner_id),
+            },
+            PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(guest_id),
+            },
+        ]);
+        panel.discord.connect(panel.guild_id, guest_id, panel.room_id);
+
+        refresh(&panel.discord, panel.repositories.rooms.as_ref(), panel.room_id).await.unwrap();
+
+        let message = panel.message().await;
+        assert_eq!(panel.discord.messages(panel.room_id).len(), 1);
+        assert_eq!(field(&message, "Privacy"), "Locked");
+        assert_eq!(field(&message, "Guests"), format!("<@{}>", guest_id));
+        assert!(field(&message, "Members").contains(&format!("<@{}>", guest_id)));
+        assert_eq!(buttons(&message, 2), vec![PanelAction::Unlock, PanelAction::Hide]);
+    }
+
+    #[tokio::test]
+    async fn refresh_without_panel_does_nothing() {
+        let panel = panel().await;
+        let other_id = panel.discord.add_voice_channel(panel.guild_id, None, "other");
+
+        refresh(&panel.discord, panel.repositories.rooms.as_ref(), other_id).await.unwrap();
+        assert!(panel.discord.messages(other_id).is_empty());
+    }
+}
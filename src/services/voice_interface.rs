@@ -0,0 +1,183 @@
+use serenity::all::{
+    ButtonStyle, ChannelId, Colour, CreateActionRow, CreateButton, CreateEmbed, CreateMessage, CreateSelectMenu,
+    CreateSelectMenuKind, EditMessage, GuildId, MessageId, UserId
+};
+
+use crate::services::autoroom::voice_channel::BotError;
+use crate::services::custom_id::{CustomId, PanelAction};
+use crate::services::gateway::DiscordGateway;
+use crate::services::member_list;
+use crate::sql::prelude::Repositories;
+
+
+/// Most members one select can pick, the limit of Discord
+const MAX_PICKED_MEMBERS: u8 = 25;
+
+fn embed() -> CreateEmbed {
+    CreateEmbed::new()
+        .title("🎙 Voice interface")
+        .colour(Colour::BLURPLE)
+        .description("Manage the room you host. Join it first, the buttons act on the room you are connected to.")
+}
+
+/// The panel has no room of its own, its ids carry the bot and the interface channel.
+/// The state of a room is unknown here, so both directions of lock and hide are offered.
+pub fn components(bot_id: UserId, channel_id: ChannelId) -> Vec<CreateActionRow> {
+    let id = |action| CustomId::new(action, bot_id, channel_id).encode();
+    let button = |action, label: &str| CreateButton::new(id(action)).label(label).style(ButtonStyle::Secondary);
+
+    vec![
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(id(PanelAction::Invite), CreateSelectMenuKind::User { default_users: None })
+                .placeholder("Invite members")
+                .min_values(1)
+                .max_values(MAX_PICKED_MEMBERS)
+        ),
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(id(PanelAction::Kick), CreateSelectMenuKind::User { default_users: None })
+                .placeholder("Kick members")
+                .min_values(1)
+                .max_values(MAX_PICKED_MEMBERS)
+        ),
+        CreateActionRow::Buttons(vec![
+            button(PanelAction::Lock, "🔒 Lock"),
+            button(PanelAction::Unlock, "🔓 Unlock"),
+            button(PanelAction::Hide, "🙈 Hide"),
+            button(PanelAction::Unhide, "👁 Show"),
+        ]),
+        CreateActionRow::Buttons(vec![
+            button(PanelAction::Rename, "✏️ Rename"),
+            button(PanelAction::Limit, "👥 Limit"),
+            button(PanelAction::Bitrate, "🎚 Bitrate"),
+        ]),
+        member_list::list_buttons(bot_id, channel_id),
+    ]
+}
+
+/// Sends the interface panel and returns its message. A redeploy into the same channel edits
+/// the previous panel in place, moving it to another channel deletes the previous one.
+pub async fn deploy(
+    discord: &dyn DiscordGateway,
+    channel_id: ChannelId,
+    previous: Option<(ChannelId, MessageId)>
+) -> Result<MessageId, serenity::Error> {
+    let bot_id = discord.current_user_id();
+    if let Some((previous_channel_id, previous_message_id)) = previous {
+        if previous_channel_id == channel_id {
+            let edited = discord
+                .edit_message(channel_id, previous_message_id, EditMessage::new().embed(embed()).components(components(bot_id, channel_id)))
+                .await;
+            // The previous panel could be deleted by hand, a new one is sent then
+            if edited.is_ok() {
+                return Ok(previous_message_id);
+            }
+        } else if let Err(err) = discord.delete_message(previous_channel_id, previous_message_id).await {
+            tracing::warn!("Failed to delete previous voice interface CHANNEL({}) MESSAGE({}).\n{}", previous_channel_id, previous_message_id, err);
+        }
+    }
+
+    tracing::info!("Sending voice interface to CHANNEL({})", channel_id);
+    discord.send_message(channel_id, CreateMessage::new().embed(embed()).components(components(bot_id, channel_id))).await
+}
+
+/// Room the member manages through the interface: the room they are connected to, only when they own it.
+pub async fn owned_room(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    user_id: UserId
+) -> Result<ChannelId, BotError> {
+    let channel_id = discord.voice_channel_id(guild_id, user_id).ok_or(BotError::NotInRoom)?;
+    match repositories.rooms.get_by_channel_id(channel_id.get() as i64).await {
+        Ok(Some(room)) if room.owner_id == user_id.get() as i64 => Ok(channel_id),
+        Ok(_) => Err(BotError::MonitoredAutoRoomNotFound),
+        Err(err) => {
+            tracing::error!("owned_room database error USER({}) CHANNEL({}).\n{}", user_id, channel_id, err);
+            Err(BotError::DatabaseError)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::{MessageId, UserId};
+
+    use super::{deploy, owned_room};
+    use crate::services::autoroom::voice_channel::BotError;
+    use crate::services::fake_discord::{next_id, FakeDiscord};
+    use crate::sql::autoroom::MonitoredAutoRoom;
+    use crate::sql::prelude::Repositories;
+
+
+    #[tokio::test]
+    async fn owned_room_requires_host_in_room() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let room_id = discord.add_voice_channel(guild_id, None, "alice`s room");
+        let lobby_id = discord.add_voice_channel(guild_id, None, "lobby");
+        let owner_id = UserId::new(next_id());
+        let guest_id = UserId::new(next_id());
+        repositories.rooms.insert_many(&[MonitoredAutoRoom {
+            channel_id: room_id.get() as i64,
+            owner_id: owner_id.get() as i64,
+            autoroom_channel_id: None,
+        }]).await.unwrap();
+
+        assert!(matches!(owned_room(&discord, &repositories, guild_id, owner_id).await, Err(BotError::NotInRoom)));
+        discord.connect(guild_id, owner_id, lobby_id);
+        assert!(matches!(
+            owned_room(&discord, &repositories, guild_id, owner_id).await,
+            Err(BotError::MonitoredAutoRoomNotFound)
+        ));
+
+        discord.connect(guild_id, owner_id, room_id);
+        discord.connect(guild_id, guest_id, room_id);
+        assert_eq!(owned_room(&discord, &repositories, guild_id, owner_id).await.unwrap(), room_id);
+        assert!(matches!(
+            owned_room(&discord, &repositories, guild_id, guest_id).await,
+            Err(BotError::MonitoredAutoRoomNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn owned_room_is_the_connected_one() {
+        let discord = FakeDiscord::new();
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let owner_id = UserId::new(next_id());
+        let older_id = discord.add_voice_channel(guild_id, None, "older room");
+        let newer_id = discord.add_voice_channel(guild_id, None, "newer room");
+        for room_id in [older_id, newer_id] {
+            repositories.rooms.insert_many(&[MonitoredAutoRoom {
+                channel_id: room_id.get() as i64,
+                owner_id: owner_id.get() as i64,
+                autoroom_channel_id: None,
+            }]).await.unwrap();
+        }
+
+        discord.connect(guild_id, owner_id, older_id);
+        assert_eq!(owned_room(&discord, &repositories, guild_id, owner_id).await.unwrap(), older_id);
+    }
+
+    #[tokio::test]
+    async fn redeploy_edits_in_place_or_moves_panel() {
+        let discord = FakeDiscord::new();
+        let guild_id = discord.add_guild();
+        let first_id = discord.add_text_channel(guild_id, "voice-interface");
+        let second_id = discord.add_text_channel(guild_id, "rooms");
+
+        let message_id = deploy(&discord, first_id, None).await.unwrap();
+        assert_eq!(deploy(&discord, first_id, Some((first_id, message_id))).await.unwrap(), message_id);
+        assert_eq!(discord.messages(first_id).len(), 1);
+
+        // A panel deleted by hand is sent again
+        let gone_id = MessageId::new(next_id());
+        let resent_id = deploy(&discord, second_id, Some((second_id, gone_id))).await.unwrap();
+        assert_ne!(resent_id, gone_id);
+
+        deploy(&discord, second_id, Some((first_id, message_id))).await.unwrap();
+        assert!(discord.messages(first_id).is_empty());
+        assert_eq!(discord.messages(second_id).len(), 2);
+    }
+}
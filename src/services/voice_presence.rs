@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serenity::all::{ChannelId, UserId, VoiceState};
+
+
+/// Join times of members per voice channel, only for joins seen since the bot started.
+static JOINED_AT: Lazy<Mutex<HashMap<u64, HashMap<u64, Instant>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+
+pub fn track(old: Option<&VoiceState>, new: &VoiceState) {
+    let old_channel_id = old.and_then(|o| o.channel_id);
+    if old_channel_id == new.channel_id {
+        return;
+    }
+
+    let mut joined_at = JOINED_AT.lock();
+    if let Some(channel_id) = old_channel_id {
+        if let Some(members) = joined_at.get_mut(&channel_id.get()) {
+            members.remove(&new.user_id.get());
+            if members.is_empty() {
+                joined_at.remove(&channel_id.get());
+            }
+        }
+    }
+    if let Some(channel_id) = new.channel_id {
+        joined_at
+            .entry(channel_id.get())
+            .or_default()
+            .insert(new.user_id.get(), Instant::now());
+    }
+}
+
+/// Picks the candidate who has been in the channel the longest.
+/// Members present before the bot started have no join time and win, in the given order.
+pub fn longest_present(channel_id: ChannelId, candidates: &[UserId]) -> Option<UserId> {
+    let joined_at = JOINED_AT.lock();
+    let members = joined_at.get(&channel_id.get());
+    candidates
+        .iter()
+        .min_by_key(|user_id| members.and_then(|m| m.get(&user_id.get())).copied())
+        .copied()
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serenity::all::{ChannelId, VoiceState};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VoiceKey {
+    Channel(u64),
+    User(u64),
+}
+
+/// Queue of async locks by key. A task holding a set of keys runs alone for each of them,
+/// the others wait in FIFO order. Keys are always taken sorted, so two sets never deadlock.
+pub struct KeyedQueue<K> {
+    slots: Mutex<HashMap<K, Arc<AsyncMutex<()>>>>,
+}
+
+pub struct KeyedGuard<'a, K: Copy + Eq + Hash> {
+    queue: &'a KeyedQueue<K>,
+    keys: Vec<K>,
+    guards: Vec<OwnedMutexGuard<()>>,
+}
+
+impl<K> Default for KeyedQueue<K> {
+    fn default() -> Self {
+        Self { slots: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl<K: Copy + Ord + Hash> KeyedQueue<K> {
+    pub async fn lock(&self, keys: impl IntoIterator<Item = K>) -> KeyedGuard<'_, K> {
+        let mut keys: Vec<K> = keys.into_iter().collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let slot = self.slots.lock().entry(*key).or_default().clone();
+            guards.push(slot.lock_owned().await);
+        }
+        KeyedGuard { queue: self, keys, guards }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.slots.lock().len()
+    }
+}
+
+impl<K: Copy + Eq + Hash> Drop for KeyedGuard<'_, K> {
+    fn drop(&mut self) {
+        self.guards.clear();
+        // Drop the slots nobody waits for, so the map only holds busy keys
+        let mut slots = self.queue.slots.lock();
+        for key in &self.keys {
+            if slots.get(key).is_some_and(|slot| Arc::strong_count(slot) == 1) {
+                slots.remove(key);
+            }
+        }
+    }
+}
+
+static VOICE_QUEUE: Lazy<KeyedQueue<VoiceKey>> = Lazy::new(KeyedQueue::default);
+
+/// Member and both channels of a voice state update.
+pub fn voice_keys(old: Option<&VoiceState>, new: &VoiceState) -> Vec<VoiceKey> {
+    [old.and_then(|old| old.channel_id), new.channel_id]
+        .into_iter()
+        .flatten()
+        .map(|channel_id| VoiceKey::Channel(channel_id.get()))
+        .chain(std::iter::once(VoiceKey::User(new.user_id.get())))
+        .collect()
+}
+
+/// Waits until no other voice event of the same member or channels is processed.
+pub async fn lock_voice_state(old: Option<&VoiceState>, new: &VoiceState) -> KeyedGuard<'static, VoiceKey> {
+    VOICE_QUEUE.lock(voice_keys(old, new)).await
+}
+
+pub async fn lock_channel(channel_id: ChannelId) -> KeyedGuard<'static, VoiceKey> {
+    VOICE_QUEUE.lock([VoiceKey::Channel(channel_id.get())]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{KeyedQueue, VoiceKey};
+
+
+    #[tokio::test]
+    async fn keys_are_taken_in_order() {
+        let queue = Arc::new(KeyedQueue::default());
+        let first = queue.lock([VoiceKey::User(7), VoiceKey::Channel(1)]).await;
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                let _guard = queue.lock([VoiceKey::Channel(1), VoiceKey::User(8)]).await;
+            }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        drop(first);
+        waiting.await.unwrap();
+        assert_eq!(queue.len(), 0);
+    }
+}
@@ -1,26 +1,107 @@
-use sqlx::{Error, FromRow, PgPool, Row};
+use poise::ChoiceParameter;
+use serenity::all::VideoQualityMode;
+use sqlx::{Error, FromRow, PgPool};
 
 
-#[derive(Debug, FromRow)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[repr(i16)]
+pub enum RoomPrivacy {
+    /// Open to everybody who can see the category
+    #[default]
+    #[name = "Public"]
+    Public = 0,
+    /// Visible to everybody, only invited members can connect
+    #[name = "Locked"]
+    Locked = 1,
+    /// Only invited members can see and connect
+    #[name = "Hidden"]
+    Hidden = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[repr(i16)]
+pub enum RoomVideoQuality {
+    #[name = "Auto"]
+    Auto = 1,
+    #[name = "720p"]
+    Full = 2,
+}
+
+impl From<RoomVideoQuality> for VideoQualityMode {
+    fn from(value: RoomVideoQuality) -> Self {
+        match value {
+            RoomVideoQuality::Auto => VideoQualityMode::Auto,
+            RoomVideoQuality::Full => VideoQualityMode::Full,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
 pub struct AutoRoom {
     pub channel_id: i64,
     pub guild_id: i64,
     pub category_id: i64,
-    pub suffix: String
+    pub suffix: String,
+    pub grace_period_secs: i32,
+    pub privacy: RoomPrivacy,
+    pub name_template: String,
+    pub user_limit: Option<i32>,
+    /// Upper bound for the room bitrate in bps, the boost tier maximum still applies
+    pub bitrate_cap: Option<i32>,
+    pub rtc_region: Option<String>,
+    pub video_quality: Option<RoomVideoQuality>,
+    pub nsfw: bool,
+    /// Use the trigger channel as the template of created rooms, the settings above override it
+    pub clone_trigger: bool,
+    /// Send the control panel into every created room, off when the guild uses the voice interface
+    pub room_panel: bool
 }
 
 impl AutoRoom {
     pub fn to_display_string(&self) -> String {
         format!(
-            "ChannelID: {}, Category: {}, Suffix: {}",
+            "ChannelID: {}, Category: {}, Suffix: {}, Grace period: {}s, Privacy: {}, Name: {}",
             self.channel_id,
             self.category_id,
-            self.suffix
-        )
+            self.suffix,
+            self.grace_period_secs,
+            self.privacy.name(),
+            self.name_template
+        ) + &self.settings_display_string()
+    }
+
+    fn settings_display_string(&self) -> String {
+        let mut settings = Vec::new();
+        if let Some(user_limit) = self.user_limit {
+            settings.push(format!("Limit: {}", user_limit));
+        }
+        if let Some(bitrate_cap) = self.bitrate_cap {
+            settings.push(format!("Bitrate cap: {}kbps", bitrate_cap / 1000));
+        }
+        if let Some(rtc_region) = &self.rtc_region {
+            settings.push(format!("Region: {}", rtc_region));
+        }
+        if let Some(video_quality) = self.video_quality {
+            settings.push(format!("Video: {}", video_quality.name()));
+        }
+        if self.nsfw {
+            settings.push("NSFW".to_string());
+        }
+        if self.clone_trigger {
+            settings.push("Cloned from trigger".to_string());
+        }
+        if !self.room_panel {
+            settings.push("No room panel".to_string());
+        }
+
+        settings
+            .iter()
+            .map(|setting| format!(", {}", setting))
+            .collect()
     }
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum AutoRoomDeleteStrategy<'a> {
     SingleByChannelId(i64),
@@ -29,15 +110,27 @@ pub enum AutoRoomDeleteStrategy<'a> {
     ManyByCategoryId(&'a Vec<i64>)
 }
 
-#[derive(Debug, FromRow)]
+#[derive(Debug, Clone, FromRow)]
 pub struct MonitoredAutoRoom {
     pub channel_id: i64,
-    pub owner_id: i64
+    pub owner_id: i64,
+    pub autoroom_channel_id: Option<i64>
+}
+
+/// Extra placement category of an autoroom, tried by `position` once the main category is full.
+#[derive(Debug, Clone, FromRow)]
+pub struct OverflowCategory {
+    #[allow(dead_code)]
+    pub autoroom_channel_id: i64,
+    pub category_id: i64,
+    pub position: i32,
+    /// The category was created by the bot when every other category was full
+    pub created_by_bot: bool
 }
 
 impl AutoRoom {
     pub async fn get_by_channel_id(pool: &PgPool, channel_id: i64) -> Result<Option<Self>, Error> {
-        match sqlx::query_as::<_, AutoRoom>("SELECT channel_id, guild_id, category_id, suffix from autoroom WHERE channel_id = $1")
+        match sqlx::query_as::<_, AutoRoom>("SELECT * from autoroom WHERE channel_id = $1")
             .bind(channel_id)
             .fetch_one(pool)
             .await {
@@ -50,7 +143,13 @@ impl AutoRoom {
     }
 
     pub async fn create(&self, pool: &PgPool) -> Result<(), &'static str> {
-        let query = "INSERT INTO autoroom (channel_id, guild_id, category_id, suffix) VALUES ($1, $2, $3, $4)";
+        let query = r#"
+            INSERT INTO autoroom (
+                channel_id, guild_id, category_id, suffix, grace_period_secs, privacy, name_template,
+                user_limit, bitrate_cap, rtc_region, video_quality, nsfw, clone_trigger, room_panel
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        "#;
         tracing::info!(
             "Inserting AutoRoom, CHANNEL({}) GUILD({}) CATEGORY({}) SUFFIX({})",
             self.channel_id,
@@ -63,6 +162,16 @@ impl AutoRoom {
             .bind(self.guild_id)
             .bind(self.category_id)
             .bind(self.suffix.clone())
+            .bind(self.grace_period_secs)
+            .bind(self.privacy)
+            .bind(self.name_template.clone())
+            .bind(self.user_limit)
+            .bind(self.bitrate_cap)
+            .bind(self.rtc_region.clone())
+            .bind(self.video_quality)
+            .bind(self.nsfw)
+            .bind(self.clone_trigger)
+            .bind(self.room_panel)
             .execute(pool)
             .await;
 
@@ -89,6 +198,36 @@ impl AutoRoom {
         }
     }
     
+    pub async fn update(&self, pool: &PgPool) -> Result<bool, Error> {
+        tracing::info!("Updating AutoRoom, CHANNEL({}) GUILD({})", self.channel_id, self.guild_id);
+        let result = sqlx::query(
+            r#"
+            UPDATE autoroom SET
+                category_id = $2, suffix = $3, grace_period_secs = $4, privacy = $5, name_template = $6,
+                user_limit = $7, bitrate_cap = $8, rtc_region = $9, video_quality = $10, nsfw = $11,
+                clone_trigger = $12, room_panel = $13
+            WHERE channel_id = $1
+            "#
+        )
+            .bind(self.channel_id)
+            .bind(self.category_id)
+            .bind(self.suffix.clone())
+            .bind(self.grace_period_secs)
+            .bind(self.privacy)
+            .bind(self.name_template.clone())
+            .bind(self.user_limit)
+            .bind(self.bitrate_cap)
+            .bind(self.rtc_region.clone())
+            .bind(self.video_quality)
+            .bind(self.nsfw)
+            .bind(self.clone_trigger)
+            .bind(self.room_panel)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn delete(pool: &PgPool, strategy: AutoRoomDeleteStrategy<'_>) -> Result<(), Error> {
         let query = match strategy {
             AutoRoomDeleteStrategy::SingleByChannelId(id) => sqlx::query("DELETE FROM autoroom WHERE channel_id = $1").bind(id),
@@ -104,47 +243,102 @@ impl AutoRoom {
     }
 
     pub async fn get_guild_autorooms(pool: &PgPool, guild_id: i64) -> Result<Vec<Self>, Error> {
-        Ok(
-            sqlx::query_as::<_, Self>(
-                "SELECT * from autoroom WHERE guild_id = $1"
-            )
-                .bind(guild_id)
-                .fetch_all(pool)
-                .await?
+        sqlx::query_as::<_, Self>(
+            "SELECT * from autoroom WHERE guild_id = $1"
         )
+            .bind(guild_id)
+            .fetch_all(pool)
+            .await
     }
 
+    pub async fn get_all_channel_ids(pool: &PgPool) -> Result<Vec<i64>, Error> {
+        sqlx::query_scalar("SELECT channel_id from autoroom")
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Main and overflow categories of every autoroom.
     pub async fn get_all_category_ids(pool: &PgPool) -> Result<Vec<i64>, Error> {
-       Ok(
-            sqlx::query_scalar(
-                "SELECT category_id from autoroom"
-            )
-                .fetch_all(pool)
-                .await?
+        sqlx::query_scalar(
+            "SELECT category_id from autoroom UNION SELECT category_id from autoroom_overflow_category"
         )
+            .fetch_all(pool)
+            .await
     }
 }
 
-impl MonitoredAutoRoom {
-    pub async fn exists(pool: &PgPool, channel_id: i64) -> bool {
-        let query = "SELECT EXISTS(SELECT 1 FROM monitored_autoroom WHERE channel_id = $1)";
-        let result = sqlx::query(query)
-            .bind(channel_id)
+impl OverflowCategory {
+    pub fn to_display_string(&self) -> String {
+        format!(
+            "#{} <#{}>{}",
+            self.position,
+            self.category_id,
+            if self.created_by_bot { " (auto)" } else { "" }
+        )
+    }
+
+    /// Overflow categories of the autoroom in the order they are used.
+    pub async fn get_by_autoroom(pool: &PgPool, autoroom_channel_id: i64) -> Result<Vec<Self>, Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT * from autoroom_overflow_category WHERE autoroom_channel_id = $1 ORDER BY position"
+        )
+            .bind(autoroom_channel_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Appends the category to the end of the autoroom overflow list.
+    pub async fn push(pool: &PgPool, autoroom_channel_id: i64, category_id: i64, created_by_bot: bool) -> Result<Self, &'static str> {
+        let result = sqlx::query_as::<_, Self>(
+            r#"
+            INSERT INTO autoroom_overflow_category (autoroom_channel_id, category_id, position, created_by_bot)
+            SELECT $1, $2, COALESCE(MAX(position), 0) + 1, $3
+            FROM autoroom_overflow_category WHERE autoroom_channel_id = $1
+            RETURNING *
+            "#
+        )
+            .bind(autoroom_channel_id)
+            .bind(category_id)
+            .bind(created_by_bot)
             .fetch_one(pool)
             .await;
 
         match result {
-            Ok(row) => {
-                let exists: bool = row.get(0);
-                exists
-            },
+            Ok(category) => Ok(category),
             Err(err) => {
-                tracing::error!("{}", err);
-                false
+                if let sqlx::Error::Database(db_err) = &err {
+                    match db_err.code().as_deref() {
+                        Some("23505") => return Err("Category is already an overflow category of this autoroom"),
+                        Some("23503") => return Err("Autoroom with given channel was not found"),
+                        _ => (),
+                    }
+                }
+                tracing::error!("Failed to add overflow CATEGORY({}) AUTOROOM({}).\n{}", category_id, autoroom_channel_id, err);
+                Err("Internal server error")
             }
         }
     }
 
+    pub async fn remove(pool: &PgPool, autoroom_channel_id: i64, category_id: i64) -> Result<bool, Error> {
+        sqlx::query("DELETE FROM autoroom_overflow_category WHERE autoroom_channel_id = $1 AND category_id = $2")
+            .bind(autoroom_channel_id)
+            .bind(category_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+    }
+
+    /// Drops deleted categories from every overflow list.
+    pub async fn remove_many(pool: &PgPool, category_ids: &[i64]) -> Result<u64, Error> {
+        sqlx::query("DELETE FROM autoroom_overflow_category WHERE category_id = ANY($1)")
+            .bind(category_ids)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
+
+impl MonitoredAutoRoom {
     pub async fn remove(pool: &PgPool, channel_id: i64) -> Result<bool, sqlx::Error> {
         let query = "DELETE FROM monitored_autoroom WHERE channel_id = $1";
         let result = sqlx::query(query)
@@ -155,23 +349,49 @@ impl MonitoredAutoRoom {
         Ok(result.rows_affected() > 0)
     }
     
-    pub async fn new(pool: &PgPool, channel_id: i64, owner_id: i64) {
-        let query = "INSERT INTO monitored_autoroom (channel_id, owner_id) VALUES ($1, $2)";
+    /// Inserts the row of a room that is still being set up, see [`MonitoredAutoRoom::commit`].
+    pub async fn insert_uncommitted(
+        pool: &PgPool,
+        channel_id: i64,
+        owner_id: i64,
+        autoroom_channel_id: i64,
+        room_number: Option<i32>
+    ) -> Result<(), Error> {
+        let query = r#"
+            INSERT INTO monitored_autoroom (channel_id, owner_id, autoroom_channel_id, room_number, committed)
+            VALUES ($1, $2, $3, $4, FALSE)
+        "#;
         sqlx::query(query)
             .bind(channel_id)
             .bind(owner_id)
+            .bind(autoroom_channel_id)
+            .bind(room_number)
             .execute(pool)
             .await
-            .expect(
-                &format!(
-                    "Failed to insert MonitoredAutoRoom, CHANNEL({}) OWNER({})",
-                    channel_id,
-                    owner_id
-                )
-            );
+            .map(|_| ())
     }
 
-    pub async fn insert_many(pool: &PgPool, data: &Vec<Self>) -> Result<(), Error> {
+    pub async fn commit(pool: &PgPool, channel_id: i64) -> Result<bool, Error> {
+        sqlx::query("UPDATE monitored_autoroom SET committed = TRUE WHERE channel_id = $1")
+            .bind(channel_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+    }
+
+    pub async fn get_all_channel_ids(pool: &PgPool) -> Result<Vec<i64>, Error> {
+        sqlx::query_scalar("SELECT channel_id FROM monitored_autoroom")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn get_uncommitted_ids(pool: &PgPool) -> Result<Vec<i64>, Error> {
+        sqlx::query_scalar("SELECT channel_id FROM monitored_autoroom WHERE NOT committed")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn insert_many(pool: &PgPool, data: &[Self]) -> Result<(), Error> {
         let channel_ids: Vec<i64> = data.iter().map(|a| a.channel_id).collect();
         let owner_ids: Vec<i64> = data.iter().map(|a| a.owner_id).collect();
         sqlx::query(
@@ -192,9 +412,25 @@ impl MonitoredAutoRoom {
         Ok(())
     }
 
-    pub async fn get_by_owner_id(pool: &PgPool, owner_id: i64) -> Result<Option<Self>, Error> {
-        match sqlx::query_as::<_, Self>("SELECT channel_id, owner_id FROM monitored_autoroom WHERE owner_id = $1 ORDER BY channel_id DESC LIMIT 1")
+    /// Rooms of the owner across every guild, newest first.
+    pub async fn get_all_by_owner_id(pool: &PgPool, owner_id: i64) -> Result<Vec<Self>, Error> {
+        sqlx::query_as::<_, Self>("SELECT channel_id, owner_id, autoroom_channel_id FROM monitored_autoroom WHERE owner_id = $1 ORDER BY channel_id DESC")
             .bind(owner_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Committed room the member owns from the trigger, used to make room creation idempotent.
+    pub async fn get_by_owner_and_trigger(pool: &PgPool, owner_id: i64, autoroom_channel_id: i64) -> Result<Option<Self>, Error> {
+        match sqlx::query_as::<_, Self>(
+            r#"
+            SELECT channel_id, owner_id, autoroom_channel_id FROM monitored_autoroom
+            WHERE owner_id = $1 AND autoroom_channel_id = $2 AND committed
+            ORDER BY channel_id DESC LIMIT 1
+            "#
+        )
+            .bind(owner_id)
+            .bind(autoroom_channel_id)
             .fetch_one(pool)
             .await {
             Ok(monitored_autoroom) => Ok(Some(monitored_autoroom)),
@@ -205,17 +441,38 @@ impl MonitoredAutoRoom {
         }
     }
 
+    pub async fn get_by_channel_id(pool: &PgPool, channel_id: i64) -> Result<Option<Self>, Error> {
+        match sqlx::query_as::<_, Self>("SELECT channel_id, owner_id, autoroom_channel_id FROM monitored_autoroom WHERE channel_id = $1")
+            .bind(channel_id)
+            .fetch_one(pool)
+            .await {
+            Ok(monitored_autoroom) => Ok(Some(monitored_autoroom)),
+            Err(err) => match err {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    pub async fn set_owner(pool: &PgPool, channel_id: i64, owner_id: i64) -> Result<bool, Error> {
+        let result = sqlx::query("UPDATE monitored_autoroom SET owner_id = $2 WHERE channel_id = $1")
+            .bind(channel_id)
+            .bind(owner_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
-       Ok(
-            sqlx::query_as::<_, Self>(
-                "SELECT channel_id, owner_id from monitored_autoroom"
-            )
-                .fetch_all(pool)
-                .await?
+        sqlx::query_as::<_, Self>(
+            "SELECT channel_id, owner_id, autoroom_channel_id from monitored_autoroom"
         )
+            .fetch_all(pool)
+            .await
     }
 
-    pub async fn remove_many(pool: &PgPool, ids: &Vec<i64>) -> Result<(), Error> {
+    pub async fn remove_many(pool: &PgPool, ids: &[i64]) -> Result<(), Error> {
         sqlx::query(
             "DELETE from monitored_autoroom where channel_id = ANY($1)"
         )
@@ -224,62 +481,224 @@ impl MonitoredAutoRoom {
         .await
         .map(|_| ())
     }
+
+    /// Room numbers in use by rooms created from triggers placing rooms into the category.
+    pub async fn get_category_room_numbers(pool: &PgPool, category_ids: &[i64]) -> Result<Vec<i32>, Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT monitored_autoroom.room_number
+            FROM monitored_autoroom
+            JOIN autoroom ON autoroom.channel_id = monitored_autoroom.autoroom_channel_id
+            WHERE monitored_autoroom.room_number IS NOT NULL
+                AND (
+                    autoroom.category_id = ANY($1)
+                    OR EXISTS (
+                        SELECT 1
+                        FROM autoroom_overflow_category
+                        WHERE autoroom_overflow_category.autoroom_channel_id = autoroom.channel_id
+                            AND autoroom_overflow_category.category_id = ANY($1)
+                    )
+                )
+            "#
+        )
+            .bind(category_ids)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Grace period of the trigger the room was created from, `0` for adopted rooms.
+    pub async fn get_grace_period(pool: &PgPool, channel_id: i64) -> Result<i32, Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(autoroom.grace_period_secs, 0)
+            FROM monitored_autoroom
+            LEFT JOIN autoroom ON autoroom.channel_id = monitored_autoroom.autoroom_channel_id
+            WHERE monitored_autoroom.channel_id = $1
+            "#
+        )
+            .bind(channel_id)
+            .fetch_optional(pool)
+            .await
+            .map(|grace_period| grace_period.unwrap_or(0))
+    }
+
+    pub async fn schedule_deletion(pool: &PgPool, channel_id: i64, delay_secs: f64) -> Result<(), Error> {
+        sqlx::query(
+            "UPDATE monitored_autoroom SET delete_at = now() + make_interval(secs => $2) WHERE channel_id = $1"
+        )
+            .bind(channel_id)
+            .bind(delay_secs)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn cancel_deletion(pool: &PgPool, channel_id: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE monitored_autoroom SET delete_at = NULL WHERE channel_id = $1")
+            .bind(channel_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Rooms waiting for deletion with the seconds left until it, negative when overdue.
+    pub async fn get_pending_deletions(pool: &PgPool) -> Result<Vec<(i64, f64)>, Error> {
+        sqlx::query_as(
+            r#"
+            SELECT channel_id, EXTRACT(EPOCH FROM delete_at - now())::FLOAT8
+            FROM monitored_autoroom
+            WHERE delete_at IS NOT NULL
+            "#
+        )
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn set_panel_message(pool: &PgPool, channel_id: i64, message_id: Option<i64>) -> Result<bool, Error> {
+        sqlx::query("UPDATE monitored_autoroom SET panel_message_id = $2 WHERE channel_id = $1")
+            .bind(channel_id)
+            .bind(message_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+    }
+
+    pub async fn get_panel_message(pool: &PgPool, channel_id: i64) -> Result<Option<i64>, Error> {
+        sqlx::query_scalar("SELECT panel_message_id FROM monitored_autoroom WHERE channel_id = $1")
+            .bind(channel_id)
+            .fetch_optional(pool)
+            .await
+            .map(Option::flatten)
+    }
 }
 
-#[derive(Debug, FromRow)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, FromRow)]
 pub struct PermamentAutoRoom {
-    pub owner_id: i64,
     pub channel_id: i64,
+    pub guild_id: i64,
+    pub owner_id: i64,
     pub placement_category_id: i64,
     pub storage_category_id: i64
 }
 
 impl PermamentAutoRoom {
-    
-}
+    pub fn to_display_string(&self) -> String {
+        format!(
+            "ChannelID: {}, Owner: {}, Placement: {}, Storage: {}",
+            self.channel_id,
+            self.owner_id,
+            self.placement_category_id,
+            self.storage_category_id
+        )
+    }
 
-pub mod table_builder {
-    use async_trait::async_trait;
-    use sqlx::{Error, PgPool, postgres::PgQueryResult};
-    use super::{ AutoRoom, MonitoredAutoRoom };
-
-    #[async_trait]
-    pub trait CreateTable {
-        async fn create_table(pool : &PgPool) -> Result<PgQueryResult, Error>;
-    }
-
-    #[async_trait]
-    impl CreateTable for AutoRoom {
-        async fn create_table(pool : &PgPool) -> Result<PgQueryResult, Error> {
-            sqlx::query(
-                r#"
-                    CREATE TABLE IF NOT EXISTS autoroom (
-                        channel_id BIGINT PRIMARY KEY,
-                        guild_id BIGINT NOT NULL,
-                        category_id BIGINT NOT NULL,
-                        suffix VARCHAR(16) NOT NULL
-                )
-                "#,
-            )
+    pub async fn create(&self, pool: &PgPool) -> Result<(), &'static str> {
+        let query = r#"
+            INSERT INTO permament_autoroom (channel_id, guild_id, owner_id, placement_category_id, storage_category_id)
+            VALUES ($1, $2, $3, $4, $5)
+        "#;
+        tracing::info!(
+            "Inserting PermamentAutoRoom, CHANNEL({}) GUILD({}) OWNER({}) PLACEMENT({}) STORAGE({})",
+            self.channel_id,
+            self.guild_id,
+            self.owner_id,
+            self.placement_category_id,
+            self.storage_category_id
+        );
+        let result = sqlx::query(query)
+            .bind(self.channel_id)
+            .bind(self.guild_id)
+            .bind(self.owner_id)
+            .bind(self.placement_category_id)
+            .bind(self.storage_category_id)
             .execute(pool)
-            .await
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                tracing::error!(
+                    "Failed to insert PermamentAutoRoom, CHANNEL({}) GUILD({}) OWNER({})\nError: `{}`",
+                    self.channel_id,
+                    self.guild_id,
+                    self.owner_id,
+                    err
+                );
+                if let sqlx::Error::Database(db_err) = err {
+                    if db_err.code() == Some("23505".into()) {
+                        return Err("Member already owns a permanent room")
+                    }
+                }
+
+                Err("Internal Server Error")
+            }
         }
     }
 
-    #[async_trait]
-    impl CreateTable for MonitoredAutoRoom {
-        async fn create_table(pool : &PgPool) -> Result<PgQueryResult, Error> {
-            sqlx::query(
-                r#"
-                    CREATE TABLE IF NOT EXISTS monitored_autoroom (
-                        channel_id BIGINT PRIMARY KEY,
-                        owner_id BIGINT NOT NULL
-                )
-                "#,
-            )
+    pub async fn get_by_channel_id(pool: &PgPool, channel_id: i64) -> Result<Option<Self>, Error> {
+        match sqlx::query_as::<_, Self>("SELECT * FROM permament_autoroom WHERE channel_id = $1")
+            .bind(channel_id)
+            .fetch_one(pool)
+            .await {
+            Ok(room) => Ok(Some(room)),
+            Err(err) => match err {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    pub async fn get_by_owner_id(pool: &PgPool, guild_id: i64, owner_id: i64) -> Result<Option<Self>, Error> {
+        match sqlx::query_as::<_, Self>("SELECT * FROM permament_autoroom WHERE guild_id = $1 AND owner_id = $2")
+            .bind(guild_id)
+            .bind(owner_id)
+            .fetch_one(pool)
+            .await {
+            Ok(room) => Ok(Some(room)),
+            Err(err) => match err {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    pub async fn get_guild_rooms(pool: &PgPool, guild_id: i64) -> Result<Vec<Self>, Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM permament_autoroom WHERE guild_id = $1")
+            .bind(guild_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM permament_autoroom")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn set_owner(pool: &PgPool, channel_id: i64, owner_id: i64) -> Result<bool, Error> {
+        let result = sqlx::query("UPDATE permament_autoroom SET owner_id = $2 WHERE channel_id = $1")
+            .bind(channel_id)
+            .bind(owner_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn remove(pool: &PgPool, channel_id: i64) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM permament_autoroom WHERE channel_id = $1")
+            .bind(channel_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn remove_many(pool: &PgPool, ids: &[i64]) -> Result<(), Error> {
+        sqlx::query("DELETE FROM permament_autoroom WHERE channel_id = ANY($1)")
+            .bind(ids)
             .execute(pool)
             .await
-        }
+            .map(|_| ())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,95 @@
+use sqlx::{Error, FromRow, PgPool};
+
+
+/// Channel created by the bot, the only channels a cleanup may delete or adopt.
+#[derive(Debug, Clone, FromRow)]
+pub struct CreatedChannel {
+    pub channel_id: i64,
+    #[allow(dead_code)]
+    pub guild_id: i64,
+    /// Member the room was created for, `None` for categories
+    pub creator_id: Option<i64>
+}
+
+/// Channel a cleanup never deletes or adopts.
+#[derive(Debug, Clone, FromRow)]
+pub struct ProtectedChannel {
+    pub channel_id: i64,
+    #[allow(dead_code)]
+    pub guild_id: i64
+}
+
+impl CreatedChannel {
+    pub async fn record(pool: &PgPool, guild_id: i64, channel_id: i64, creator_id: Option<i64>) -> Result<(), Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO bot_created_channel (channel_id, guild_id, creator_id) VALUES ($1, $2, $3)
+            ON CONFLICT (channel_id) DO NOTHING
+            "#
+        )
+            .bind(channel_id)
+            .bind(guild_id)
+            .bind(creator_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn get_guild(pool: &PgPool, guild_id: i64) -> Result<Vec<Self>, Error> {
+        sqlx::query_as::<_, Self>("SELECT channel_id, guild_id, creator_id FROM bot_created_channel WHERE guild_id = $1")
+            .bind(guild_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn forget_many(pool: &PgPool, channel_ids: &[i64]) -> Result<u64, Error> {
+        sqlx::query("DELETE FROM bot_created_channel WHERE channel_id = ANY($1)")
+            .bind(channel_ids)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
+
+impl ProtectedChannel {
+    pub fn to_display_string(&self) -> String {
+        format!("<#{}>", self.channel_id)
+    }
+
+    pub async fn get_guild(pool: &PgPool, guild_id: i64) -> Result<Vec<Self>, Error> {
+        sqlx::query_as::<_, Self>("SELECT channel_id, guild_id FROM protected_channel WHERE guild_id = $1 ORDER BY channel_id")
+            .bind(guild_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn add(pool: &PgPool, guild_id: i64, channel_id: i64) -> Result<(), &'static str> {
+        let result = sqlx::query("INSERT INTO protected_channel (channel_id, guild_id) VALUES ($1, $2)")
+            .bind(channel_id)
+            .bind(guild_id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                if let sqlx::Error::Database(db_err) = &err {
+                    if db_err.code().as_deref() == Some("23505") {
+                        return Err("Channel is already protected");
+                    }
+                }
+                tracing::error!("Failed to protect CHANNEL({}) GUILD({}).\n{}", channel_id, guild_id, err);
+                Err("Internal server error")
+            }
+        }
+    }
+
+    pub async fn remove(pool: &PgPool, guild_id: i64, channel_id: i64) -> Result<bool, Error> {
+        sqlx::query("DELETE FROM protected_channel WHERE guild_id = $1 AND channel_id = $2")
+            .bind(guild_id)
+            .bind(channel_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+    }
+}
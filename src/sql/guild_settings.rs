@@ -0,0 +1,111 @@
+use poise::ChoiceParameter;
+use sqlx::{Error, FromRow, PgPool};
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[repr(i16)]
+pub enum OwnershipTransferMode {
+    #[default]
+    #[name = "Automatic"]
+    Auto = 0,
+    #[name = "Claim button"]
+    Claim = 1,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct GuildSettings {
+    #[allow(dead_code)]
+    pub guild_id: i64,
+    pub ownership_transfer: OwnershipTransferMode,
+    /// Where admin alerts go, the guild system channel when `None`
+    pub alert_channel_id: Option<i64>,
+    /// Text channel of the voice interface panel, acting on the room of whoever clicks
+    pub interface_channel_id: Option<i64>,
+    pub interface_message_id: Option<i64>
+}
+
+impl GuildSettings {
+    pub fn new(guild_id: i64) -> Self {
+        Self {
+            guild_id,
+            ownership_transfer: OwnershipTransferMode::default(),
+            alert_channel_id: None,
+            interface_channel_id: None,
+            interface_message_id: None
+        }
+    }
+
+    pub fn to_display_string(&self) -> String {
+        format!(
+            "Ownership transfer: {}\nAlert channel: {}\nVoice interface: {}",
+            self.ownership_transfer.name(),
+            self.alert_channel_id.map_or("system channel".to_string(), |id| format!("<#{}>", id)),
+            self.interface_channel_id.map_or("none".to_string(), |id| format!("<#{}>", id))
+        )
+    }
+
+    /// Settings of the guild, defaults when the guild never changed them.
+    pub async fn get(pool: &PgPool, guild_id: i64) -> Result<Self, Error> {
+        match sqlx::query_as::<_, Self>(
+            r#"
+            SELECT guild_id, ownership_transfer, alert_channel_id, interface_channel_id, interface_message_id
+            FROM guild_settings WHERE guild_id = $1
+            "#
+        )
+            .bind(guild_id)
+            .fetch_one(pool)
+            .await {
+            Ok(settings) => Ok(settings),
+            Err(err) => match err {
+                sqlx::Error::RowNotFound => Ok(Self::new(guild_id)),
+                _ => Err(err),
+            },
+        }
+    }
+
+    pub async fn set_ownership_transfer(pool: &PgPool, guild_id: i64, mode: OwnershipTransferMode) -> Result<(), Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO guild_settings (guild_id, ownership_transfer) VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE SET ownership_transfer = EXCLUDED.ownership_transfer
+            "#
+        )
+            .bind(guild_id)
+            .bind(mode)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn set_alert_channel(pool: &PgPool, guild_id: i64, channel_id: Option<i64>) -> Result<(), Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO guild_settings (guild_id, alert_channel_id) VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE SET alert_channel_id = EXCLUDED.alert_channel_id
+            "#
+        )
+            .bind(guild_id)
+            .bind(channel_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Remembers the voice interface panel, `None` forgets it.
+    pub async fn set_interface(pool: &PgPool, guild_id: i64, channel_id: Option<i64>, message_id: Option<i64>) -> Result<(), Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO guild_settings (guild_id, interface_channel_id, interface_message_id) VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id) DO UPDATE SET
+                interface_channel_id = EXCLUDED.interface_channel_id,
+                interface_message_id = EXCLUDED.interface_message_id
+            "#
+        )
+            .bind(guild_id)
+            .bind(channel_id)
+            .bind(message_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,52 @@
+use sqlx::{Error, PgPool};
+
+
+/// Per-host list of members applied to every room the host creates.
+/// A member is on one list of a host at most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemberList {
+    Trusted,
+    Blocked,
+}
+
+impl MemberList {
+    fn table(&self) -> &'static str {
+        match self {
+            Self::Trusted => "trusted_member",
+            Self::Blocked => "blocked_member",
+        }
+    }
+
+    pub fn other(&self) -> Self {
+        match self {
+            Self::Trusted => Self::Blocked,
+            Self::Blocked => Self::Trusted,
+        }
+    }
+
+    /// Members of the host's list, oldest first.
+    pub async fn get(&self, pool: &PgPool, owner_id: i64) -> Result<Vec<i64>, Error> {
+        sqlx::query_scalar(&format!("SELECT member_id FROM {} WHERE owner_id = $1 ORDER BY created_at", self.table()))
+            .bind(owner_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn add(&self, pool: &PgPool, owner_id: i64, member_id: i64) -> Result<bool, Error> {
+        sqlx::query(&format!("INSERT INTO {} (owner_id, member_id) VALUES ($1, $2) ON CONFLICT DO NOTHING", self.table()))
+            .bind(owner_id)
+            .bind(member_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+    }
+
+    pub async fn remove(&self, pool: &PgPool, owner_id: i64, member_id: i64) -> Result<bool, Error> {
+        sqlx::query(&format!("DELETE FROM {} WHERE owner_id = $1 AND member_id = $2", self.table()))
+            .bind(owner_id)
+            .bind(member_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+    }
+}
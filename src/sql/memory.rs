@@ -0,0 +1,568 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use sqlx::Error;
+
+use super::autoroom::{AutoRoom, AutoRoomDeleteStrategy, MonitoredAutoRoom, OverflowCategory, PermamentAutoRoom};
+use super::channel_guard::{CreatedChannel, ProtectedChannel};
+use super::guild_settings::{GuildSettings, OwnershipTransferMode};
+use super::member_list::MemberList;
+use super::repository::{
+    AutoRoomRepository, ChannelGuardRepository, GuildSettingsRepository, MemberListRepository, MonitoredRoomRepository,
+    PermanentRoomRepository, Repositories
+};
+
+
+#[derive(Default)]
+pub struct InMemoryAutoRoomRepository {
+    autorooms: Mutex<HashMap<i64, AutoRoom>>,
+    overflow: Mutex<Vec<OverflowCategory>>,
+}
+
+struct MonitoredRoomRecord {
+    room: MonitoredAutoRoom,
+    room_number: Option<i32>,
+    committed: bool,
+    delete_at: Option<Instant>,
+    panel_message_id: Option<i64>,
+}
+
+/// Rooms in memory, the autorooms are read for room numbers and grace periods like the SQL joins do.
+pub struct InMemoryMonitoredRoomRepository {
+    autorooms: Arc<InMemoryAutoRoomRepository>,
+    rooms: Mutex<HashMap<i64, MonitoredRoomRecord>>,
+}
+
+impl InMemoryMonitoredRoomRepository {
+    pub fn new(autorooms: Arc<InMemoryAutoRoomRepository>) -> Self {
+        Self { autorooms, rooms: Mutex::new(HashMap::new()) }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPermanentRoomRepository {
+    rooms: Mutex<HashMap<i64, PermamentAutoRoom>>,
+}
+
+impl InMemoryPermanentRoomRepository {
+    pub fn insert(&self, room: PermamentAutoRoom) {
+        self.rooms.lock().insert(room.channel_id, room);
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryGuildSettingsRepository {
+    settings: Mutex<HashMap<i64, GuildSettings>>,
+}
+
+impl InMemoryGuildSettingsRepository {
+    pub fn set(&self, settings: GuildSettings) {
+        self.settings.lock().insert(settings.guild_id, settings);
+    }
+
+    fn update(&self, guild_id: i64, change: impl FnOnce(&mut GuildSettings)) {
+        change(self.settings.lock().entry(guild_id).or_insert_with(|| GuildSettings::new(guild_id)));
+    }
+}
+
+/// Ledger and protected channels keyed by channel.
+#[derive(Default)]
+pub struct InMemoryChannelGuardRepository {
+    created: Mutex<HashMap<i64, CreatedChannel>>,
+    protected: Mutex<HashMap<i64, i64>>,
+}
+
+/// Lists in the order the members were added.
+#[derive(Default)]
+pub struct InMemoryMemberListRepository {
+    members: Mutex<Vec<(MemberList, i64, i64)>>,
+}
+
+impl Repositories {
+    pub fn in_memory() -> Self {
+        Self::in_memory_with(Arc::default(), Arc::default())
+    }
+
+    /// In-memory repositories on top of the given permanent rooms and guild settings, so tests can fill them.
+    pub fn in_memory_with(
+        permanent: Arc<InMemoryPermanentRoomRepository>,
+        settings: Arc<InMemoryGuildSettingsRepository>
+    ) -> Self {
+        let autorooms = Arc::new(InMemoryAutoRoomRepository::default());
+        Self {
+            rooms: Arc::new(InMemoryMonitoredRoomRepository::new(autorooms.clone())),
+            autorooms,
+            permanent,
+            settings,
+            channels: Arc::new(InMemoryChannelGuardRepository::default()),
+            lists: Arc::new(InMemoryMemberListRepository::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl AutoRoomRepository for InMemoryAutoRoomRepository {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<AutoRoom>, Error> {
+        Ok(self.autorooms.lock().get(&channel_id).cloned())
+    }
+
+    async fn create(&self, autoroom: &AutoRoom) -> Result<(), &'static str> {
+        let mut autorooms = self.autorooms.lock();
+        if autorooms.contains_key(&autoroom.channel_id) {
+            return Err("Channel already in use");
+        }
+        autorooms.insert(autoroom.channel_id, autoroom.clone());
+        Ok(())
+    }
+
+    async fn update(&self, autoroom: &AutoRoom) -> Result<bool, Error> {
+        Ok(self.autorooms.lock().get_mut(&autoroom.channel_id).map(|stored| *stored = autoroom.clone()).is_some())
+    }
+
+    async fn delete(&self, strategy: AutoRoomDeleteStrategy<'_>) -> Result<(), Error> {
+        let mut autorooms = self.autorooms.lock();
+        autorooms.retain(|_, autoroom| !match &strategy {
+            AutoRoomDeleteStrategy::SingleByChannelId(id) => autoroom.channel_id == *id,
+            AutoRoomDeleteStrategy::SingleByCategoryId(id) => autoroom.category_id == *id,
+            AutoRoomDeleteStrategy::ManyByChannelId(ids) => ids.contains(&autoroom.channel_id),
+            AutoRoomDeleteStrategy::ManyByCategoryId(ids) => ids.contains(&autoroom.category_id),
+        });
+        // Overflow categories are removed with their autoroom, like the foreign key cascade does
+        self.overflow.lock().retain(|category| autorooms.contains_key(&category.autoroom_channel_id));
+        Ok(())
+    }
+
+    async fn get_guild_autorooms(&self, guild_id: i64) -> Result<Vec<AutoRoom>, Error> {
+        Ok(self.autorooms.lock().values().filter(|autoroom| autoroom.guild_id == guild_id).cloned().collect())
+    }
+
+    async fn get_all_channel_ids(&self) -> Result<Vec<i64>, Error> {
+        Ok(self.autorooms.lock().keys().copied().collect())
+    }
+
+    async fn get_all_category_ids(&self) -> Result<Vec<i64>, Error> {
+        let mut category_ids: Vec<i64> = self.autorooms.lock().values().map(|autoroom| autoroom.category_id).collect();
+        category_ids.extend(self.overflow.lock().iter().map(|category| category.category_id));
+        category_ids.sort();
+        category_ids.dedup();
+        Ok(category_ids)
+    }
+
+    async fn get_overflow_categories(&self, autoroom_channel_id: i64) -> Result<Vec<OverflowCategory>, Error> {
+        let mut categories: Vec<OverflowCategory> = self.overflow
+            .lock()
+            .iter()
+            .filter(|category| category.autoroom_channel_id == autoroom_channel_id)
+            .cloned()
+            .collect();
+        categories.sort_by_key(|category| category.position);
+        Ok(categories)
+    }
+
+    async fn push_overflow_category(
+        &self,
+        autoroom_channel_id: i64,
+        category_id: i64,
+        created_by_bot: bool
+    ) -> Result<OverflowCategory, &'static str> {
+        if !self.autorooms.lock().contains_key(&autoroom_channel_id) {
+            return Err("Autoroom with given channel was not found");
+        }
+        let mut overflow = self.overflow.lock();
+        let listed = overflow.iter().filter(|category| category.autoroom_channel_id == autoroom_channel_id);
+        if listed.clone().any(|category| category.category_id == category_id) {
+            return Err("Category is already an overflow category of this autoroom");
+        }
+        let category = OverflowCategory {
+            autoroom_channel_id,
+            category_id,
+            position: listed.map(|category| category.position).max().unwrap_or(0) + 1,
+            created_by_bot,
+        };
+        overflow.push(category.clone());
+        Ok(category)
+    }
+
+    async fn remove_overflow_category(&self, autoroom_channel_id: i64, category_id: i64) -> Result<bool, Error> {
+        let mut overflow = self.overflow.lock();
+        let before = overflow.len();
+        overflow.retain(|category| category.autoroom_channel_id != autoroom_channel_id || category.category_id != category_id);
+        Ok(overflow.len() < before)
+    }
+
+    async fn remove_overflow_categories(&self, category_ids: &[i64]) -> Result<u64, Error> {
+        let mut overflow = self.overflow.lock();
+        let before = overflow.len();
+        overflow.retain(|category| !category_ids.contains(&category.category_id));
+        Ok((before - overflow.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl MonitoredRoomRepository for InMemoryMonitoredRoomRepository {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<MonitoredAutoRoom>, Error> {
+        Ok(self.rooms.lock().get(&channel_id).map(|record| record.room.clone()))
+    }
+
+    async fn get_all_by_owner_id(&self, owner_id: i64) -> Result<Vec<MonitoredAutoRoom>, Error> {
+        let mut rooms: Vec<MonitoredAutoRoom> = self.rooms
+            .lock()
+            .values()
+            .filter(|record| record.room.owner_id == owner_id)
+            .map(|record| record.room.clone())
+            .collect();
+        rooms.sort_by_key(|room| Reverse(room.channel_id));
+        Ok(rooms)
+    }
+
+    async fn get_by_owner_and_trigger(&self, owner_id: i64, autoroom_channel_id: i64) -> Result<Option<MonitoredAutoRoom>, Error> {
+        Ok(self.rooms
+            .lock()
+            .values()
+            .filter(|record| record.committed)
+            .filter(|record| record.room.owner_id == owner_id && record.room.autoroom_channel_id == Some(autoroom_channel_id))
+            .max_by_key(|record| record.room.channel_id)
+            .map(|record| record.room.clone()))
+    }
+
+    async fn get_all(&self) -> Result<Vec<MonitoredAutoRoom>, Error> {
+        Ok(self.rooms.lock().values().map(|record| record.room.clone()).collect())
+    }
+
+    async fn get_all_channel_ids(&self) -> Result<Vec<i64>, Error> {
+        Ok(self.rooms.lock().keys().copied().collect())
+    }
+
+    async fn get_uncommitted_ids(&self) -> Result<Vec<i64>, Error> {
+        Ok(self.rooms.lock().values().filter(|record| !record.committed).map(|record| record.room.channel_id).collect())
+    }
+
+    async fn insert_uncommitted(
+        &self,
+        channel_id: i64,
+        owner_id: i64,
+        autoroom_channel_id: i64,
+        room_number: Option<i32>
+    ) -> Result<(), Error> {
+        self.rooms.lock().insert(channel_id, MonitoredRoomRecord {
+            room: MonitoredAutoRoom { channel_id, owner_id, autoroom_channel_id: Some(autoroom_channel_id) },
+            room_number,
+            committed: false,
+            delete_at: None,
+            panel_message_id: None,
+        });
+        Ok(())
+    }
+
+    async fn commit(&self, channel_id: i64) -> Result<bool, Error> {
+        Ok(self.rooms.lock().get_mut(&channel_id).map(|record| record.committed = true).is_some())
+    }
+
+    async fn insert_many(&self, rooms: &[MonitoredAutoRoom]) -> Result<(), Error> {
+        let mut records = self.rooms.lock();
+        for room in rooms {
+            records.entry(room.channel_id).or_insert_with(|| MonitoredRoomRecord {
+                room: MonitoredAutoRoom { channel_id: room.channel_id, owner_id: room.owner_id, autoroom_channel_id: None },
+                room_number: None,
+                committed: true,
+                delete_at: None,
+                panel_message_id: None,
+            });
+        }
+        Ok(())
+    }
+
+    async fn set_owner(&self, channel_id: i64, owner_id: i64) -> Result<bool, Error> {
+        Ok(self.rooms.lock().get_mut(&channel_id).map(|record| record.room.owner_id = owner_id).is_some())
+    }
+
+    async fn remove(&self, channel_id: i64) -> Result<bool, Error> {
+        Ok(self.rooms.lock().remove(&channel_id).is_some())
+    }
+
+    async fn remove_many(&self, ids: &[i64]) -> Result<(), Error> {
+        self.rooms.lock().retain(|channel_id, _| !ids.contains(channel_id));
+        Ok(())
+    }
+
+    async fn get_category_room_numbers(&self, category_ids: &[i64]) -> Result<Vec<i32>, Error> {
+        let autorooms = self.autorooms.autorooms.lock();
+        let overflow = self.autorooms.overflow.lock();
+        let places_into = |autoroom_channel_id: i64| {
+            autorooms.get(&autoroom_channel_id).is_some_and(|autoroom| category_ids.contains(&autoroom.category_id))
+                || overflow.iter().any(|category| {
+                    category.autoroom_channel_id == autoroom_channel_id && category_ids.contains(&category.category_id)
+                })
+        };
+        Ok(self.rooms
+            .lock()
+            .values()
+            .filter(|record| record.room.autoroom_channel_id.is_some_and(places_into))
+            .filter_map(|record| record.room_number)
+            .collect())
+    }
+
+    async fn get_grace_period(&self, channel_id: i64) -> Result<i32, Error> {
+        let autorooms = self.autorooms.autorooms.lock();
+        Ok(self.rooms
+            .lock()
+            .get(&channel_id)
+            .and_then(|record| record.room.autoroom_channel_id)
+            .and_then(|id| autorooms.get(&id))
+            .map_or(0, |autoroom| autoroom.grace_period_secs))
+    }
+
+    async fn schedule_deletion(&self, channel_id: i64, delay_secs: f64) -> Result<(), Error> {
+        if let Some(record) = self.rooms.lock().get_mut(&channel_id) {
+            record.delete_at = Some(Instant::now() + std::time::Duration::from_secs_f64(delay_secs));
+        }
+        Ok(())
+    }
+
+    async fn cancel_deletion(&self, channel_id: i64) -> Result<(), Error> {
+        if let Some(record) = self.rooms.lock().get_mut(&channel_id) {
+            record.delete_at = None;
+        }
+        Ok(())
+    }
+
+    async fn get_pending_deletions(&self) -> Result<Vec<(i64, f64)>, Error> {
+        let now = Instant::now();
+        Ok(self.rooms
+            .lock()
+            .values()
+            .filter_map(|record| record.delete_at.map(|delete_at| {
+                let remaining = match delete_at >= now {
+                    true => (delete_at - now).as_secs_f64(),
+                    false => -(now - delete_at).as_secs_f64(),
+                };
+                (record.room.channel_id, remaining)
+            }))
+            .collect())
+    }
+
+    async fn set_panel_message(&self, channel_id: i64, message_id: Option<i64>) -> Result<bool, Error> {
+        Ok(self.rooms.lock().get_mut(&channel_id).map(|record| record.panel_message_id = message_id).is_some())
+    }
+
+    async fn get_panel_message(&self, channel_id: i64) -> Result<Option<i64>, Error> {
+        Ok(self.rooms.lock().get(&channel_id).and_then(|record| record.panel_message_id))
+    }
+}
+
+#[async_trait]
+impl PermanentRoomRepository for InMemoryPermanentRoomRepository {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<PermamentAutoRoom>, Error> {
+        Ok(self.rooms.lock().get(&channel_id).cloned())
+    }
+
+    async fn get_by_owner_id(&self, guild_id: i64, owner_id: i64) -> Result<Option<PermamentAutoRoom>, Error> {
+        Ok(self.rooms
+            .lock()
+            .values()
+            .find(|room| room.guild_id == guild_id && room.owner_id == owner_id)
+            .cloned())
+    }
+
+    async fn get_guild_rooms(&self, guild_id: i64) -> Result<Vec<PermamentAutoRoom>, Error> {
+        Ok(self.rooms.lock().values().filter(|room| room.guild_id == guild_id).cloned().collect())
+    }
+
+    async fn get_all(&self) -> Result<Vec<PermamentAutoRoom>, Error> {
+        Ok(self.rooms.lock().values().cloned().collect())
+    }
+
+    async fn create(&self, room: &PermamentAutoRoom) -> Result<(), &'static str> {
+        let mut rooms = self.rooms.lock();
+        if rooms.values().any(|existing| existing.guild_id == room.guild_id && existing.owner_id == room.owner_id) {
+            return Err("Member already owns a permanent room");
+        }
+        rooms.insert(room.channel_id, room.clone());
+        Ok(())
+    }
+
+    async fn set_owner(&self, channel_id: i64, owner_id: i64) -> Result<bool, Error> {
+        Ok(self.rooms.lock().get_mut(&channel_id).map(|room| room.owner_id = owner_id).is_some())
+    }
+
+    async fn remove(&self, channel_id: i64) -> Result<bool, Error> {
+        Ok(self.rooms.lock().remove(&channel_id).is_some())
+    }
+
+    async fn remove_many(&self, ids: &[i64]) -> Result<(), Error> {
+        self.rooms.lock().retain(|channel_id, _| !ids.contains(channel_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GuildSettingsRepository for InMemoryGuildSettingsRepository {
+    async fn get(&self, guild_id: i64) -> Result<GuildSettings, Error> {
+        Ok(self.settings.lock().get(&guild_id).cloned().unwrap_or_else(|| GuildSettings::new(guild_id)))
+    }
+
+    async fn set_ownership_transfer(&self, guild_id: i64, mode: OwnershipTransferMode) -> Result<(), Error> {
+        self.update(guild_id, |settings| settings.ownership_transfer = mode);
+        Ok(())
+    }
+
+    async fn set_alert_channel(&self, guild_id: i64, channel_id: Option<i64>) -> Result<(), Error> {
+        self.update(guild_id, |settings| settings.alert_channel_id = channel_id);
+        Ok(())
+    }
+
+    async fn set_interface(&self, guild_id: i64, channel_id: Option<i64>, message_id: Option<i64>) -> Result<(), Error> {
+        self.update(guild_id, |settings| {
+            settings.interface_channel_id = channel_id;
+            settings.interface_message_id = message_id;
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChannelGuardRepository for InMemoryChannelGuardRepository {
+    async fn record_created(&self, guild_id: i64, channel_id: i64, creator_id: Option<i64>) -> Result<(), Error> {
+        self.created.lock().entry(channel_id).or_insert(CreatedChannel { channel_id, guild_id, creator_id });
+        Ok(())
+    }
+
+    async fn get_created(&self, guild_id: i64) -> Result<Vec<CreatedChannel>, Error> {
+        Ok(self.created.lock().values().filter(|channel| channel.guild_id == guild_id).cloned().collect())
+    }
+
+    async fn forget_created(&self, channel_ids: &[i64]) -> Result<u64, Error> {
+        let mut created = self.created.lock();
+        let before = created.len();
+        created.retain(|id, _| !channel_ids.contains(id));
+        Ok((before - created.len()) as u64)
+    }
+
+    async fn get_protected(&self, guild_id: i64) -> Result<Vec<ProtectedChannel>, Error> {
+        let mut protected: Vec<ProtectedChannel> = self.protected
+            .lock()
+            .iter()
+            .filter(|(_, guild)| **guild == guild_id)
+            .map(|(channel_id, guild_id)| ProtectedChannel { channel_id: *channel_id, guild_id: *guild_id })
+            .collect();
+        protected.sort_by_key(|channel| channel.channel_id);
+        Ok(protected)
+    }
+
+    async fn protect(&self, guild_id: i64, channel_id: i64) -> Result<(), &'static str> {
+        let mut protected = self.protected.lock();
+        if protected.contains_key(&channel_id) {
+            return Err("Channel is already protected");
+        }
+        protected.insert(channel_id, guild_id);
+        Ok(())
+    }
+
+    async fn unprotect(&self, guild_id: i64, channel_id: i64) -> Result<bool, Error> {
+        let mut protected = self.protected.lock();
+        match protected.get(&channel_id) {
+            Some(guild) if *guild == guild_id => Ok(protected.remove(&channel_id).is_some()),
+            _ => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl MemberListRepository for InMemoryMemberListRepository {
+    async fn get_members(&self, list: MemberList, owner_id: i64) -> Result<Vec<i64>, Error> {
+        Ok(self.members
+            .lock()
+            .iter()
+            .filter(|(listed, owner, _)| *listed == list && *owner == owner_id)
+            .map(|(_, _, member_id)| *member_id)
+            .collect())
+    }
+
+    async fn add_member(&self, list: MemberList, owner_id: i64, member_id: i64) -> Result<bool, Error> {
+        let mut members = self.members.lock();
+        if members.contains(&(list, owner_id, member_id)) {
+            return Ok(false);
+        }
+        members.push((list, owner_id, member_id));
+        Ok(true)
+    }
+
+    async fn remove_member(&self, list: MemberList, owner_id: i64, member_id: i64) -> Result<bool, Error> {
+        let mut members = self.members.lock();
+        let before = members.len();
+        members.retain(|entry| *entry != (list, owner_id, member_id));
+        Ok(members.len() < before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::autoroom::RoomPrivacy;
+
+
+    fn autoroom(channel_id: i64, category_id: i64, grace_period_secs: i32) -> AutoRoom {
+        AutoRoom {
+            channel_id,
+            guild_id: 1,
+            category_id,
+            suffix: "room".to_string(),
+            grace_period_secs,
+            privacy: RoomPrivacy::Public,
+            name_template: "{nick}".to_string(),
+            user_limit: None,
+            bitrate_cap: None,
+            rtc_region: None,
+            video_quality: None,
+            nsfw: false,
+            clone_trigger: false,
+            room_panel: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn room_numbers_are_shared_by_category() {
+        let repositories = Repositories::in_memory();
+        repositories.autorooms.create(&autoroom(10, 100, 0)).await.unwrap();
+        repositories.autorooms.create(&autoroom(11, 100, 0)).await.unwrap();
+        repositories.autorooms.create(&autoroom(12, 200, 0)).await.unwrap();
+        repositories.rooms.insert_uncommitted(1, 7, 10, Some(1)).await.unwrap();
+        repositories.rooms.insert_uncommitted(2, 8, 11, Some(2)).await.unwrap();
+        repositories.rooms.insert_uncommitted(3, 9, 12, Some(1)).await.unwrap();
+
+        let mut taken = repositories.rooms.get_category_room_numbers(&[100]).await.unwrap();
+        taken.sort();
+        assert_eq!(taken, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn only_committed_rooms_are_reused() {
+        let repositories = Repositories::in_memory();
+        repositories.autorooms.create(&autoroom(10, 100, 30)).await.unwrap();
+        repositories.rooms.insert_uncommitted(1, 7, 10, None).await.unwrap();
+        assert!(repositories.rooms.get_by_owner_and_trigger(7, 10).await.unwrap().is_none());
+        assert_eq!(repositories.rooms.get_uncommitted_ids().await.unwrap(), vec![1]);
+
+        assert!(repositories.rooms.commit(1).await.unwrap());
+        assert_eq!(repositories.rooms.get_by_owner_and_trigger(7, 10).await.unwrap().map(|room| room.channel_id), Some(1));
+        assert_eq!(repositories.rooms.get_grace_period(1).await.unwrap(), 30);
+    }
+
+    #[tokio::test]
+    async fn pending_deletions_are_listed_until_cancelled() {
+        let repositories = Repositories::in_memory();
+        repositories.rooms.insert_uncommitted(1, 7, 10, None).await.unwrap();
+        repositories.rooms.schedule_deletion(1, 60.0).await.unwrap();
+
+        let pending = repositories.rooms.get_pending_deletions().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].1 > 59.0 && pending[0].1 <= 60.0);
+
+        repositories.rooms.cancel_deletion(1).await.unwrap();
+        assert!(repositories.rooms.get_pending_deletions().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::PgPool;
+
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+pub fn latest_version() -> i64 {
+    MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Applies every pending migration in version order.
+///
+/// Applied migrations are tracked in `_sqlx_migrations` together with their checksums,
+/// so an edited migration fails with `VersionMismatch` and a database migrated by a newer
+/// binary fails with `VersionMissing` instead of being used with an unknown schema.
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrateError> {
+    let latest = latest_version();
+    tracing::info!("Running migrations, latest known version ({})", latest);
+
+    MIGRATOR.run(pool).await.inspect_err(|err| match err {
+        MigrateError::VersionMissing(version) => tracing::error!(
+            "Database has migration ({}) unknown to this binary (latest {}). The database is ahead of the binary",
+            version,
+            latest
+        ),
+        MigrateError::VersionMismatch(version) => tracing::error!(
+            "Migration ({}) was modified after it had been applied",
+            version
+        ),
+        _ => (),
+    })?;
+
+    tracing::info!("Migrations are up to date, version ({})", latest);
+    Ok(())
+}
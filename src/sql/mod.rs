@@ -1,49 +1,26 @@
 pub mod autoroom;
+pub mod channel_guard;
+pub mod guild_settings;
+pub mod member_list;
+pub mod migrations;
+pub mod reconcile_run;
+pub mod repository;
+#[cfg(test)]
+pub mod memory;
 
 
 pub mod prelude {
     use serenity::prelude::TypeMapKey;
-    use sqlx::{Error, PgPool};
-
-    use crate::sql::autoroom::table_builder::CreateTable;
+    use sqlx::PgPool;
 
     pub use super::autoroom::{AutoRoom, MonitoredAutoRoom};
+    pub use super::migrations::run_migrations;
+    pub use super::repository::Repositories;
     use super::SerenityPool;
     
     impl TypeMapKey for SerenityPool {
         type Value = PgPool;
     }
-
-    pub async fn create_tables(pool : &PgPool) -> Result<(), Error> {
-        AutoRoom::create_table(pool).await?;
-        MonitoredAutoRoom::create_table(pool).await?;
-
-        Ok(())
-    }
 }
 
 pub struct SerenityPool;
-
-
-pub mod pool {
-    use once_cell::sync::OnceCell;
-    use sqlx::{Pool, Postgres};
-
-    pub type PoolType = Pool<Postgres>;
-
-    pub struct SqlPool {
-        pool: PoolType
-    }
-
-    impl SqlPool {
-        pub fn get_pool(&self) -> PoolType {
-            self.pool.clone()
-        }
-
-        pub fn new(pool: PoolType) -> Self {
-            Self { pool }
-        }
-    }
-
-    pub static GLOBAL_SQL_POOL: OnceCell<SqlPool> = OnceCell::new();
-}
\ No newline at end of file
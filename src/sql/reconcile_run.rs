@@ -0,0 +1,135 @@
+use sqlx::pool::PoolConnection;
+use sqlx::{Error, FromRow, PgPool, Postgres};
+
+
+/// Key of the advisory lock held while a reconciliation runs, "AutoRoom" in ASCII
+const RECONCILE_LOCK_KEY: i64 = 0x4175_746f_526f_6f6d;
+
+/// Counters of a finished reconciliation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReconcileCounts {
+    pub stale_rows: i32,
+    pub deleted: i32,
+    pub adopted: i32,
+    pub skipped: i32
+}
+
+/// Background reconciliation, times are seconds relative to now.
+#[derive(Debug, Clone, FromRow)]
+pub struct ReconcileRun {
+    #[allow(dead_code)]
+    pub id: i64,
+    pub started_secs_ago: f64,
+    /// `None` while the run is in progress
+    pub duration_secs: Option<f64>,
+    pub succeeded: Option<bool>,
+    pub stale_rows: i32,
+    pub deleted: i32,
+    pub adopted: i32,
+    pub skipped: i32,
+    pub error: Option<String>
+}
+
+impl ReconcileRun {
+    pub fn to_display_string(&self) -> String {
+        let status = match (self.succeeded, self.duration_secs) {
+            (Some(true), Some(duration)) => format!("succeeded in {:.1}s", duration),
+            (Some(false), _) => format!("failed: {}", self.error.as_deref().unwrap_or("unknown error")),
+            _ => "running".to_string(),
+        };
+        format!(
+            "{:.0}s ago, {} | Stale rows: {} | Deleted: {} | Adopted: {} | Skipped: {}",
+            self.started_secs_ago,
+            status,
+            self.stale_rows,
+            self.deleted,
+            self.adopted,
+            self.skipped
+        )
+    }
+
+    /// Records the start of a run, returns its id.
+    pub async fn start(pool: &PgPool) -> Result<i64, Error> {
+        sqlx::query_scalar("INSERT INTO reconcile_run DEFAULT VALUES RETURNING id")
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn finish(pool: &PgPool, id: i64, counts: &ReconcileCounts, error: Option<&str>) -> Result<(), Error> {
+        sqlx::query(
+            r#"
+            UPDATE reconcile_run
+            SET finished_at = now(), succeeded = $2, stale_rows = $3, deleted = $4, adopted = $5, skipped = $6, error = $7
+            WHERE id = $1
+            "#
+        )
+            .bind(id)
+            .bind(error.is_none())
+            .bind(counts.stale_rows)
+            .bind(counts.deleted)
+            .bind(counts.adopted)
+            .bind(counts.skipped)
+            .bind(error)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn get_last(pool: &PgPool) -> Result<Option<Self>, Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT
+                id,
+                EXTRACT(EPOCH FROM now() - started_at)::FLOAT8 AS started_secs_ago,
+                EXTRACT(EPOCH FROM finished_at - started_at)::FLOAT8 AS duration_secs,
+                succeeded, stale_rows, deleted, adopted, skipped, error
+            FROM reconcile_run
+            ORDER BY id DESC
+            LIMIT 1
+            "#
+        )
+            .fetch_optional(pool)
+            .await
+    }
+}
+
+/// Session advisory lock, so only one instance of the bot reconciles at a time.
+pub struct ReconcileLock {
+    connection: Option<PoolConnection<Postgres>>
+}
+
+impl ReconcileLock {
+    /// `None` when another instance holds the lock.
+    pub async fn try_acquire(pool: &PgPool) -> Result<Option<Self>, Error> {
+        let mut connection = pool.acquire().await?;
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(RECONCILE_LOCK_KEY)
+            .fetch_one(&mut *connection)
+            .await?;
+        Ok(locked.then_some(Self { connection: Some(connection) }))
+    }
+
+    pub async fn release(mut self) -> Result<(), Error> {
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+        let result = sqlx::query("SELECT pg_advisory_unlock($1)")
+            .bind(RECONCILE_LOCK_KEY)
+            .execute(&mut *connection)
+            .await;
+        if result.is_err() {
+            drop(connection.detach());
+        }
+        result.map(|_| ())
+    }
+}
+
+impl Drop for ReconcileLock {
+    // A pooled connection keeps its session locks, one that was not released is closed instead of reused
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            drop(connection.detach());
+        }
+    }
+}
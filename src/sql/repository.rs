@@ -0,0 +1,419 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serenity::all::Context;
+use serenity::prelude::TypeMapKey;
+use sqlx::{Error, PgPool};
+
+use super::autoroom::{AutoRoom, AutoRoomDeleteStrategy, MonitoredAutoRoom, OverflowCategory, PermamentAutoRoom};
+use super::channel_guard::{CreatedChannel, ProtectedChannel};
+use super::guild_settings::{GuildSettings, OwnershipTransferMode};
+use super::member_list::MemberList;
+use super::SerenityPool;
+
+
+#[async_trait]
+pub trait AutoRoomRepository: Send + Sync {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<AutoRoom>, Error>;
+    async fn create(&self, autoroom: &AutoRoom) -> Result<(), &'static str>;
+    async fn update(&self, autoroom: &AutoRoom) -> Result<bool, Error>;
+    async fn delete(&self, strategy: AutoRoomDeleteStrategy<'_>) -> Result<(), Error>;
+    async fn get_guild_autorooms(&self, guild_id: i64) -> Result<Vec<AutoRoom>, Error>;
+    async fn get_all_channel_ids(&self) -> Result<Vec<i64>, Error>;
+    /// Main and overflow categories of every autoroom.
+    async fn get_all_category_ids(&self) -> Result<Vec<i64>, Error>;
+    /// Overflow categories of the autoroom in the order they are tried.
+    async fn get_overflow_categories(&self, autoroom_channel_id: i64) -> Result<Vec<OverflowCategory>, Error>;
+    async fn push_overflow_category(
+        &self,
+        autoroom_channel_id: i64,
+        category_id: i64,
+        created_by_bot: bool
+    ) -> Result<OverflowCategory, &'static str>;
+    async fn remove_overflow_category(&self, autoroom_channel_id: i64, category_id: i64) -> Result<bool, Error>;
+    /// Drops deleted categories from every overflow list.
+    async fn remove_overflow_categories(&self, category_ids: &[i64]) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait MonitoredRoomRepository: Send + Sync {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<MonitoredAutoRoom>, Error>;
+    /// Rooms of the owner across every guild, newest first.
+    async fn get_all_by_owner_id(&self, owner_id: i64) -> Result<Vec<MonitoredAutoRoom>, Error>;
+    /// Committed room the member owns from the trigger.
+    async fn get_by_owner_and_trigger(&self, owner_id: i64, autoroom_channel_id: i64) -> Result<Option<MonitoredAutoRoom>, Error>;
+    async fn get_all(&self) -> Result<Vec<MonitoredAutoRoom>, Error>;
+    async fn get_all_channel_ids(&self) -> Result<Vec<i64>, Error>;
+    async fn get_uncommitted_ids(&self) -> Result<Vec<i64>, Error>;
+    async fn insert_uncommitted(
+        &self,
+        channel_id: i64,
+        owner_id: i64,
+        autoroom_channel_id: i64,
+        room_number: Option<i32>
+    ) -> Result<(), Error>;
+    async fn commit(&self, channel_id: i64) -> Result<bool, Error>;
+    async fn insert_many(&self, rooms: &[MonitoredAutoRoom]) -> Result<(), Error>;
+    async fn set_owner(&self, channel_id: i64, owner_id: i64) -> Result<bool, Error>;
+    async fn remove(&self, channel_id: i64) -> Result<bool, Error>;
+    async fn remove_many(&self, ids: &[i64]) -> Result<(), Error>;
+    /// Room numbers taken by the rooms of every autoroom placing rooms into one of the categories,
+    /// as its main or as an overflow category.
+    async fn get_category_room_numbers(&self, category_ids: &[i64]) -> Result<Vec<i32>, Error>;
+    /// Grace period of the trigger the room was created from, `0` for adopted rooms.
+    async fn get_grace_period(&self, channel_id: i64) -> Result<i32, Error>;
+    async fn schedule_deletion(&self, channel_id: i64, delay_secs: f64) -> Result<(), Error>;
+    async fn cancel_deletion(&self, channel_id: i64) -> Result<(), Error>;
+    /// Rooms waiting for deletion with the seconds left until it, negative when overdue.
+    async fn get_pending_deletions(&self) -> Result<Vec<(i64, f64)>, Error>;
+    /// Stores the control panel message of the room, `false` when the room is not monitored.
+    async fn set_panel_message(&self, channel_id: i64, message_id: Option<i64>) -> Result<bool, Error>;
+    async fn get_panel_message(&self, channel_id: i64) -> Result<Option<i64>, Error>;
+}
+
+#[async_trait]
+pub trait PermanentRoomRepository: Send + Sync {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<PermamentAutoRoom>, Error>;
+    async fn get_by_owner_id(&self, guild_id: i64, owner_id: i64) -> Result<Option<PermamentAutoRoom>, Error>;
+    async fn get_guild_rooms(&self, guild_id: i64) -> Result<Vec<PermamentAutoRoom>, Error>;
+    async fn get_all(&self) -> Result<Vec<PermamentAutoRoom>, Error>;
+    async fn create(&self, room: &PermamentAutoRoom) -> Result<(), &'static str>;
+    async fn set_owner(&self, channel_id: i64, owner_id: i64) -> Result<bool, Error>;
+    async fn remove(&self, channel_id: i64) -> Result<bool, Error>;
+    async fn remove_many(&self, ids: &[i64]) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait GuildSettingsRepository: Send + Sync {
+    /// Settings of the guild, defaults when the guild never changed them.
+    async fn get(&self, guild_id: i64) -> Result<GuildSettings, Error>;
+    async fn set_ownership_transfer(&self, guild_id: i64, mode: OwnershipTransferMode) -> Result<(), Error>;
+    async fn set_alert_channel(&self, guild_id: i64, channel_id: Option<i64>) -> Result<(), Error>;
+    async fn set_interface(&self, guild_id: i64, channel_id: Option<i64>, message_id: Option<i64>) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait ChannelGuardRepository: Send + Sync {
+    /// Remembers a channel the bot created, so cleanups may delete it later.
+    async fn record_created(&self, guild_id: i64, channel_id: i64, creator_id: Option<i64>) -> Result<(), Error>;
+    async fn get_created(&self, guild_id: i64) -> Result<Vec<CreatedChannel>, Error>;
+    /// Drops channels that no longer exist from the ledger.
+    async fn forget_created(&self, channel_ids: &[i64]) -> Result<u64, Error>;
+    async fn get_protected(&self, guild_id: i64) -> Result<Vec<ProtectedChannel>, Error>;
+    async fn protect(&self, guild_id: i64, channel_id: i64) -> Result<(), &'static str>;
+    async fn unprotect(&self, guild_id: i64, channel_id: i64) -> Result<bool, Error>;
+}
+
+#[async_trait]
+pub trait MemberListRepository: Send + Sync {
+    /// Members of the host's list, oldest first.
+    async fn get_members(&self, list: MemberList, owner_id: i64) -> Result<Vec<i64>, Error>;
+    /// `false` when the member is already on the list.
+    async fn add_member(&self, list: MemberList, owner_id: i64, member_id: i64) -> Result<bool, Error>;
+    async fn remove_member(&self, list: MemberList, owner_id: i64, member_id: i64) -> Result<bool, Error>;
+}
+
+pub struct PgAutoRoomRepository {
+    pool: PgPool
+}
+
+pub struct PgMonitoredRoomRepository {
+    pool: PgPool
+}
+
+pub struct PgPermanentRoomRepository {
+    pool: PgPool
+}
+
+pub struct PgGuildSettingsRepository {
+    pool: PgPool
+}
+
+pub struct PgChannelGuardRepository {
+    pool: PgPool
+}
+
+pub struct PgMemberListRepository {
+    pool: PgPool
+}
+
+#[async_trait]
+impl AutoRoomRepository for PgAutoRoomRepository {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<AutoRoom>, Error> {
+        AutoRoom::get_by_channel_id(&self.pool, channel_id).await
+    }
+
+    async fn create(&self, autoroom: &AutoRoom) -> Result<(), &'static str> {
+        autoroom.create(&self.pool).await
+    }
+
+    async fn update(&self, autoroom: &AutoRoom) -> Result<bool, Error> {
+        autoroom.update(&self.pool).await
+    }
+
+    async fn delete(&self, strategy: AutoRoomDeleteStrategy<'_>) -> Result<(), Error> {
+        AutoRoom::delete(&self.pool, strategy).await
+    }
+
+    async fn get_guild_autorooms(&self, guild_id: i64) -> Result<Vec<AutoRoom>, Error> {
+        AutoRoom::get_guild_autorooms(&self.pool, guild_id).await
+    }
+
+    async fn get_all_channel_ids(&self) -> Result<Vec<i64>, Error> {
+        AutoRoom::get_all_channel_ids(&self.pool).await
+    }
+
+    async fn get_all_category_ids(&self) -> Result<Vec<i64>, Error> {
+        AutoRoom::get_all_category_ids(&self.pool).await
+    }
+
+    async fn get_overflow_categories(&self, autoroom_channel_id: i64) -> Result<Vec<OverflowCategory>, Error> {
+        OverflowCategory::get_by_autoroom(&self.pool, autoroom_channel_id).await
+    }
+
+    async fn push_overflow_category(
+        &self,
+        autoroom_channel_id: i64,
+        category_id: i64,
+        created_by_bot: bool
+    ) -> Result<OverflowCategory, &'static str> {
+        OverflowCategory::push(&self.pool, autoroom_channel_id, category_id, created_by_bot).await
+    }
+
+    async fn remove_overflow_category(&self, autoroom_channel_id: i64, category_id: i64) -> Result<bool, Error> {
+        OverflowCategory::remove(&self.pool, autoroom_channel_id, category_id).await
+    }
+
+    async fn remove_overflow_categories(&self, category_ids: &[i64]) -> Result<u64, Error> {
+        OverflowCategory::remove_many(&self.pool, category_ids).await
+    }
+}
+
+#[async_trait]
+impl MonitoredRoomRepository for PgMonitoredRoomRepository {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<MonitoredAutoRoom>, Error> {
+        MonitoredAutoRoom::get_by_channel_id(&self.pool, channel_id).await
+    }
+
+    async fn get_all_by_owner_id(&self, owner_id: i64) -> Result<Vec<MonitoredAutoRoom>, Error> {
+        MonitoredAutoRoom::get_all_by_owner_id(&self.pool, owner_id).await
+    }
+
+    async fn get_by_owner_and_trigger(&self, owner_id: i64, autoroom_channel_id: i64) -> Result<Option<MonitoredAutoRoom>, Error> {
+        MonitoredAutoRoom::get_by_owner_and_trigger(&self.pool, owner_id, autoroom_channel_id).await
+    }
+
+    async fn get_all(&self) -> Result<Vec<MonitoredAutoRoom>, Error> {
+        MonitoredAutoRoom::get_all(&self.pool).await
+    }
+
+    async fn get_all_channel_ids(&self) -> Result<Vec<i64>, Error> {
+        MonitoredAutoRoom::get_all_channel_ids(&self.pool).await
+    }
+
+    async fn get_uncommitted_ids(&self) -> Result<Vec<i64>, Error> {
+        MonitoredAutoRoom::get_uncommitted_ids(&self.pool).await
+    }
+
+    async fn insert_uncommitted(
+        &self,
+        channel_id: i64,
+        owner_id: i64,
+        autoroom_channel_id: i64,
+        room_number: Option<i32>
+    ) -> Result<(), Error> {
+        MonitoredAutoRoom::insert_uncommitted(&self.pool, channel_id, owner_id, autoroom_channel_id, room_number).await
+    }
+
+    async fn commit(&self, channel_id: i64) -> Result<bool, Error> {
+        MonitoredAutoRoom::commit(&self.pool, channel_id).await
+    }
+
+    async fn insert_many(&self, rooms: &[MonitoredAutoRoom]) -> Result<(), Error> {
+        MonitoredAutoRoom::insert_many(&self.pool, rooms).await
+    }
+
+    async fn set_owner(&self, channel_id: i64, owner_id: i64) -> Result<bool, Error> {
+        MonitoredAutoRoom::set_owner(&self.pool, channel_id, owner_id).await
+    }
+
+    async fn remove(&self, channel_id: i64) -> Result<bool, Error> {
+        MonitoredAutoRoom::remove(&self.pool, channel_id).await
+    }
+
+    async fn remove_many(&self, ids: &[i64]) -> Result<(), Error> {
+        MonitoredAutoRoom::remove_many(&self.pool, ids).await
+    }
+
+    async fn get_category_room_numbers(&self, category_ids: &[i64]) -> Result<Vec<i32>, Error> {
+        MonitoredAutoRoom::get_category_room_numbers(&self.pool, category_ids).await
+    }
+
+    async fn get_grace_period(&self, channel_id: i64) -> Result<i32, Error> {
+        MonitoredAutoRoom::get_grace_period(&self.pool, channel_id).await
+    }
+
+    async fn schedule_deletion(&self, channel_id: i64, delay_secs: f64) -> Result<(), Error> {
+        MonitoredAutoRoom::schedule_deletion(&self.pool, channel_id, delay_secs).await
+    }
+
+    async fn cancel_deletion(&self, channel_id: i64) -> Result<(), Error> {
+        MonitoredAutoRoom::cancel_deletion(&self.pool, channel_id).await
+    }
+
+    async fn get_pending_deletions(&self) -> Result<Vec<(i64, f64)>, Error> {
+        MonitoredAutoRoom::get_pending_deletions(&self.pool).await
+    }
+
+    async fn set_panel_message(&self, channel_id: i64, message_id: Option<i64>) -> Result<bool, Error> {
+        MonitoredAutoRoom::set_panel_message(&self.pool, channel_id, message_id).await
+    }
+
+    async fn get_panel_message(&self, channel_id: i64) -> Result<Option<i64>, Error> {
+        MonitoredAutoRoom::get_panel_message(&self.pool, channel_id).await
+    }
+}
+
+#[async_trait]
+impl PermanentRoomRepository for PgPermanentRoomRepository {
+    async fn get_by_channel_id(&self, channel_id: i64) -> Result<Option<PermamentAutoRoom>, Error> {
+        PermamentAutoRoom::get_by_channel_id(&self.pool, channel_id).await
+    }
+
+    async fn get_by_owner_id(&self, guild_id: i64, owner_id: i64) -> Result<Option<PermamentAutoRoom>, Error> {
+        PermamentAutoRoom::get_by_owner_id(&self.pool, guild_id, owner_id).await
+    }
+
+    async fn get_guild_rooms(&self, guild_id: i64) -> Result<Vec<PermamentAutoRoom>, Error> {
+        PermamentAutoRoom::get_guild_rooms(&self.pool, guild_id).await
+    }
+
+    async fn get_all(&self) -> Result<Vec<PermamentAutoRoom>, Error> {
+        PermamentAutoRoom::get_all(&self.pool).await
+    }
+
+    async fn create(&self, room: &PermamentAutoRoom) -> Result<(), &'static str> {
+        room.create(&self.pool).await
+    }
+
+    async fn set_owner(&self, channel_id: i64, owner_id: i64) -> Result<bool, Error> {
+        PermamentAutoRoom::set_owner(&self.pool, channel_id, owner_id).await
+    }
+
+    async fn remove(&self, channel_id: i64) -> Result<bool, Error> {
+        PermamentAutoRoom::remove(&self.pool, channel_id).await
+    }
+
+    async fn remove_many(&self, ids: &[i64]) -> Result<(), Error> {
+        PermamentAutoRoom::remove_many(&self.pool, ids).await
+    }
+}
+
+#[async_trait]
+impl GuildSettingsRepository for PgGuildSettingsRepository {
+    async fn get(&self, guild_id: i64) -> Result<GuildSettings, Error> {
+        GuildSettings::get(&self.pool, guild_id).await
+    }
+
+    async fn set_ownership_transfer(&self, guild_id: i64, mode: OwnershipTransferMode) -> Result<(), Error> {
+        GuildSettings::set_ownership_transfer(&self.pool, guild_id, mode).await
+    }
+
+    async fn set_alert_channel(&self, guild_id: i64, channel_id: Option<i64>) -> Result<(), Error> {
+        GuildSettings::set_alert_channel(&self.pool, guild_id, channel_id).await
+    }
+
+    async fn set_interface(&self, guild_id: i64, channel_id: Option<i64>, message_id: Option<i64>) -> Result<(), Error> {
+        GuildSettings::set_interface(&self.pool, guild_id, channel_id, message_id).await
+    }
+}
+
+#[async_trait]
+impl ChannelGuardRepository for PgChannelGuardRepository {
+    async fn record_created(&self, guild_id: i64, channel_id: i64, creator_id: Option<i64>) -> Result<(), Error> {
+        CreatedChannel::record(&self.pool, guild_id, channel_id, creator_id).await
+    }
+
+    async fn get_created(&self, guild_id: i64) -> Result<Vec<CreatedChannel>, Error> {
+        CreatedChannel::get_guild(&self.pool, guild_id).await
+    }
+
+    async fn forget_created(&self, channel_ids: &[i64]) -> Result<u64, Error> {
+        CreatedChannel::forget_many(&self.pool, channel_ids).await
+    }
+
+    async fn get_protected(&self, guild_id: i64) -> Result<Vec<ProtectedChannel>, Error> {
+        ProtectedChannel::get_guild(&self.pool, guild_id).await
+    }
+
+    async fn protect(&self, guild_id: i64, channel_id: i64) -> Result<(), &'static str> {
+        ProtectedChannel::add(&self.pool, guild_id, channel_id).await
+    }
+
+    async fn unprotect(&self, guild_id: i64, channel_id: i64) -> Result<bool, Error> {
+        ProtectedChannel::remove(&self.pool, guild_id, channel_id).await
+    }
+}
+
+#[async_trait]
+impl MemberListRepository for PgMemberListRepository {
+    async fn get_members(&self, list: MemberList, owner_id: i64) -> Result<Vec<i64>, Error> {
+        list.get(&self.pool, owner_id).await
+    }
+
+    async fn add_member(&self, list: MemberList, owner_id: i64, member_id: i64) -> Result<bool, Error> {
+        list.add(&self.pool, owner_id, member_id).await
+    }
+
+    async fn remove_member(&self, list: MemberList, owner_id: i64, member_id: i64) -> Result<bool, Error> {
+        list.remove(&self.pool, owner_id, member_id).await
+    }
+}
+
+/// Storage of autorooms and their rooms. Shared through `CommandData` and the serenity context.
+#[derive(Clone)]
+pub struct Repositories {
+    pub autorooms: Arc<dyn AutoRoomRepository>,
+    pub rooms: Arc<dyn MonitoredRoomRepository>,
+    pub permanent: Arc<dyn PermanentRoomRepository>,
+    pub settings: Arc<dyn GuildSettingsRepository>,
+    pub channels: Arc<dyn ChannelGuardRepository>,
+    pub lists: Arc<dyn MemberListRepository>,
+}
+
+impl TypeMapKey for Repositories {
+    type Value = Repositories;
+}
+
+impl Repositories {
+    pub fn postgres(pool: PgPool) -> Self {
+        Self {
+            autorooms: Arc::new(PgAutoRoomRepository { pool: pool.clone() }),
+            rooms: Arc::new(PgMonitoredRoomRepository { pool: pool.clone() }),
+            permanent: Arc::new(PgPermanentRoomRepository { pool: pool.clone() }),
+            settings: Arc::new(PgGuildSettingsRepository { pool: pool.clone() }),
+            channels: Arc::new(PgChannelGuardRepository { pool: pool.clone() }),
+            lists: Arc::new(PgMemberListRepository { pool }),
+        }
+    }
+
+    /// Repositories of the serenity context, inserted before the client starts.
+    pub async fn from_context(ctx: &Context) -> Self {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("Repositories are inserted before the client starts")
+    }
+}
+
+/// Pool of the serenity context for the tables without a repository.
+pub async fn context_pool(ctx: &Context) -> PgPool {
+    ctx.data
+        .read()
+        .await
+        .get::<SerenityPool>()
+        .cloned()
+        .expect("DB pool is inserted before the client starts")
+}
@@ -1,170 +1,819 @@
-use serenity::all::{Cache, Http};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::all::{GuildChannel, GuildId, Member, UserId};
 use serenity::model::voice::VoiceState;
 use serenity::model::id::ChannelId;
 use serenity::builder::CreateChannel;
-use serenity::client::Context;
 
-use crate::services::autoroom::grant_owner_privileges;
-use crate::services::autoroom::invite_modal::deploy_encoded_menu;
+use crate::services::autoroom::{apply_room_privacy, grant_owner_privileges};
+use crate::services::{
+    channel_cache, gateway::{is_not_found, DiscordGateway}, member_list, overflow, ownership, permanent_room, room_deletion,
+    room_panel, voice_queue
+};
+use crate::services::room_creation::{self, RoomCreationError, RoomTransaction};
+use crate::services::room_name::{lowest_free_number, render, uses_number, RoomNameValues};
 
-use super::sql::SerenityPool;
-use super::sql::autoroom::{AutoRoom, MonitoredAutoRoom};
+use super::sql::{prelude::{AutoRoom, Repositories}, repository::MonitoredRoomRepository};
 
 use super::bitrate::get_bitrate;
 
 
-pub async fn create_proccessing(ctx: &Context, new: &VoiceState) {
-    if let Some(channel_id) = new.channel_id {
+async fn trigger_channel(discord: &dyn DiscordGateway, channel_id: ChannelId) -> Option<GuildChannel> {
+    match discord.channel(channel_id).await {
+        Ok(channel) => channel,
+        Err(err) => {
+            tracing::error!("Failed to fetch trigger CHANNEL({}).\n{}", channel_id.get(), err);
+            None
+        }
+    }
+}
 
-        let data = ctx.data.read().await;
-        let pool = data.get::<SerenityPool>().expect("Failed to get DB pool");
+/// Builds the new room from the autoroom settings on top of the trigger channel, when it is used as a template.
+/// The owner overwrite is granted after creation, so it always wins over the cloned overwrites.
+fn room_builder(
+    autoroom: &AutoRoom,
+    template: Option<&GuildChannel>,
+    category_id: ChannelId,
+    name: String,
+    max_bitrate: u32
+) -> CreateChannel<'static> {
+    let bitrate = template
+        .and_then(|channel| channel.bitrate)
+        .map_or(max_bitrate, |bitrate| max_bitrate.min(bitrate));
+    let bitrate = autoroom.bitrate_cap.map_or(bitrate, |cap| bitrate.min(cap as u32));
 
-        let autoroom_result = AutoRoom::get_by_channel_id(pool, channel_id.get() as i64).await;
-        let autoroom = match autoroom_result{
-            Ok(Some(autoroom)) => {
-                autoroom
-            }
-            Ok(None) => {
-                return;
-            }
-            Err(e) => {
-                tracing::error!("Error fetching autoroom: {:?}", e);
-                return;
-            }
-        };
-            
-        if let Some(guild_id) = new.guild_id {
-            // Get max available server bitrate
-            let max_bitrate = get_bitrate(&guild_id.to_guild_cached(&ctx.cache).unwrap().premium_tier);
-
-            if let Some(member) = &new.member {
-                let user_id = member.user.id;
-                let user_name = &member.user.name;
-
-                // Создаем новый голосовой канал с именем пользователя
-                let builder = CreateChannel::new(format!("{}`s {}", user_name, autoroom.suffix))
-                    .category(ChannelId::new(autoroom.category_id as u64))
-                        .kind(serenity::model::channel::ChannelType::Voice)
-                            .bitrate(max_bitrate);
-                let channel_result = guild_id.create_channel(&ctx.http, builder).await;
-
-                if let Ok(channel) = channel_result {
-
-                     if let Err(why) = guild_id.move_member(&ctx.http, user_id, channel.id).await {
-                        tracing::error!(
-                            "Failed to move the user({:?}) to the new voice channel({:?}). Error: \"{:?}\"",
-                            &user_id.get(),
-                            &channel.id.get(),
-                            &why
-                        );
-                    }
-
-                    if let Err(_) = grant_owner_privileges(&ctx.http, &channel.id, &user_id).await {
-                        let _ = channel.delete(&ctx.http).await;
-                    }
-                    
-                    if let Err(err) = deploy_encoded_menu(
-                        ctx,
-                        channel.id,
-                        member.user.id,
-                    ).await {
-                        tracing::error!(
-                            "Failed to send_invite_user_modal.\nUser({:?}), Channel({:?})\nError: \"{:?}\"",
-                            member.user.id,
-                            channel,
-                            err
-                        )
-                    }
-
-                    MonitoredAutoRoom::new(
-                        pool, channel.id.get() as i64,
-                        user_id.get() as i64
-                    ).await;
-                    
-                }
-            }
+    let mut builder = CreateChannel::new(name)
+        .category(category_id)
+            .kind(serenity::model::channel::ChannelType::Voice)
+                .bitrate(bitrate)
+                .nsfw(autoroom.nsfw || template.is_some_and(|channel| channel.nsfw));
+
+    let user_limit = autoroom.user_limit
+        .map(|user_limit| user_limit as u32)
+        .or(template.and_then(|channel| channel.user_limit));
+    if let Some(user_limit) = user_limit {
+        builder = builder.user_limit(user_limit);
+    }
+    let rtc_region = autoroom.rtc_region
+        .clone()
+        .or(template.and_then(|channel| channel.rtc_region.clone()));
+    if let Some(rtc_region) = rtc_region {
+        builder = builder.rtc_region(rtc_region);
+    }
+    let video_quality = autoroom.video_quality
+        .map(Into::into)
+        .or(template.and_then(|channel| channel.video_quality_mode));
+    if let Some(video_quality) = video_quality {
+        builder = builder.video_quality_mode(video_quality);
+    }
+    if let Some(template) = template {
+        builder = builder.permissions(template.permission_overwrites.clone());
+    }
+    builder
+}
+
+/// Выкидывает участника из триггер-канала, чтобы он мог перезайти, когда комната не создалась
+async fn release_member(discord: &dyn DiscordGateway, guild_id: GuildId, user_id: UserId) {
+    if let Err(err) = discord.disconnect_member(guild_id, user_id).await {
+        tracing::error!("Failed to disconnect USER({}) from trigger. Error: \"{}\"", user_id.get(), err);
+    }
+}
+
+pub async fn create_proccessing(discord: &dyn DiscordGateway, repositories: &Repositories, new: &VoiceState) {
+    let (channel_id, guild_id, member) = match (new.channel_id, new.guild_id, &new.member) {
+        (Some(channel_id), Some(guild_id), Some(member)) => (channel_id, guild_id, member),
+        _ => return,
+    };
+
+    // Почти все события про посторонние каналы, их отсекает кэш без запроса в базу
+    match channel_cache::is_trigger(repositories.autorooms.as_ref(), channel_id).await {
+        Ok(true) => (),
+        Ok(false) => return,
+        Err(err) => {
+            tracing::error!("Error checking trigger CHANNEL({}): {:?}", channel_id.get(), err);
+            return;
         }
+    };
+
+    let autoroom_result = repositories.autorooms.get_by_channel_id(channel_id.get() as i64).await;
+    let autoroom = match autoroom_result{
+        Ok(Some(autoroom)) => {
+            autoroom
+        }
+        Ok(None) => {
+            return;
+        }
+        Err(e) => {
+            tracing::error!("Error fetching autoroom: {:?}", e);
+            return;
+        }
+    };
+
+    if let Err(err) = create_room(discord, repositories, guild_id, channel_id, member, &autoroom).await {
+        let count = room_creation::record_failure(&err);
+        tracing::error!(
+            "Failed to create room AUTOROOM({}) OWNER({}) FAILURE({}) COUNT({}). Error: \"{}\"",
+            autoroom.channel_id,
+            member.user.id.get(),
+            err.label(),
+            count,
+            err
+        );
+        release_member(discord, guild_id, member.user.id).await;
+    }
+}
+
+/// Создание комнаты целиком: либо все шаги прошли и запись закоммичена, либо всё откатывается
+async fn create_room(
+    discord: &dyn DiscordGateway,
+    repositories: &Repositories,
+    guild_id: GuildId,
+    trigger_id: ChannelId,
+    member: &Member,
+    autoroom: &AutoRoom
+) -> Result<(), RoomCreationError> {
+    let user_id = member.user.id;
+    let rooms = repositories.rooms.as_ref();
+
+    // Событие могло устареть, пока ждало своей очереди: участник уже ушёл из триггера
+    let current_channel_id = discord.voice_channel_id(guild_id, user_id);
+    if current_channel_id.is_some_and(|current| current != trigger_id) {
+        return Ok(());
+    }
+
+    // Повторное создание для той же пары участник/триггер возвращает его уже созданную комнату
+    if let Some(room) = rooms.get_by_owner_and_trigger(user_id.get() as i64, autoroom.channel_id).await? {
+        let room_id = ChannelId::new(room.channel_id as u64);
+        let room_exists = discord
+            .guild_channels(guild_id)
+            .is_some_and(|channels| channels.iter().any(|channel| channel.id == room_id));
+        if room_exists {
+            tracing::info!("Return OWNER({}) to the existing room CHANNEL({})", user_id.get(), room_id.get());
+            return discord
+                .move_member(guild_id, user_id, room_id)
+                .await
+                .map_err(RoomCreationError::MoveMember);
+        }
+    }
+
+    // Get max available server bitrate
+    let max_bitrate = discord
+        .premium_tier(guild_id)
+        .map(|premium_tier| get_bitrate(&premium_tier))
+        .ok_or(RoomCreationError::GuildNotCached)?;
+
+    let room_number = match uses_number(&autoroom.name_template) {
+        true => {
+            // Rooms spill over into the overflow categories, numbers are unique across all of them
+            let mut category_ids = vec![autoroom.category_id];
+            category_ids.extend(
+                repositories.autorooms.get_overflow_categories(autoroom.channel_id)
+                    .await?
+                    .iter()
+                    .map(|category| category.category_id)
+            );
+            let taken = rooms.get_category_room_numbers(&category_ids).await?;
+            Some(lowest_free_number(&taken))
+        },
+        false => None,
+    };
+    let mut name_values = RoomNameValues::from_member(discord, member, &autoroom.suffix);
+    name_values.number = room_number;
+
+    // Шаблоном служит сам триггер-канал, если это включено для автокомнаты
+    let template = match autoroom.clone_trigger {
+        true => trigger_channel(discord, trigger_id).await,
+        false => None,
+    };
+
+    // Основная категория может быть заполнена, тогда берём следующую из списка переполнения
+    let category_id = overflow::pick_category(discord, repositories, autoroom)
+        .await
+        .map_err(RoomCreationError::NoCategory)?;
+
+    // Создаем новый голосовой канал с именем по шаблону
+    let builder = room_builder(
+        autoroom,
+        template.as_ref(),
+        category_id,
+        render(&autoroom.name_template, &name_values),
+        max_bitrate
+    );
+    let channel = discord
+        .create_channel(guild_id, builder)
+        .await
+        .map_err(RoomCreationError::CreateChannel)?;
+
+    let mut transaction = RoomTransaction::default();
+    transaction.channel_created(channel.id);
+
+    let setup = async {
+        // Cleanups only ever delete channels found in this ledger
+        repositories.channels.record_created(guild_id.get() as i64, channel.id.get() as i64, Some(user_id.get() as i64)).await?;
+        rooms.insert_uncommitted(
+            channel.id.get() as i64,
+            user_id.get() as i64,
+            autoroom.channel_id,
+            room_number
+        ).await?;
+        channel_cache::set_monitored(channel.id, true);
+        transaction.row_inserted(channel.id);
+
+        grant_owner_privileges(discord, &channel.id, &user_id)
+            .await
+            .map_err(RoomCreationError::GrantOwner)?;
+        apply_room_privacy(discord, &channel, autoroom.privacy)
+            .await
+            .map_err(RoomCreationError::Privacy)?;
+        discord
+            .move_member(guild_id, user_id, channel.id)
+            .await
+            .map_err(RoomCreationError::MoveMember)?;
+        Ok(())
+    };
+    if let Err(err) = setup.await {
+        transaction.rollback(discord, rooms).await;
+        return Err(err);
     }
+    transaction.commit(discord, rooms, channel.id).await?;
+
+    // Списки доверенных и заблокированных хозяина действуют в каждой его новой комнате
+    if let Err(err) = member_list::apply_to_room(discord, repositories, guild_id, channel.id, autoroom.privacy, user_id).await {
+        tracing::error!("Failed to apply member lists CHANNEL({}) OWNER({}).\n{}", channel.id, user_id, err);
+    }
+
+    // Меню не критично для комнаты, поэтому создаётся уже после коммита.
+    // Гильдии с голосовым интерфейсом могут отключить меню в комнатах
+    if !autoroom.room_panel {
+        return Ok(());
+    }
+    if let Err(err) = room_panel::deploy(
+        discord,
+        rooms,
+        channel.id,
+        member.user.id,
+    ).await {
+        tracing::error!(
+            "Failed to send_invite_user_modal.\nUser({:?}), Channel({:?})\nError: \"{:?}\"",
+            member.user.id,
+            channel,
+            err
+        )
+    }
+    Ok(())
 }
 
-pub async fn remove_channel_by_voicestate(ctx: &Context, new: &VoiceState) -> Result<(), String> {
-    if let Some(channel_id) = new.channel_id {
-        let data = ctx.data.read().await;
-        let pool = data.get::<SerenityPool>().expect("Failed to get DB pool");
-        if !MonitoredAutoRoom::exists(pool, channel_id.get() as i64).await {
-            return Ok(());
+/// Все шаги одного события голосового состояния.
+/// События одного участника и одних каналов обрабатываются строго по очереди
+pub async fn voice_state_update(
+    discord: Arc<dyn DiscordGateway>,
+    repositories: &Repositories,
+    old: Option<&VoiceState>,
+    new: &VoiceState
+) {
+    let _guard = voice_queue::lock_voice_state(old, new).await;
+    let old_channel_id = old.and_then(|old| old.channel_id);
+    if let Some(channel_id) = new.channel_id.filter(|id| old_channel_id != Some(*id)) {
+        if let Err(err) = room_deletion::cancel(repositories.rooms.as_ref(), channel_id).await {
+            tracing::error!(err);
         };
-        
-        tracing::info!("Remove Room: {}", channel_id.get() as i64);
-
-        match channel_id.to_channel(&ctx.http).await {
-            Ok(channel) => {
-                match &channel.clone().guild().unwrap().members(&ctx.cache) {
-                    Ok(members) => {
-                            if members.len() == 0 {
-                                let _ = match channel.delete(&ctx.http).await {
-                                    Ok(_) => {
-                                        MonitoredAutoRoom::remove(pool, channel_id.get() as i64)
-                                            .await
-                                            .map_err(|err| err.to_string())?;
-                                    },
-                                    Err(err) => {
-                                        tracing::error!("Remove Room `MonitoredAutoRoom` Error: {}", err);
-                                        return Err(err.to_string())
-                                    }   
-                                };
-                            };
-                            return Ok(())
-                    },
-                    Err(err) => {
-                            tracing::error!("Remove Room `members` Error: {}", err);
-                            return Err(err.to_string())
-                    }
-                }
-            },
-            Err(err) => {
-                        tracing::error!("Remove Room `ctx.http` Error: {}", err);
-                        return Err(err.to_string())
-            }  
+    };
+    create_proccessing(discord.as_ref(), repositories, new).await;
+    if let Err(err) = permanent_room::on_join(discord.as_ref(), repositories, new).await {
+        tracing::error!(err);
+    };
+    if let Some(old) = old {
+        if let Err(err) = permanent_room::on_leave(discord.as_ref(), repositories, old).await {
+            tracing::error!(err);
+        };
+        if old.channel_id != new.channel_id {
+            if let Err(err) = ownership::on_owner_leave(discord.as_ref(), repositories, old).await {
+                tracing::error!(err);
+            };
         };
+        if let Err(err) = remove_channel_by_voicestate(discord, repositories, old).await {
+            tracing::error!(err);
+        };
+    };
+}
+
+/// Убирает комнату, из которой вышел участник, если в ней никого не осталось:
+/// сразу или по истечении grace period автокомнаты
+pub async fn remove_channel_by_voicestate(
+    discord: Arc<dyn DiscordGateway>,
+    repositories: &Repositories,
+    old: &VoiceState
+) -> Result<(), String> {
+    let (channel_id, guild_id) = match (old.channel_id, old.guild_id) {
+        (Some(channel_id), Some(guild_id)) => (channel_id, guild_id),
+        _ => return Ok(()),
+    };
+    let rooms = repositories.rooms.as_ref();
+    if !channel_cache::is_monitored(rooms, channel_id).await.map_err(|err| err.to_string())? {
+        return Ok(());
     };
 
-    Ok(())
+    tracing::info!("Remove Room: {}", channel_id.get());
+    // Без кэша гильдии не понять, пуста ли комната, её уберёт следующая сверка
+    let members = discord
+        .channel_members(guild_id, channel_id)
+        .ok_or_else(|| format!("Remove Room({}) Error: guild is not cached", channel_id.get()))?;
+    if !members.is_empty() {
+        return Ok(());
+    }
+
+    let grace_period = rooms.get_grace_period(channel_id.get() as i64)
+        .await
+        .map_err(|err| err.to_string())?;
+    if grace_period > 0 {
+        return room_deletion::schedule(discord, repositories, channel_id, Duration::from_secs(grace_period as u64)).await;
+    }
+    delete_room(discord.as_ref(), rooms, channel_id).await
 }
 
-#[allow(dead_code)]
 pub async fn remove_channel_by_id_proccessing(
-    http: &Http, cache: &Cache, channel_id: &ChannelId, pool: &sqlx::Pool<sqlx::Postgres>
+    discord: &dyn DiscordGateway, channel_id: &ChannelId, rooms: &dyn MonitoredRoomRepository
 ) -> Result<(), String> {
-    match channel_id.to_channel(http).await {
-        Ok(channel) => {
-            // if let Some(members) = &channel.members(&ctx.http).await.unwrap() {
-            match &channel.clone().guild().unwrap().members(cache) {
-                Ok(members) => {
-                    if members.len() == 0 {
-                        let _ = match channel.delete(http).await {
-                            Ok(_) => {
-                                MonitoredAutoRoom::remove(pool, channel_id.get() as i64)
-                                    .await
-                                    .map_err(|err| err.to_string())?;
-                            },
-                            Err(err) => {
-                                tracing::error!("Remove Room `MonitoredAutoRoom` Error: {}", err);
-                                return Err(err.to_string())
-                            }
-                        };
-                    };
-                },
-                Err(err) => {
-                    tracing::error!("Remove Room `members` Error: {}", err);
-                    return Err(err.to_string())
-                }
-        }},
-            Err(err) => {
-                tracing::error!("Remove Room `ctx.http` Error: {}", err);
-                return Err(err.to_string())
-            }
+    let channel = match discord.channel(*channel_id).await {
+        Ok(Some(channel)) => channel,
+        // Канал удалили вручную, осталась только запись
+        Ok(None) => return forget_room(rooms, *channel_id).await,
+        Err(err) => {
+            tracing::error!("Remove Room `channel` Error: {}", err);
+            return Err(err.to_string());
+        }
+    };
+
+    let members = discord
+        .channel_members(channel.guild_id, channel.id)
+        .ok_or_else(|| format!("Remove Room({}) Error: guild is not cached", channel_id.get()))?;
+    if !members.is_empty() {
+        return Ok(());
     }
+    delete_room(discord, rooms, channel.id).await
+}
 
+/// Удаляет канал комнаты вместе с записью. Канал, которого уже нет, не мешает убрать запись
+async fn delete_room(discord: &dyn DiscordGateway, rooms: &dyn MonitoredRoomRepository, channel_id: ChannelId) -> Result<(), String> {
+    match discord.delete_channel(channel_id).await {
+        Ok(_) => (),
+        Err(err) if is_not_found(&err) => tracing::warn!("Remove Room({}) channel is already gone", channel_id.get()),
+        Err(err) => {
+            tracing::error!("Remove Room `MonitoredAutoRoom` Error: {}", err);
+            return Err(err.to_string());
+        }
+    };
+    forget_room(rooms, channel_id).await
+}
+
+async fn forget_room(rooms: &dyn MonitoredRoomRepository, channel_id: ChannelId) -> Result<(), String> {
+    rooms.remove(channel_id.get() as i64)
+        .await
+        .map_err(|err| err.to_string())?;
+    channel_cache::set_monitored(channel_id, false);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::{EditChannel, MessageId, PermissionOverwrite, PermissionOverwriteType, Permissions, RoleId, VideoQualityMode};
+
+    use super::*;
+    use crate::services::fake_discord::{autoroom, next_id, voice_state, FakeCall, FakeDiscord};
+    use crate::services::overflow::{CATEGORY_CHANNEL_LIMIT, GUILD_CHANNEL_LIMIT};
+
+
+    struct Scenario {
+        discord: Arc<FakeDiscord>,
+        repositories: Repositories,
+        guild_id: GuildId,
+        trigger_id: ChannelId,
+        category_id: ChannelId,
+        user_id: UserId,
+    }
+
+    async fn scenario() -> Scenario {
+        let discord = Arc::new(FakeDiscord::new());
+        let repositories = Repositories::in_memory();
+        let guild_id = discord.add_guild();
+        let category_id = discord.add_category(guild_id, "Rooms");
+        let trigger_id = discord.add_voice_channel(guild_id, None, "Create room");
+        repositories.autorooms.create(&autoroom(guild_id, trigger_id, category_id)).await.unwrap();
+
+        let user_id = UserId::new(next_id());
+        discord.connect(guild_id, user_id, trigger_id);
+        Scenario { discord, repositories, guild_id, trigger_id, category_id, user_id }
+    }
+
+    impl Scenario {
+        /// Turns the trigger into a template: 64 kbps, 5 members, Rotterdam, full video,
+        /// age-restricted and a role that can't speak.
+        async fn shape_trigger(&self, role_id: RoleId) {
+            let trigger = EditChannel::new()
+                .bitrate(64_000)
+                .user_limit(5)
+                .voice_region(Some("rotterdam".to_string()))
+                .video_quality_mode(VideoQualityMode::Full)
+                .nsfw(true)
+                .permissions(vec![PermissionOverwrite {
+                    allow: Permissions::empty(),
+                    deny: Permissions::SPEAK,
+                    kind: PermissionOverwriteType::Role(role_id),
+                }]);
+            self.discord.edit_channel(self.trigger_id, trigger).await.unwrap();
+        }
+
+        async fn update_autoroom(&self, edit: impl FnOnce(&mut AutoRoom)) {
+            let mut autoroom = autoroom(self.guild_id, self.trigger_id, self.category_id);
+            edit(&mut autoroom);
+            self.repositories.autorooms.update(&autoroom).await.unwrap();
+        }
+
+        /// Adds voice channels until the category holds `count` of them, `None` fills the guild instead.
+        fn fill(&self, parent_id: Option<ChannelId>, count: usize) {
+            for _ in 0..count {
+                self.discord.add_voice_channel(self.guild_id, parent_id, "Busy");
+            }
+        }
+
+        async fn join_as(&self, user_id: UserId) {
+            self.discord.connect(self.guild_id, user_id, self.trigger_id);
+            let state = voice_state(self.guild_id, user_id, "bob", self.trigger_id);
+            create_proccessing(self.discord.as_ref(), &self.repositories, &state).await;
+        }
+
+        async fn join_trigger(&self) {
+            let state = voice_state(self.guild_id, self.user_id, "alice", self.trigger_id);
+            create_proccessing(self.discord.as_ref(), &self.repositories, &state).await;
+        }
+
+        /// Replays a burst of voice events like the gateway does: the guild is updated on receive
+        /// and the handlers run concurrently, so they meet at every request to Discord.
+        async fn replay(&self, events: &[(UserId, Option<ChannelId>, Option<ChannelId>)]) {
+            let mut tasks = Vec::new();
+            for (user_id, old, new) in events.iter().copied() {
+                match new {
+                    Some(channel_id) => self.discord.connect(self.guild_id, user_id, channel_id),
+                    None => self.discord.disconnect(self.guild_id, user_id),
+                };
+                let old = old.map(|channel_id| voice_state(self.guild_id, user_id, "alice", channel_id));
+                let mut new_state = voice_state(self.guild_id, user_id, "alice", new.unwrap_or(self.trigger_id));
+                new_state.channel_id = new;
+
+                let discord: Arc<dyn DiscordGateway> = self.discord.clone();
+                let repositories = self.repositories.clone();
+                tasks.push(tokio::spawn(async move {
+                    voice_state_update(discord, &repositories, old.as_ref(), &new_state).await;
+                }));
+            }
+            for task in tasks {
+                task.await.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn creates_room_and_moves_owner() {
+        let scenario = scenario().await;
+        scenario.join_trigger().await;
+
+        let rooms = scenario.discord.children(scenario.category_id);
+        assert_eq!(rooms.len(), 1);
+        let room = &rooms[0];
+        assert_eq!(room.name, "alice`s room");
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), Some(room.id));
+
+        let owner = scenario.discord.member_overwrite(room.id, scenario.user_id).unwrap();
+        assert!(owner.allow.contains(Permissions::MANAGE_CHANNELS | Permissions::CONNECT));
+
+        let row = scenario.repositories.rooms
+            .get_by_owner_and_trigger(scenario.user_id.get() as i64, scenario.trigger_id.get() as i64)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.channel_id, room.id.get() as i64);
+        let created = scenario.repositories.channels.get_created(scenario.guild_id.get() as i64).await.unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].channel_id, room.id.get() as i64);
+        assert_eq!(created[0].creator_id, Some(scenario.user_id.get() as i64));
+        let panel_id = scenario.repositories.rooms.get_panel_message(room.id.get() as i64).await.unwrap().unwrap();
+        let panel = scenario.discord.message(MessageId::new(panel_id as u64)).unwrap();
+        assert_eq!(panel["embeds"][0]["fields"][0]["value"], format!("<@{}>", scenario.user_id));
+    }
+
+    #[tokio::test]
+    async fn failed_move_rolls_back_room() {
+        let scenario = scenario().await;
+        scenario.discord.fail(FakeCall::MoveMember);
+        scenario.join_trigger().await;
+
+        assert!(scenario.discord.children(scenario.category_id).is_empty());
+        assert!(scenario.repositories.rooms.get_all().await.unwrap().is_empty());
+        assert!(scenario.repositories.rooms.get_uncommitted_ids().await.unwrap().is_empty());
+        // The member is released from the trigger to retry
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), None);
+    }
+
+    #[tokio::test]
+    async fn returns_owner_to_existing_room() {
+        let scenario = scenario().await;
+        scenario.join_trigger().await;
+        let room_id = scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id).unwrap();
+
+        scenario.discord.connect(scenario.guild_id, scenario.user_id, scenario.trigger_id);
+        scenario.join_trigger().await;
+
+        assert_eq!(scenario.discord.children(scenario.category_id).len(), 1);
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), Some(room_id));
+    }
+
+    #[tokio::test]
+    async fn locked_room_denies_everyone() {
+        let scenario = scenario().await;
+        let mut locked = autoroom(scenario.guild_id, scenario.trigger_id, scenario.category_id);
+        locked.privacy = crate::sql::autoroom::RoomPrivacy::Locked;
+        scenario.repositories.autorooms.update(&locked).await.unwrap();
+        scenario.join_trigger().await;
+
+        let room = scenario.discord.children(scenario.category_id).remove(0);
+        let everyone = room.permission_overwrites
+            .iter()
+            .find(|overwrite| overwrite.kind == PermissionOverwriteType::Role(serenity::all::RoleId::new(scenario.guild_id.get())))
+            .unwrap();
+        assert!(everyone.deny.contains(Permissions::CONNECT));
+    }
+
+    #[tokio::test]
+    async fn hidden_room_stays_visible_to_bot() {
+        let scenario = scenario().await;
+        scenario.update_autoroom(|autoroom| autoroom.privacy = crate::sql::autoroom::RoomPrivacy::Hidden).await;
+        scenario.join_trigger().await;
+
+        let room = scenario.discord.children(scenario.category_id).remove(0);
+        let bot = scenario.discord.member_overwrite(room.id, scenario.discord.current_user_id()).unwrap();
+        assert!(bot.allow.contains(Permissions::VIEW_CHANNEL | Permissions::CONNECT | Permissions::MANAGE_CHANNELS | Permissions::MOVE_MEMBERS));
+        // The panel still reaches the room and the owner is still found by the overwrite
+        assert_eq!(scenario.discord.messages(room.id).len(), 1);
+        let room = scenario.discord.get(room.id).unwrap();
+        assert_eq!(crate::services::autoroom::owner_from_overwrites(&room, scenario.discord.current_user_id()), Some(scenario.user_id));
+    }
+
+    #[tokio::test]
+    async fn room_panel_can_be_turned_off() {
+        let scenario = scenario().await;
+        let mut quiet = autoroom(scenario.guild_id, scenario.trigger_id, scenario.category_id);
+        quiet.room_panel = false;
+        scenario.repositories.autorooms.update(&quiet).await.unwrap();
+        scenario.join_trigger().await;
+
+        let room = scenario.discord.children(scenario.category_id).remove(0);
+        assert!(scenario.discord.messages(room.id).is_empty());
+        assert_eq!(scenario.repositories.rooms.get_panel_message(room.id.get() as i64).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn new_room_invites_trusted_members() {
+        let scenario = scenario().await;
+        let friend_id = UserId::new(next_id());
+        scenario.repositories.lists
+            .add_member(crate::sql::member_list::MemberList::Trusted, scenario.user_id.get() as i64, friend_id.get() as i64)
+            .await
+            .unwrap();
+        scenario.join_trigger().await;
+
+        let room = scenario.discord.children(scenario.category_id).remove(0);
+        assert!(scenario.discord.member_overwrite(room.id, friend_id).unwrap().allow.contains(Permissions::VIEW_CHANNEL));
+    }
+
+    #[tokio::test]
+    async fn cloned_trigger_is_room_template() {
+        let scenario = scenario().await;
+        let role_id = RoleId::new(next_id());
+        scenario.shape_trigger(role_id).await;
+        scenario.update_autoroom(|autoroom| autoroom.clone_trigger = true).await;
+        scenario.join_trigger().await;
+
+        let room = scenario.discord.children(scenario.category_id).remove(0);
+        assert_eq!(room.bitrate, Some(64_000));
+        assert_eq!(room.user_limit, Some(5));
+        assert_eq!(room.rtc_region.as_deref(), Some("rotterdam"));
+        assert_eq!(room.video_quality_mode, Some(VideoQualityMode::Full));
+        assert!(room.nsfw);
+        let role = room.permission_overwrites
+            .iter()
+            .find(|overwrite| overwrite.kind == PermissionOverwriteType::Role(role_id))
+            .unwrap();
+        assert_eq!(role.deny, Permissions::SPEAK);
+        // The owner overwrite is granted on top of the cloned ones
+        let owner = scenario.discord.member_overwrite(room.id, scenario.user_id).unwrap();
+        assert!(owner.allow.contains(Permissions::MANAGE_CHANNELS | Permissions::CONNECT));
+    }
+
+    #[tokio::test]
+    async fn autoroom_settings_override_cloned_trigger() {
+        let scenario = scenario().await;
+        scenario.shape_trigger(RoleId::new(next_id())).await;
+        scenario.update_autoroom(|autoroom| {
+            autoroom.clone_trigger = true;
+            autoroom.user_limit = Some(2);
+            autoroom.bitrate_cap = Some(32_000);
+            autoroom.rtc_region = Some("japan".to_string());
+            autoroom.video_quality = Some(crate::sql::autoroom::RoomVideoQuality::Auto);
+        }).await;
+        scenario.join_trigger().await;
+
+        let room = scenario.discord.children(scenario.category_id).remove(0);
+        assert_eq!(room.bitrate, Some(32_000));
+        assert_eq!(room.user_limit, Some(2));
+        assert_eq!(room.rtc_region.as_deref(), Some("japan"));
+        assert_eq!(room.video_quality_mode, Some(VideoQualityMode::Auto));
+        // Age restriction of the trigger can't be turned off by the autoroom
+        assert!(room.nsfw);
+    }
+
+    #[tokio::test]
+    async fn trigger_is_not_cloned_by_default() {
+        let scenario = scenario().await;
+        let role_id = RoleId::new(next_id());
+        scenario.shape_trigger(role_id).await;
+        scenario.join_trigger().await;
+
+        let room = scenario.discord.children(scenario.category_id).remove(0);
+        assert_eq!(room.bitrate, Some(96_000));
+        assert_eq!(room.user_limit, None);
+        assert_eq!(room.rtc_region, None);
+        assert!(!room.nsfw);
+        assert!(!room.permission_overwrites.iter().any(|overwrite| overwrite.kind == PermissionOverwriteType::Role(role_id)));
+    }
+
+    #[tokio::test]
+    async fn full_category_sends_room_to_overflow() {
+        let scenario = scenario().await;
+        scenario.fill(Some(scenario.category_id), CATEGORY_CHANNEL_LIMIT);
+        let overflow_id = scenario.discord.add_category(scenario.guild_id, "More rooms");
+        scenario.repositories.autorooms
+            .push_overflow_category(scenario.trigger_id.get() as i64, overflow_id.get() as i64, false)
+            .await
+            .unwrap();
+        scenario.join_trigger().await;
+
+        assert_eq!(scenario.discord.children(scenario.category_id).len(), CATEGORY_CHANNEL_LIMIT);
+        let rooms = scenario.discord.children(overflow_id);
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), Some(rooms[0].id));
+    }
+
+    #[tokio::test]
+    async fn full_categories_get_numbered_category() {
+        let scenario = scenario().await;
+        scenario.fill(Some(scenario.category_id), CATEGORY_CHANNEL_LIMIT);
+        scenario.join_trigger().await;
+
+        let overflow = scenario.repositories.autorooms.get_overflow_categories(scenario.trigger_id.get() as i64).await.unwrap();
+        assert_eq!(overflow.len(), 1);
+        assert!(overflow[0].created_by_bot);
+        let overflow_id = ChannelId::new(overflow[0].category_id as u64);
+        assert_eq!(scenario.discord.get(overflow_id).unwrap().name, "Rooms 2");
+        let created = scenario.repositories.channels.get_created(scenario.guild_id.get() as i64).await.unwrap();
+        assert!(created.iter().any(|channel| channel.channel_id == overflow_id.get() as i64));
+
+        // The next room goes to the same category instead of a third one
+        scenario.join_as(UserId::new(next_id())).await;
+        assert_eq!(scenario.discord.children(overflow_id).len(), 2);
+        assert_eq!(scenario.repositories.autorooms.get_overflow_categories(scenario.trigger_id.get() as i64).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn room_numbers_span_overflow_categories() {
+        let scenario = scenario().await;
+        let overflow_id = scenario.discord.add_category(scenario.guild_id, "More rooms");
+        scenario.repositories.autorooms
+            .push_overflow_category(scenario.trigger_id.get() as i64, overflow_id.get() as i64, false)
+            .await
+            .unwrap();
+        scenario.update_autoroom(|autoroom| autoroom.name_template = "Room {n}".to_string()).await;
+        // Another autoroom places its rooms straight into the overflow category
+        let other_trigger_id = scenario.discord.add_voice_channel(scenario.guild_id, None, "Create another room");
+        let mut other = autoroom(scenario.guild_id, other_trigger_id, overflow_id);
+        other.name_template = "Room {n}".to_string();
+        scenario.repositories.autorooms.create(&other).await.unwrap();
+        let other_owner_id = UserId::new(next_id());
+        scenario.discord.connect(scenario.guild_id, other_owner_id, other_trigger_id);
+        let state = voice_state(scenario.guild_id, other_owner_id, "bob", other_trigger_id);
+        create_proccessing(scenario.discord.as_ref(), &scenario.repositories, &state).await;
+
+        scenario.join_trigger().await;
+        scenario.fill(Some(scenario.category_id), CATEGORY_CHANNEL_LIMIT - 1);
+        scenario.join_as(UserId::new(next_id())).await;
+
+        let names = |category_id| {
+            let mut names: Vec<String> = scenario.discord.children(category_id)
+                .into_iter()
+                .map(|channel| channel.name)
+                .filter(|name| name.starts_with("Room "))
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(scenario.category_id), vec!["Room 2"]);
+        assert_eq!(names(overflow_id), vec!["Room 1", "Room 3"]);
+    }
+
+    #[tokio::test]
+    async fn channel_limit_releases_member_and_alerts() {
+        let scenario = scenario().await;
+        let alert_id = scenario.discord.add_text_channel(scenario.guild_id, "alerts");
+        scenario.repositories.settings
+            .set_alert_channel(scenario.guild_id.get() as i64, Some(alert_id.get() as i64))
+            .await
+            .unwrap();
+        scenario.fill(Some(scenario.category_id), CATEGORY_CHANNEL_LIMIT);
+        // One channel short of the limit: no room for a new category and the room
+        let total = scenario.discord.guild_channels(scenario.guild_id).unwrap().len();
+        scenario.fill(None, GUILD_CHANNEL_LIMIT - 1 - total);
+        scenario.join_trigger().await;
+
+        assert!(scenario.repositories.rooms.get_all().await.unwrap().is_empty());
+        assert!(scenario.repositories.autorooms.get_overflow_categories(scenario.trigger_id.get() as i64).await.unwrap().is_empty());
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), None);
+        let alerts = scenario.discord.messages(alert_id);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("499 of 500"));
+    }
+
+    #[tokio::test]
+    async fn ignores_other_channels() {
+        let scenario = scenario().await;
+        let lobby_id = scenario.discord.add_voice_channel(scenario.guild_id, None, "Lobby");
+        scenario.discord.connect(scenario.guild_id, scenario.user_id, lobby_id);
+        let state = voice_state(scenario.guild_id, scenario.user_id, "alice", lobby_id);
+        create_proccessing(scenario.discord.as_ref(), &scenario.repositories, &state).await;
+
+        assert!(scenario.discord.children(scenario.category_id).is_empty());
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), Some(lobby_id));
+    }
+
+    #[tokio::test]
+    async fn double_join_creates_one_room() {
+        let scenario = scenario().await;
+        let join = (scenario.user_id, None, Some(scenario.trigger_id));
+        scenario.replay(&[join, join]).await;
+
+        let rooms = scenario.discord.children(scenario.category_id);
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), Some(rooms[0].id));
+    }
+
+    #[tokio::test]
+    async fn quick_rejoin_creates_one_room() {
+        let scenario = scenario().await;
+        let lobby_id = scenario.discord.add_voice_channel(scenario.guild_id, None, "Lobby");
+        scenario.replay(&[
+            (scenario.user_id, None, Some(scenario.trigger_id)),
+            (scenario.user_id, Some(scenario.trigger_id), Some(lobby_id)),
+            (scenario.user_id, Some(lobby_id), Some(scenario.trigger_id)),
+        ]).await;
+
+        let rooms = scenario.discord.children(scenario.category_id);
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), Some(rooms[0].id));
+        assert_eq!(scenario.repositories.rooms.get_all().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn room_kept_while_owner_moves_back() {
+        let scenario = scenario().await;
+        scenario.join_trigger().await;
+        let room_id = scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id).unwrap();
+        let guest_id = UserId::new(next_id());
+        scenario.discord.connect(scenario.guild_id, guest_id, room_id);
+
+        // The owner goes back to the trigger while the last guest leaves the room
+        scenario.replay(&[
+            (scenario.user_id, Some(room_id), Some(scenario.trigger_id)),
+            (guest_id, Some(room_id), None),
+        ]).await;
+
+        assert!(scenario.discord.get(room_id).is_some());
+        assert_eq!(scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id), Some(room_id));
+        assert!(scenario.repositories.rooms.get_by_channel_id(room_id.get() as i64).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn last_member_leaving_deletes_room() {
+        let scenario = scenario().await;
+        scenario.join_trigger().await;
+        let room_id = scenario.discord.voice_channel_id(scenario.guild_id, scenario.user_id).unwrap();
+        let guest_id = UserId::new(next_id());
+        scenario.discord.connect(scenario.guild_id, guest_id, room_id);
+
+        scenario.replay(&[
+            (guest_id, Some(room_id), None),
+            (scenario.user_id, Some(room_id), None),
+        ]).await;
+
+        assert!(scenario.discord.get(room_id).is_none());
+        assert!(scenario.repositories.rooms.get_all().await.unwrap().is_empty());
+    }
+}